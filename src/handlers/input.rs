@@ -1,3 +1,5 @@
+use std::time::{Duration, Instant};
+
 use smithay::{
     backend::{
         input::{
@@ -12,7 +14,7 @@ use smithay::{
     input::{
         keyboard::{
             keysyms::{KEY_XF86Switch_VT_1, KEY_XF86Switch_VT_12},
-            FilterResult, Keysym,
+            FilterResult, Keysym, XkbConfig,
         },
         pointer::{
             AxisFrame, ButtonEvent, GestureHoldBeginEvent, GestureHoldEndEvent,
@@ -22,22 +24,45 @@ use smithay::{
         },
         touch::{DownEvent, UpEvent},
     },
-    reexports::wayland_server::protocol::wl_pointer,
-    utils::{Logical, Point},
+    reexports::{
+        calloop::timer::{TimeoutAction, Timer},
+        wayland_server::protocol::wl_pointer,
+    },
+    utils::{Logical, Point, Serial},
     wayland::{
         compositor,
-        keyboard_shortcuts_inhibit::KeyboardShortcutsInhibitorSeat,
-        seat::WaylandFocus,
+        keyboard_shortcuts_inhibit::{KeyboardShortcutsInhibitor, KeyboardShortcutsInhibitorSeat},
         shell::wlr_layer::{self, KeyboardInteractivity, LayerSurfaceCachedState},
         tablet_manager::{TabletDescriptor, TabletSeatTrait},
     },
 };
 
 use crate::{handlers::input, state::State, SERIAL_COUNTER};
-use crate::{utils::action::Action, utils::config::parse_keybind};
+use crate::{
+    utils::action::{Action, Direction},
+    utils::config::{
+        modifiers_match, parse_buttonbind, parse_keybind, AccelProfile, ModScrollAction,
+    },
+    utils::workspaces::is_fullscreen,
+};
+
+/// Distance a bound swipe has to travel before its action runs.
+const SWIPE_THRESHOLD: f64 = 100.0;
+
+/// Longest press of Super that still counts as a tap.
+const MOD_TAP_TIMEOUT: Duration = Duration::from_millis(300);
 
 impl State {
     pub fn process_input_event(&mut self, event: InputEvent<LibinputInputBackend>) {
+        // Releasing the keys of a `dpmsoff` bind mustn't wake the outputs again
+        let activity = match &event {
+            InputEvent::DeviceAdded { .. } | InputEvent::DeviceRemoved { .. } => false,
+            InputEvent::Keyboard { event } => event.state() == KeyState::Pressed,
+            _ => true,
+        };
+        if activity {
+            self.notify_activity();
+        }
         match event {
             InputEvent::Keyboard { event } => {
                 let keycode = event.key_code();
@@ -47,6 +72,7 @@ impl State {
                 let time = Event::time_msec(&event);
                 let press_state = event.state();
                 let keyboard = self.seat.get_keyboard().unwrap();
+                self.stop_key_repeat();
 
                 for layer in self.layer_shell_state.layer_surfaces().rev() {
                     let exclusive = compositor::with_states(&layer.wl_surface(), |states| {
@@ -79,66 +105,124 @@ impl State {
                 }
 
                 let inhibited = self
-                    .workspaces
-                    .get_current()
-                    .space
-                    .element_under(self.pointer.current_location())
-                    .and_then(|(window, _)| {
-                        let surface = window.wl_surface()?;
-                        self.seat.keyboard_shortcuts_inhibitor_for_surface(&surface)
-                    })
+                    .focused_inhibitor()
                     .map(|inhibitor| inhibitor.is_active())
                     .unwrap_or(false);
 
-                let action = self.seat.get_keyboard().unwrap().input::<Action, _>(
-                    self,
-                    keycode,
-                    press_state,
-                    0.into(),
-                    0,
-                    |state, modifiers, handle| {
-                        // Get representation of what key was pressed.
-                        if press_state == KeyState::Pressed {
-                            if !inhibited {
-                                let raw_syms = {
-                                    let xkb = handle.xkb().lock().unwrap();
-                                    let mut raws = Vec::<Keysym>::new();
-                                    for layout in xkb.layouts() {
-                                        raws.extend(xkb.raw_syms_for_key_in_layout(keycode, layout))
-                                    }
-                                    raws
-                                };
+                let mut mod_tapped = false;
+                let mut escaped = false;
+                let action = self
+                    .seat
+                    .get_keyboard()
+                    .unwrap()
+                    .input::<Option<Action>, _>(
+                        self,
+                        keycode,
+                        press_state,
+                        serial,
+                        time,
+                        |state, modifiers, handle| {
+                            // Super pressed and released on its own is a tap
+                            if matches!(handle.modified_sym(), Keysym::Super_L | Keysym::Super_R) {
+                                if press_state == KeyState::Pressed {
+                                    state.mod_tap_start = Some(Instant::now());
+                                } else if let Some(start) = state.mod_tap_start.take() {
+                                    mod_tapped = start.elapsed() < MOD_TAP_TIMEOUT;
+                                }
+                            } else if press_state == KeyState::Pressed {
+                                state.mod_tap_start = None;
+                            }
 
-                                for (keymap, action) in &state.config.keymaps {
-                                    if let Some((config_modifiers, config_keysyms)) =
-                                        parse_keybind(&keymap)
-                                    {
-                                        if (modifiers.logo == config_modifiers.logo
-                                            && modifiers.shift == config_modifiers.shift
-                                            && modifiers.ctrl == config_modifiers.ctrl
-                                            && modifiers.alt == config_modifiers.alt)
-                                            && raw_syms.contains(&config_keysyms)
-                                        {
-                                            return FilterResult::Intercept(action.clone());
-                                        }
-                                    }
+                            let raw_syms = if press_state == KeyState::Pressed {
+                                let xkb = handle.xkb().lock().unwrap();
+                                let mut raws = Vec::<Keysym>::new();
+                                for layout in xkb.layouts() {
+                                    raws.extend(xkb.raw_syms_for_key_in_layout(keycode, layout))
                                 }
-                                if (KEY_XF86Switch_VT_1..=KEY_XF86Switch_VT_12)
-                                    .contains(&handle.modified_sym().raw())
+                                raws
+                            } else {
+                                Vec::new()
+                            };
+
+                            // The lock surface gets every key but VT switches
+                            if state.is_locked() {
+                                if press_state == KeyState::Pressed
+                                    && (KEY_XF86Switch_VT_1..=KEY_XF86Switch_VT_12)
+                                        .contains(&handle.modified_sym().raw())
                                 {
-                                    // VTSwitch
                                     let vt = (handle.modified_sym().raw() - KEY_XF86Switch_VT_1 + 1)
                                         as i32;
-                                    return FilterResult::Intercept(Action::VTSwitch(vt));
+                                    return FilterResult::Intercept(Some(Action::VTSwitch { vt }));
                                 }
+                                return FilterResult::Forward;
                             }
-                        }
-                        FilterResult::Forward
-                    },
-                );
-                if let Some(action) = action {
+
+                            // The escape chord goes past inhibitors and overlays
+                            if let Some((config_modifiers, config_keysym)) =
+                                parse_keybind(&state.config.escape_chord)
+                            {
+                                if modifiers_match(modifiers, &config_modifiers)
+                                    && raw_syms.contains(&config_keysym)
+                                {
+                                    escaped = true;
+                                    return FilterResult::Intercept(None);
+                                }
+                            }
+
+                            // Overlays take every key while they are open
+                            if state.overlay.is_some() {
+                                if press_state == KeyState::Pressed {
+                                    state.overlay_key(handle.modified_sym());
+                                }
+                                return FilterResult::Intercept(None);
+                            }
+
+                            // Get representation of what key was pressed.
+                            if press_state == KeyState::Pressed {
+                                if !inhibited {
+                                    if let Some(action) =
+                                        state.config.key_action(modifiers, &raw_syms)
+                                    {
+                                        return FilterResult::Intercept(Some(action.clone()));
+                                    }
+                                    if (KEY_XF86Switch_VT_1..=KEY_XF86Switch_VT_12)
+                                        .contains(&handle.modified_sym().raw())
+                                    {
+                                        // VTSwitch
+                                        let vt = (handle.modified_sym().raw() - KEY_XF86Switch_VT_1
+                                            + 1)
+                                            as i32;
+                                        return FilterResult::Intercept(Some(Action::VTSwitch {
+                                            vt,
+                                        }));
+                                    }
+                                }
+                            }
+                            FilterResult::Forward
+                        },
+                    );
+                if escaped {
+                    self.escape_fullscreen(serial, time);
+                    return;
+                }
+                if let Some(Some(action)) = action.filter(|_| !self.input_locked()) {
+                    if action.repeats() {
+                        self.start_key_repeat(action.clone());
+                    }
                     action.execute(self);
                 }
+                if mod_tapped {
+                    if let Some(action) = self.config.on_mod_tap.clone() {
+                        action.execute(self);
+                    }
+                }
+                // Releasing the modifiers ends a window cycle
+                if press_state == KeyState::Released && self.window_cycle.is_some() {
+                    let modifiers = self.seat.get_keyboard().unwrap().modifier_state();
+                    if !(modifiers.ctrl || modifiers.alt || modifiers.shift || modifiers.logo) {
+                        self.end_window_cycle();
+                    }
+                }
             }
 
             InputEvent::PointerMotionAbsolute { event } => {
@@ -153,6 +237,8 @@ impl State {
 
                 if let Some(ptr) = self.seat.get_pointer() {
                     self.pointer_location = self.clamp_coords(pos);
+                    self.update_autohide();
+                    self.update_drag();
 
                     let under = self.surface_under();
                     if !ptr.is_grabbed() {
@@ -163,7 +249,7 @@ impl State {
                         self,
                         under, // (Option<(WlSurface, Point<f64, Logical>)>)
                         &MotionEvent {
-                            location: pos,
+                            location: self.pointer_location,
                             serial,
                             time: event.time_msec(),
                         },
@@ -176,6 +262,8 @@ impl State {
                 let delta = (event.delta_x(), event.delta_y()).into();
                 self.pointer_location += delta;
                 self.pointer_location = self.clamp_coords(self.pointer_location);
+                self.update_autohide();
+                self.update_drag();
                 let under = self.surface_under();
 
                 if let Some(ptr) = self.seat.get_pointer() {
@@ -211,8 +299,46 @@ impl State {
 
                 let button = event.button_code();
                 let state = wl_pointer::ButtonState::from(event.state());
+                // Super+click is a chord, not a tap
+                self.mod_tap_start = None;
+
+                // Releases still go out so clients don't see a stuck button
+                if state == wl_pointer::ButtonState::Pressed && self.input_locked() {
+                    return;
+                }
+
+                // A locked session only has the lock surface to click on
+                let locked = self.is_locked();
+
+                // Bound buttons never reach clients, neither the press nor its release
+                if state == wl_pointer::ButtonState::Released {
+                    if let Some(index) = self.suppressed_buttons.iter().position(|b| *b == button) {
+                        self.suppressed_buttons.remove(index);
+                        // A grab started by the bind still waits for the release
+                        if !pointer.is_grabbed() {
+                            return;
+                        }
+                    }
+                } else if let Some(action) = self.button_bind(button).filter(|_| !locked) {
+                    self.suppressed_buttons.push(button);
+                    action.execute(self);
+                    return;
+                }
 
-                if state == wl_pointer::ButtonState::Pressed {
+                if state == wl_pointer::ButtonState::Pressed
+                    && !locked
+                    && self.focus_tab_under_pointer()
+                {
+                    return;
+                }
+                if state == wl_pointer::ButtonState::Pressed
+                    && !locked
+                    && button == 272
+                    && self.titlebar_click(button, serial)
+                {
+                    return;
+                }
+                if state == wl_pointer::ButtonState::Pressed && !locked {
                     if button == 272 {
                         self.init_pointer_resize_grab(button, serial);
                     }
@@ -233,6 +359,11 @@ impl State {
                 pointer.frame(self);
             }
             InputEvent::PointerAxis { event } => {
+                // Super+scroll is a chord too
+                self.mod_tap_start = None;
+                if self.input_locked() {
+                    return;
+                }
                 let horizontal_amount =
                     event.amount(input::Axis::Horizontal).unwrap_or_else(|| {
                         event.amount_v120(input::Axis::Horizontal).unwrap_or(0.0) * 3.0
@@ -243,6 +374,15 @@ impl State {
                 let horizontal_amount_discrete = event.amount_v120(input::Axis::Horizontal);
                 let vertical_amount_discrete = event.amount_v120(input::Axis::Vertical);
 
+                let logo = self.seat.get_keyboard().unwrap().modifier_state().logo;
+                if logo && self.config.mod_scroll_action == Some(ModScrollAction::Workspace) {
+                    // Continuous sources report roughly 15 units per wheel notch
+                    let delta = vertical_amount_discrete.unwrap_or(vertical_amount * 8.0);
+                    self.scroll_workspaces(delta);
+                    return;
+                }
+                self.scroll_accumulator = 0.0;
+
                 {
                     let mut frame = AxisFrame::new(event.time_msec()).source(event.source());
                     if horizontal_amount != 0.0 {
@@ -280,11 +420,17 @@ impl State {
                 {
                     self.seat.add_touch();
                 }
+                if device.has_capability(DeviceCapability::Keyboard.into()) {
+                    self.keyboards.push(device.clone());
+                }
                 device.config_tap_set_enabled(true).ok();
                 device.config_tap_set_drag_enabled(true).ok();
+                self.configure_input_device(&mut device);
             }
 
             InputEvent::DeviceRemoved { device } => {
+                self.keyboards.retain(|keyboard| keyboard != &device);
+
                 if device.has_capability(DeviceCapability::TabletTool.into()) {
                     let tablet_seat = self.seat.tablet_seat();
 
@@ -375,6 +521,15 @@ impl State {
 
             // GesturesInput
             InputEvent::GestureSwipeBegin { event } => {
+                if self
+                    .config
+                    .gestures
+                    .iter()
+                    .any(|gesture| gesture.fingers == event.fingers())
+                {
+                    self.swipe = Some((event.fingers(), event.time_msec(), Point::default()));
+                    return;
+                }
                 let serial = SERIAL_COUNTER.next_serial();
                 let pointer = self.pointer.clone();
                 pointer.gesture_swipe_begin(
@@ -387,6 +542,20 @@ impl State {
                 );
             }
             InputEvent::GestureSwipeUpdate { event } => {
+                if let Some((fingers, _, delta)) = self.swipe.as_mut() {
+                    *delta += event.delta();
+                    // Swipes no binding is waiting for belong to the clients
+                    let (fingers, delta) = (*fingers, *delta);
+                    if swipe_direction(delta).is_none()
+                        || self.swipe_action(fingers, delta).is_some()
+                    {
+                        return;
+                    }
+                    if let Some(swipe) = self.swipe.take() {
+                        self.forward_swipe(swipe);
+                    }
+                    return;
+                }
                 let pointer = self.pointer.clone();
                 pointer.gesture_swipe_update(
                     self,
@@ -397,6 +566,17 @@ impl State {
                 );
             }
             InputEvent::GestureSwipeEnd { event } => {
+                if let Some(swipe) = self.swipe.take() {
+                    let (fingers, _, delta) = swipe;
+                    match self.swipe_action(fingers, delta) {
+                        Some(action) if !event.cancelled() => {
+                            action.execute(self);
+                            return;
+                        }
+                        Some(_) => return,
+                        None => self.forward_swipe(swipe),
+                    }
+                }
                 let serial = SERIAL_COUNTER.next_serial();
                 let pointer = self.pointer.clone();
                 pointer.gesture_swipe_end(
@@ -474,18 +654,141 @@ impl State {
             _ => {}
         }
     }
+    /// Accumulates scroll in v120 units and switches one workspace per wheel notch.
+    fn scroll_workspaces(&mut self, delta: f64) {
+        self.scroll_accumulator += delta;
+        let steps = (self.scroll_accumulator / 120.0).trunc();
+        if steps == 0.0 {
+            return;
+        }
+        self.scroll_accumulator -= steps * 120.0;
+
+        let count = self.workspaces.workspaces.len() as i64;
+        let target = (self.workspaces.active_workspace as i64 + steps as i64).clamp(0, count - 1);
+        if target as usize != self.workspaces.active_workspace {
+            Action::Workspace {
+                index: target as usize + 1,
+            }
+            .execute(self);
+        }
+    }
+    /// Shortcut inhibitor of the surface with keyboard focus.
+    fn focused_inhibitor(&self) -> Option<KeyboardShortcutsInhibitor> {
+        let surface = self.seat.get_keyboard()?.current_focus()?;
+        self.seat.keyboard_shortcuts_inhibitor_for_surface(&surface)
+    }
+
+    /// Hands the input back to the compositor: ends pointer grabs, lifts the
+    /// shortcut inhibitor of the focused surface, closes overlays and leaves
+    /// fullscreen.
+    fn escape_fullscreen(&mut self, serial: Serial, time: u32) {
+        if let Some(pointer) = self.seat.get_pointer() {
+            if pointer.is_grabbed() {
+                pointer.unset_grab(self, serial, time);
+            }
+        }
+        if let Some(inhibitor) = self.focused_inhibitor() {
+            inhibitor.inactivate();
+        }
+        self.overlay = None;
+        let fullscreen = is_fullscreen(self.workspaces.get_current().space.elements()).cloned();
+        if let Some(window) = fullscreen {
+            self.unfullscreen(&window);
+        }
+        self.set_keyboard_focus_auto();
+    }
+
+    /// Action bound to `button` together with the currently held modifiers.
+    fn button_bind(&self, button: u32) -> Option<Action> {
+        let modifiers = self.seat.get_keyboard()?.modifier_state();
+        self.config.keymaps.iter().find_map(|(bind, action)| {
+            let (config_modifiers, code) = parse_buttonbind(bind)?;
+            (code == button && modifiers_match(&modifiers, &config_modifiers))
+                .then(|| action.clone())
+        })
+    }
+
+    /// Runs `action` again while its bind is held, until the next key event.
+    /// It repeats like keys do for clients.
+    fn start_key_repeat(&mut self, action: Action) {
+        let keyboard = &self.config.keyboard;
+        if keyboard.repeat_rate <= 0 {
+            return;
+        }
+        let delay = Duration::from_millis(keyboard.repeat_delay.max(0) as u64);
+        let interval = Duration::from_secs(1) / keyboard.repeat_rate as u32;
+        let timer = Timer::from_duration(delay);
+        match self.loop_handle.insert_source(timer, move |_, _, state| {
+            action.execute(state);
+            TimeoutAction::ToDuration(interval)
+        }) {
+            Ok(token) => self.key_repeat = Some(token),
+            Err(err) => tracing::warn!("Failed to start key repeat: {}", err),
+        }
+    }
+
+    fn stop_key_repeat(&mut self) {
+        if let Some(token) = self.key_repeat.take() {
+            self.loop_handle.remove(token);
+        }
+    }
+
+    /// Action bound to a swipe with `fingers` that travelled `delta`.
+    fn swipe_action(&self, fingers: u32, delta: Point<f64, Logical>) -> Option<Action> {
+        let direction = swipe_direction(delta)?;
+        self.config
+            .gestures
+            .iter()
+            .find(|gesture| gesture.fingers == fingers && gesture.direction == direction)
+            .map(|gesture| gesture.action.clone())
+    }
+
+    /// Hands a held back swipe to the clients, as it went so far.
+    fn forward_swipe(&mut self, (fingers, time, delta): (u32, u32, Point<f64, Logical>)) {
+        let serial = SERIAL_COUNTER.next_serial();
+        let pointer = self.pointer.clone();
+        pointer.gesture_swipe_begin(
+            self,
+            &GestureSwipeBeginEvent {
+                serial,
+                time,
+                fingers,
+            },
+        );
+        if delta != Point::default() {
+            pointer.gesture_swipe_update(self, &GestureSwipeUpdateEvent { time, delta });
+        }
+    }
+
+    /// Keeps the pointer inside the union of all outputs. A position outside of
+    /// every output is snapped to the closest point of the nearest one, so the
+    /// pointer slides along edges instead of crossing into empty space.
     fn clamp_coords(&self, pos: Point<f64, Logical>) -> Point<f64, Logical> {
         let ws = self.workspaces.get_current();
-        let (pos_x, pos_y) = pos.into();
-        let (max_x, max_y) = ws
+        let geometries: Vec<_> = ws
             .space
-            .output_geometry(ws.space.outputs().next().unwrap())
-            .unwrap()
-            .size
-            .into();
-        let clamped_x = pos_x.max(0.0).min(max_x as f64);
-        let clamped_y = pos_y.max(0.0).min(max_y as f64);
-        (clamped_x, clamped_y).into()
+            .outputs()
+            .filter_map(|output| ws.space.output_geometry(output))
+            .collect();
+        if geometries.iter().any(|geo| geo.to_f64().contains(pos)) {
+            return pos;
+        }
+
+        geometries
+            .iter()
+            .map(|geo| {
+                let min_x = geo.loc.x as f64;
+                let min_y = geo.loc.y as f64;
+                let max_x = (geo.loc.x + geo.size.w - 1).max(geo.loc.x) as f64;
+                let max_y = (geo.loc.y + geo.size.h - 1).max(geo.loc.y) as f64;
+                Point::<f64, Logical>::from((pos.x.clamp(min_x, max_x), pos.y.clamp(min_y, max_y)))
+            })
+            .min_by(|a, b| {
+                let da = (a.x - pos.x).powi(2) + (a.y - pos.y).powi(2);
+                let db = (b.x - pos.x).powi(2) + (b.y - pos.y).powi(2);
+                da.total_cmp(&db)
+            })
+            .unwrap_or(pos)
     }
     fn touch_location_transformed<B: InputBackend, E: AbsolutePositionEvent<B>>(
         &self,
@@ -509,3 +812,96 @@ impl State {
         )
     }
 }
+
+impl State {
+    /// Applies the `keyboard` settings to the live keyboard: repeat info and layouts.
+    pub fn apply_keyboard_config(&mut self) {
+        let Some(keyboard) = self.seat.get_keyboard() else {
+            return;
+        };
+        let config = &self.config.keyboard;
+        keyboard.change_repeat_info(config.repeat_rate, config.repeat_delay);
+        let layout = config.layouts.join(",");
+        let xkb_config = XkbConfig {
+            layout: &layout,
+            ..Default::default()
+        };
+        if let Err(err) = keyboard.set_xkb_config(self, xkb_config) {
+            tracing::warn!("Failed to apply keyboard layouts {}: {:?}", layout, err);
+        }
+    }
+
+    /// Index and name of the active keyboard layout.
+    pub fn keyboard_layout(&mut self) -> Option<(usize, String)> {
+        let keyboard = self.seat.get_keyboard()?;
+        Some(keyboard.with_xkb_state(self, |context| {
+            let xkb = context.xkb().lock().unwrap();
+            let layout = xkb.active_layout();
+            (layout.0 as usize, xkb.layout_name(layout).to_string())
+        }))
+    }
+
+    /// Applies the `input_devices` settings matching `device`. The `touchpad` and
+    /// `pointer` entries apply to every device of that kind, an entry named after
+    /// the device overrides them.
+    fn configure_input_device(&self, device: &mut input::Device) {
+        let is_touchpad = device.config_tap_finger_count() > 0;
+        let is_pointer = device.has_capability(DeviceCapability::Pointer.into());
+        let name = device.name().to_string();
+
+        let configs = [
+            ("pointer", is_pointer && !is_touchpad),
+            ("touchpad", is_touchpad),
+            (name.as_str(), true),
+        ];
+        for (key, applies) in configs {
+            let Some(config) = self.config.input_devices.get(key).filter(|_| applies) else {
+                continue;
+            };
+            if let Some(natural_scroll) = config.natural_scroll {
+                device
+                    .config_scroll_set_natural_scroll_enabled(natural_scroll)
+                    .ok();
+            }
+            if let Some(speed) = config.accel_speed {
+                device.config_accel_set_speed(speed.clamp(-1.0, 1.0)).ok();
+            }
+            if let Some(profile) = config.accel_profile {
+                let profile = match profile {
+                    AccelProfile::Flat => input::AccelProfile::Flat,
+                    AccelProfile::Adaptive => input::AccelProfile::Adaptive,
+                };
+                device.config_accel_set_profile(profile).ok();
+            }
+            if let Some(tap) = config.tap {
+                device.config_tap_set_enabled(tap).ok();
+            }
+            if let Some(left_handed) = config.left_handed {
+                device.config_left_handed_set(left_handed).ok();
+            }
+        }
+    }
+}
+
+/// Direction of a swipe that travelled `delta`, None until it went far enough.
+fn swipe_direction(delta: Point<f64, Logical>) -> Option<Direction> {
+    if delta.x.abs() >= delta.y.abs() {
+        if delta.x.abs() < SWIPE_THRESHOLD {
+            return None;
+        }
+        Some(if delta.x < 0.0 {
+            Direction::Left
+        } else {
+            Direction::Right
+        })
+    } else {
+        if delta.y.abs() < SWIPE_THRESHOLD {
+            return None;
+        }
+        Some(if delta.y < 0.0 {
+            Direction::Top
+        } else {
+            Direction::Down
+        })
+    }
+}
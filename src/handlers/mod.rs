@@ -4,28 +4,43 @@ mod xdg;
 mod xwayland;
 
 use std::{
+    cell::RefCell,
+    io::Write,
     os::fd::OwnedFd,
     sync::{Arc, Mutex},
 };
 
-use crate::state::{ClientState, State};
+use crate::{
+    state::{ClientState, State},
+    utils::{
+        autohide::usable_zone,
+        grab::Drag,
+        selection::SelectionData,
+        workspaces::{is_pending_center, place_on_center, WindowMode},
+    },
+};
+#[cfg(feature = "xwayland")]
+use smithay::xwayland::XWaylandClientData;
 use smithay::{
     backend::{input::TabletToolDescriptor, renderer::utils::on_commit_buffer_handler},
-    delegate_compositor, delegate_data_device, delegate_fractional_scale,
-    delegate_input_method_manager, delegate_keyboard_shortcuts_inhibit, delegate_layer_shell,
-    delegate_output, delegate_pointer_gestures, delegate_presentation, delegate_primary_selection,
-    delegate_seat, delegate_security_context, delegate_shm, delegate_single_pixel_buffer,
-    delegate_tablet_manager, delegate_viewporter,
+    delegate_compositor, delegate_content_type, delegate_cursor_shape, delegate_data_device,
+    delegate_fractional_scale, delegate_input_method_manager, delegate_keyboard_shortcuts_inhibit,
+    delegate_layer_shell, delegate_output, delegate_pointer_gestures, delegate_presentation,
+    delegate_primary_selection, delegate_seat, delegate_security_context, delegate_shm,
+    delegate_single_pixel_buffer, delegate_tablet_manager, delegate_viewporter,
     desktop::{
         layer_map_for_output, utils::surface_primary_scanout_output, LayerSurface, PopupKind,
         PopupManager, Space, Window, WindowSurfaceType,
     },
-    input::{pointer::CursorImageStatus, Seat, SeatHandler, SeatState},
+    input::{
+        pointer::{CursorImageStatus, CursorImageSurfaceData},
+        Seat, SeatHandler, SeatState,
+    },
     output::Output,
     reexports::{
         calloop::Interest,
         wayland_server::{
-            protocol::{wl_buffer, wl_surface::WlSurface},
+            protocol::{wl_buffer, wl_data_source::WlDataSource, wl_surface::WlSurface},
             Client, Resource,
         },
     },
@@ -33,9 +48,9 @@ use smithay::{
     wayland::{
         buffer::BufferHandler,
         compositor::{
-            add_blocker, add_pre_commit_hook, get_parent, is_sync_subsurface, with_states,
-            BufferAssignment, CompositorClientState, CompositorHandler, CompositorState,
-            SurfaceAttributes,
+            add_blocker, add_pre_commit_hook, get_parent, is_sync_subsurface, send_surface_state,
+            with_states, with_surface_tree_downward, BufferAssignment, CompositorClientState,
+            CompositorHandler, CompositorState, SurfaceAttributes, TraversalAction,
         },
         dmabuf::get_dmabuf,
         drm_syncobj::DrmSyncobjCachedState,
@@ -58,7 +73,7 @@ use smithay::{
             primary_selection::{
                 set_primary_focus, PrimarySelectionHandler, PrimarySelectionState,
             },
-            SelectionHandler,
+            SelectionHandler, SelectionSource, SelectionTarget,
         },
         shell::{
             wlr_layer::{LayerSurface as WlrLayerSurface, LayerSurfaceData, WlrLayerShellHandler},
@@ -68,11 +83,6 @@ use smithay::{
         tablet_manager::TabletSeatHandler,
     },
 };
-#[cfg(feature = "xwayland")]
-use smithay::{
-    wayland::selection::{SelectionSource, SelectionTarget},
-    xwayland::XWaylandClientData,
-};
 
 delegate_compositor!(State);
 delegate_shm!(State);
@@ -86,6 +96,25 @@ impl BufferHandler for State {
     fn buffer_destroyed(&mut self, _buffer: &wl_buffer::WlBuffer) {}
 }
 
+/// Tells the client the scale of the output it is about to be shown on, so its
+/// first buffer is already rendered at the right scale.
+fn send_preferred_scale(surface: &WlSurface, output: &Output) {
+    let scale = output.current_scale();
+    let transform = output.current_transform();
+    with_surface_tree_downward(
+        surface,
+        (),
+        |_, _, _| TraversalAction::DoChildren(()),
+        |surface, states, _| {
+            send_surface_state(surface, states, scale.integer_scale(), transform);
+            with_fractional_scale(states, |fractional_scale| {
+                fractional_scale.set_preferred_scale(scale.fractional_scale());
+            });
+        },
+        |_, _, _| true,
+    );
+}
+
 pub fn handle_commit(space: &Space<Window>, surface: &WlSurface, popup_manager: &PopupManager) {
     // Handle toplevel commits.
 
@@ -107,6 +136,14 @@ pub fn handle_commit(space: &Space<Window>, surface: &WlSurface, popup_manager:
             });
 
             if !initial_configure_sent {
+                let output = space
+                    .outputs_for_element(&window)
+                    .first()
+                    .cloned()
+                    .or_else(|| space.outputs().next().cloned());
+                if let Some(output) = output {
+                    send_preferred_scale(surface, &output);
+                }
                 toplevel.send_configure();
             }
         }
@@ -133,6 +170,7 @@ pub fn handle_commit(space: &Space<Window>, surface: &WlSurface, popup_manager:
         map.arrange();
         // send the initial configure if relevant
         if !initial_configure_sent {
+            send_preferred_scale(surface, output);
             let layer = map
                 .layer_for_surface(surface, WindowSurfaceType::TOPLEVEL)
                 .unwrap();
@@ -159,34 +197,57 @@ pub fn handle_commit(space: &Space<Window>, surface: &WlSurface, popup_manager:
 }
 
 impl SelectionHandler for State {
-    type SelectionUserData = ();
+    type SelectionUserData = SelectionData;
 
-    #[cfg(feature = "xwayland")]
     fn new_selection(
         &mut self,
         ty: SelectionTarget,
         source: Option<SelectionSource>,
         _seat: Seat<Self>,
     ) {
+        let mime_types = source.map(|source| source.mime_types());
+
+        #[cfg(feature = "xwayland")]
         if let Some(xwm) = self.xwm.as_mut() {
-            if let Err(err) = xwm.new_selection(ty, source.map(|source| source.mime_types())) {
+            if let Err(err) = xwm.new_selection(ty, mime_types.clone()) {
                 tracing::warn!(?err, ?ty, "Failed to set Xwayland selection");
             }
         }
+
+        // Primary selections outlive their client, like X11 users expect
+        if ty == SelectionTarget::Primary {
+            match mime_types {
+                Some(mime_types) => self.cache_primary_selection(mime_types),
+                None => self.restore_primary_selection(),
+            }
+        }
     }
 
-    #[cfg(feature = "xwayland")]
     fn send_selection(
         &mut self,
         ty: SelectionTarget,
         mime_type: String,
         fd: OwnedFd,
         _seat: Seat<Self>,
-        _user_data: &(),
+        user_data: &SelectionData,
     ) {
-        if let Some(xwm) = self.xwm.as_mut() {
-            if let Err(err) = xwm.send_selection(ty, mime_type, fd, self.loop_handle.clone()) {
-                tracing::warn!(?err, "Failed to send primary (X11 -> Wayland)");
+        match user_data {
+            SelectionData::Cached(data) => {
+                if let Err(err) = std::fs::File::from(fd).write_all(data) {
+                    tracing::warn!("Failed to send cached selection: {}", err);
+                }
+            }
+            SelectionData::Xwayland => {
+                #[cfg(feature = "xwayland")]
+                if let Some(xwm) = self.xwm.as_mut() {
+                    if let Err(err) =
+                        xwm.send_selection(ty, mime_type, fd, self.loop_handle.clone())
+                    {
+                        tracing::warn!(?err, "Failed to send primary (X11 -> Wayland)");
+                    }
+                }
+                #[cfg(not(feature = "xwayland"))]
+                let _ = (ty, mime_type, fd);
             }
         }
     }
@@ -198,7 +259,26 @@ impl DataDeviceHandler for State {
     }
 }
 
-impl ClientDndGrabHandler for State {}
+impl ClientDndGrabHandler for State {
+    fn started(
+        &mut self,
+        _source: Option<WlDataSource>,
+        icon: Option<WlSurface>,
+        _seat: Seat<Self>,
+    ) {
+        self.drag = Some(Drag {
+            origin: self.pointer_location,
+            icon,
+            icon_offset: (0, 0).into(),
+            shown: false,
+        });
+    }
+
+    // Also called when the drag is cancelled, with `validated` unset
+    fn dropped(&mut self, _target: Option<WlSurface>, _validated: bool, _seat: Seat<Self>) {
+        self.drag = None;
+    }
+}
 impl ServerDndGrabHandler for State {}
 
 impl CompositorHandler for State {
@@ -273,17 +353,78 @@ impl CompositorHandler for State {
 
     fn commit(&mut self, surface: &WlSurface) {
         on_commit_buffer_handler::<Self>(surface);
-        self.backend_data.early_import(surface);
+        let copies = self.backend_data.early_import(surface);
+        self.render_stats.gpu_copies(copies);
 
+        // Cursor surfaces move their hotspot against the buffer offset
+        if matches!(&self.cursor_status, CursorImageStatus::Surface(cursor) if cursor == surface) {
+            with_states(surface, |states| {
+                let offset = states
+                    .cached_state
+                    .get::<SurfaceAttributes>()
+                    .current()
+                    .buffer_delta
+                    .take();
+                if let (Some(offset), Some(data)) =
+                    (offset, states.data_map.get::<CursorImageSurfaceData>())
+                {
+                    data.lock().unwrap().hotspot -= offset;
+                }
+            });
+        }
+
+        // The drag icon follows the pointer, its buffer offsets move it from there
+        if let Some(drag) = self
+            .drag
+            .as_mut()
+            .filter(|drag| drag.icon.as_ref() == Some(surface))
+        {
+            let offset = with_states(surface, |states| {
+                states
+                    .cached_state
+                    .get::<SurfaceAttributes>()
+                    .current()
+                    .buffer_delta
+                    .take()
+            });
+            if let Some(offset) = offset {
+                drag.icon_offset += offset;
+            }
+        }
+
+        // Clients set the app id and title before their first commit
+        let mut new_window = None;
         if !is_sync_subsurface(surface) {
             let mut root = surface.clone();
             while let Some(parent) = get_parent(&root) {
                 root = parent;
             }
-            if let Some(window) = self.window_for_surface(surface) {
+            // Subsurface commits can change the window bbox and geometry too
+            if let Some(window) = self.window_for_surface(&root) {
                 window.on_commit();
 
+                // Floating windows placed before they had a size
+                let floating = window
+                    .user_data()
+                    .get::<RefCell<WindowMode>>()
+                    .is_some_and(|d| *d.borrow() == WindowMode::Floating);
+                if floating && is_pending_center(&window) && !window.geometry().size.is_empty() {
+                    place_on_center(
+                        &mut self.workspaces.get_current_mut().space,
+                        &window,
+                        self.config.border.gap_outer + self.config.border.thickness,
+                        &self.config.floating,
+                        self.config.titlebars,
+                    );
+                }
+
                 if &root == surface {
+                    if window
+                        .toplevel()
+                        .is_some_and(|toplevel| !toplevel.is_initial_configure_sent())
+                    {
+                        new_window = Some(window.clone());
+                    }
                     let buffer_offset = with_states(surface, |states| {
                         states
                             .cached_state
@@ -299,15 +440,57 @@ impl CompositorHandler for State {
                         ws.space
                             .map_element(window.clone(), current_loc + buffer_offset, false);
                     }
+                    self.unmap_toplevel(&window);
+                }
+            } else if let Some(window) = self
+                .workspaces
+                .workspaces
+                .iter()
+                .flat_map(|ws| ws.space.elements())
+                .find(|window| window.wl_surface().as_deref() == Some(&root))
+                .cloned()
+            {
+                // Windows on hidden workspaces, e.g. sent there by a rule
+                window.on_commit();
+                if &root == surface {
+                    self.unmap_toplevel(&window);
                 }
+            } else if &root == surface {
+                self.remap_toplevel(surface);
             }
         };
         self.popup_manager.commit(surface);
+
+        // Exclusive zones are logical sizes, the layer map turns them into the
+        // usable area using the output's fractional scale when it arranges.
+        // Tiles have to follow whenever that area changes.
+        let layer_output = self
+            .workspaces
+            .get_current()
+            .space
+            .outputs()
+            .find(|o| {
+                layer_map_for_output(o)
+                    .layer_for_surface(surface, WindowSurfaceType::TOPLEVEL)
+                    .is_some()
+            })
+            .cloned();
+        let zone = layer_output
+            .as_ref()
+            .map(|o| usable_zone(&layer_map_for_output(o)));
         handle_commit(
             &self.workspaces.get_current().space,
             surface,
             &self.popup_manager,
         );
+        if let Some(window) = new_window {
+            self.apply_window_rules(&window);
+        }
+        if let Some(output) = layer_output {
+            if Some(usable_zone(&layer_map_for_output(&output))) != zone {
+                self.refresh_layout();
+            }
+        }
     }
 }
 
@@ -328,8 +511,11 @@ impl SeatHandler for State {
     fn led_state_changed(
         &mut self,
         _seat: &Seat<Self>,
-        _led_state: smithay::input::keyboard::LedState,
+        led_state: smithay::input::keyboard::LedState,
     ) {
+        for mut keyboard in self.keyboards.iter().cloned() {
+            keyboard.led_update(led_state.into());
+        }
     }
 
     fn focus_changed(&mut self, seat: &Seat<Self>, focused: Option<&WlSurface>) {
@@ -337,6 +523,8 @@ impl SeatHandler for State {
         let focus = focused
             .and_then(WaylandFocus::wl_surface)
             .and_then(|s| dh.get_client(s.id()).ok());
+        // A None focus only takes the offers away from the previous client, the
+        // selections themselves stay (see SelectionHandler::new_selection)
         set_data_device_focus(dh, seat, focus.clone());
         set_primary_focus(dh, seat, focus);
         //let ws = self.workspaces.get_current();
@@ -356,11 +544,8 @@ impl SeatHandler for State {
         //}
     }
 
-    fn cursor_image(
-        &mut self,
-        _seat: &Seat<Self>,
-        _image: smithay::input::pointer::CursorImageStatus,
-    ) {
+    fn cursor_image(&mut self, _seat: &Seat<Self>, image: CursorImageStatus) {
+        self.cursor_status = image;
     }
 }
 
@@ -377,9 +562,10 @@ impl WlrLayerShellHandler for State {
             .as_ref()
             .and_then(Output::from_resource)
             .unwrap_or_else(|| ws.space.outputs().next().unwrap().clone());
+        let layer = LayerSurface::new(surface, namespace);
+        self.mark_autohide(&layer);
         let mut map = layer_map_for_output(&output);
-        map.map_layer(&LayerSurface::new(surface, namespace))
-            .unwrap();
+        map.map_layer(&layer).unwrap();
     }
     fn shell_state(&mut self) -> &mut smithay::wayland::shell::wlr_layer::WlrLayerShellState {
         &mut self.layer_shell_state
@@ -396,6 +582,8 @@ impl WlrLayerShellHandler for State {
         }) {
             map.unmap_layer(&layer);
         }
+        // Give the panel's exclusive zone back to the tiles
+        self.refresh_layout();
     }
     fn new_popup(
         &mut self,
@@ -534,8 +722,12 @@ impl TabletSeatHandler for State {
 }
 delegate_tablet_manager!(State);
 
+delegate_cursor_shape!(State);
+
 delegate_pointer_gestures!(State);
 
+delegate_content_type!(State);
+
 delegate_presentation!(State);
 
 impl SecurityContextHandler for State {
@@ -1,6 +1,7 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 
 use smithay::{
+    backend::renderer::utils::with_renderer_surface_state,
     delegate_data_control, delegate_xdg_activation, delegate_xdg_decoration, delegate_xdg_shell,
     desktop::{
         find_popup_root_surface, get_popup_toplevel_coords, layer_map_for_output,
@@ -18,11 +19,12 @@ use smithay::{
     },
     utils::{Rectangle, Serial},
     wayland::{
+        compositor::with_states,
         seat::WaylandFocus,
         selection::wlr_data_control::{DataControlHandler, DataControlState},
         shell::xdg::{
-            decoration::XdgDecorationHandler, Configure, PopupSurface, PositionerState,
-            ToplevelSurface, XdgShellHandler, XdgShellState,
+            decoration::XdgDecorationHandler, Configure, PingError, PopupSurface, PositionerState,
+            ShellClient, ToplevelSurface, XdgShellHandler, XdgShellState, XdgToplevelSurfaceData,
         },
         xdg_activation::{
             XdgActivationHandler, XdgActivationState, XdgActivationToken, XdgActivationTokenData,
@@ -34,9 +36,11 @@ use smithay::{
 use crate::{
     state::State,
     utils::{
+        config::UnmapBehavior,
         grab::{MovePointerGrab, ResizePointerGrub},
-        workspaces::WindowMode,
+        workspaces::{set_unresponsive, UnmappedWindow, WindowMode},
     },
+    SERIAL_COUNTER,
 };
 
 impl XdgShellHandler for State {
@@ -76,6 +80,35 @@ impl XdgShellHandler for State {
         surface.send_configure();
     }
 
+    fn maximize_request(&mut self, surface: ToplevelSurface) {
+        let maximized = surface
+            .current_state()
+            .states
+            .contains(xdg_toplevel::State::Maximized);
+        let handled = match self.window_for_surface(surface.wl_surface()) {
+            Some(window) if !maximized => self.toggle_maximize(&window),
+            _ => false,
+        };
+        // A configure is due even when the request is ignored
+        if !handled {
+            surface.send_configure();
+        }
+    }
+
+    fn unmaximize_request(&mut self, surface: ToplevelSurface) {
+        let maximized = surface
+            .current_state()
+            .states
+            .contains(xdg_toplevel::State::Maximized);
+        let handled = match self.window_for_surface(surface.wl_surface()) {
+            Some(window) if maximized => self.toggle_maximize(&window),
+            _ => false,
+        };
+        if !handled {
+            surface.send_configure();
+        }
+    }
+
     fn unfullscreen_request(&mut self, surface: ToplevelSurface) {
         let ws = &mut self.workspaces.get_current_mut();
         let window = ws
@@ -124,12 +157,18 @@ impl XdgShellHandler for State {
 
     fn new_toplevel(&mut self, surface: ToplevelSurface) {
         let window = Window::new_wayland_window(surface);
-        self.set_keyboard_focus(window.wl_surface().map(|s| s.as_ref().clone()));
+        let output = self.pointer_output();
         self.workspaces.get_current_mut().insert_window(
             window.clone(),
-            self.config.border.gap + self.config.border.thickness,
+            self.config.border.gap_outer + self.config.border.thickness,
+            self.config.new_window_position,
+            &self.config.floating,
+            self.config.titlebars,
+            output.as_ref(),
         );
+        self.swallow(&window);
         self.refresh_layout();
+        self.focus_new_window(&window, self.workspaces.active_ws());
     }
 
     fn new_popup(&mut self, surface: PopupSurface, positioner: PositionerState) {
@@ -143,22 +182,34 @@ impl XdgShellHandler for State {
     }
 
     fn toplevel_destroyed(&mut self, surface: ToplevelSurface) {
-        let window = self
+        // Unmapped windows are already out of the layout
+        if let Some(index) = self
+            .unmapped_windows
+            .iter()
+            .position(|unmapped| unmapped.window.toplevel() == Some(&surface))
+        {
+            let unmapped = self.unmapped_windows.remove(index);
+            self.unswallow(&unmapped.window);
+            return;
+        }
+        let Some(window) = self
             .workspaces
-            .get_current()
-            .space
-            .elements()
+            .workspaces
+            .iter()
+            .flat_map(|ws| ws.space.elements())
             .find(|w| {
                 w.toplevel()
                     .map(|toplevel| toplevel == &surface)
                     .unwrap_or(false)
             })
-            .unwrap()
-            .clone();
-        let ws = self.workspaces.get_current_mut();
-        ws.remove_window(&window);
-        ws.set_active_window(None);
-        self.refresh_layout();
+            .cloned()
+        else {
+            return;
+        };
+        if let Some((workspace, _)) = self.workspaces.take_window(&window) {
+            self.refresh_layout_for(workspace);
+        }
+        self.unswallow(&window);
     }
 
     fn grab(&mut self, surface: PopupSurface, seat: wl_seat::WlSeat, serial: Serial) {
@@ -248,11 +299,7 @@ impl XdgShellHandler for State {
         let start_data = pointer.grab_start_data().unwrap();
         let start_loc = ws.space.element_location(&window).unwrap();
 
-        let grab = MovePointerGrab {
-            start_data,
-            window,
-            start_loc,
-        };
+        let grab = MovePointerGrab::new(start_data, window, start_loc);
         pointer.set_grab(self, grab, serial, Focus::Clear);
     }
 
@@ -305,6 +352,144 @@ impl XdgShellHandler for State {
         tracing::info!("ack_configure request");
         if let Configure::Toplevel(configure) = configure {}
     }
+
+    fn client_pong(&mut self, client: ShellClient) {
+        self.mark_client_windows(&client, false);
+    }
+}
+
+impl State {
+    /// Pings every client owning a window. A client that still has a ping
+    /// pending from the previous round is considered unresponsive.
+    pub fn ping_clients(&mut self) {
+        let mut clients: Vec<ShellClient> = Vec::new();
+        for ws in &self.workspaces.workspaces {
+            for window in ws.space.elements() {
+                if let Some(toplevel) = window.toplevel() {
+                    let client = toplevel.client();
+                    if !clients.contains(&client) {
+                        clients.push(client);
+                    }
+                }
+            }
+        }
+
+        for client in clients {
+            match client.send_ping(SERIAL_COUNTER.next_serial()) {
+                Ok(()) => {}
+                Err(PingError::PingAlreadyPending(_)) => self.mark_client_windows(&client, true),
+                Err(PingError::DeadSurface) => {}
+            }
+        }
+    }
+
+    fn mark_client_windows(&mut self, client: &ShellClient, unresponsive: bool) {
+        for ws in &self.workspaces.workspaces {
+            for window in ws.space.elements() {
+                if window
+                    .toplevel()
+                    .map(|toplevel| &toplevel.client() == client)
+                    .unwrap_or(false)
+                {
+                    set_unresponsive(window, unresponsive);
+                }
+            }
+        }
+    }
+}
+
+/// Set on toplevels while they have a buffer attached, to tell an unmap apart
+/// from the commits before the first buffer.
+struct WasMapped(Cell<bool>);
+
+impl State {
+    /// Takes `window` out of the layout if this commit unmapped it.
+    pub fn unmap_toplevel(&mut self, window: &Window) {
+        if window.toplevel().is_none() {
+            return;
+        }
+        let Some(surface) = window.wl_surface() else {
+            return;
+        };
+        let mapped = with_renderer_surface_state(&surface, |state| state.buffer().is_some())
+            .unwrap_or(false);
+        window
+            .user_data()
+            .insert_if_missing(|| WasMapped(Cell::new(false)));
+        let was_mapped = window
+            .user_data()
+            .get::<WasMapped>()
+            .unwrap()
+            .0
+            .replace(mapped);
+        if mapped || !was_mapped {
+            return;
+        }
+
+        // Hidden workspaces keep committing too, look the window up in all
+        let Some((workspace, loc)) = self.workspaces.take_window(window) else {
+            return;
+        };
+        self.unmapped_windows.push(UnmappedWindow {
+            window: window.clone(),
+            workspace,
+            loc,
+        });
+        self.refresh_layout_for(workspace);
+    }
+
+    /// Puts an unmapped toplevel back once its client commits to it again.
+    pub fn remap_toplevel(&mut self, surface: &WlSurface) {
+        let Some(index) = self
+            .unmapped_windows
+            .iter()
+            .position(|unmapped| unmapped.window.wl_surface().as_deref() == Some(surface))
+        else {
+            return;
+        };
+        let UnmappedWindow {
+            window,
+            workspace,
+            loc,
+        } = self.unmapped_windows.remove(index);
+
+        match self.config.unmapped_windows {
+            UnmapBehavior::Restore => {
+                if let Some(ws) = self.workspaces.workspaces.get_mut(workspace) {
+                    ws.space.map_element(window.clone(), loc, false);
+                }
+                self.refresh_layout_for(workspace);
+            }
+            UnmapBehavior::Reinsert => {
+                let output = self.pointer_output();
+                self.workspaces.get_current_mut().insert_window(
+                    window.clone(),
+                    self.config.border.gap_outer + self.config.border.thickness,
+                    self.config.new_window_position,
+                    &self.config.floating,
+                    self.config.titlebars,
+                    output.as_ref(),
+                );
+            }
+        }
+
+        // The client waits for a fresh initial configure before attaching a buffer
+        if let Some(toplevel) = window.toplevel() {
+            let initial_configure_sent = with_states(surface, |states| {
+                states
+                    .data_map
+                    .get::<XdgToplevelSurfaceData>()
+                    .unwrap()
+                    .lock()
+                    .unwrap()
+                    .initial_configure_sent
+            });
+            if !initial_configure_sent {
+                toplevel.send_configure();
+            }
+        }
+        self.refresh_layout();
+    }
 }
 
 delegate_xdg_shell!(State);
@@ -24,10 +24,12 @@ use smithay::{
         xwayland_keyboard_grab::XWaylandKeyboardGrabHandler,
         xwayland_shell::{XWaylandShellHandler, XWaylandShellState},
     },
-    xwayland::{X11Wm, XWaylandEvent, XwmHandler},
+    xwayland::{xwm::WmWindowType, X11Wm, XWaylandEvent, XwmHandler},
 };
 
-use crate::utils::workspaces::WindowMode;
+use crate::utils::{
+    config::OverrideRedirectFocus, selection::SelectionData, workspaces::WindowMode,
+};
 use crate::FALLBACK_CURSOR_DATA;
 use crate::{
     state::State,
@@ -145,10 +147,16 @@ impl XwmHandler for State {
     ) {
         window.set_mapped(true).unwrap();
         let window = Window::new_x11_window(window);
+        let output = self.pointer_output();
         self.workspaces.get_current_mut().insert_window(
             window.clone(),
-            self.config.border.gap + self.config.border.thickness,
+            self.config.border.gap_outer + self.config.border.thickness,
+            self.config.new_window_position,
+            &self.config.floating,
+            self.config.titlebars,
+            output.as_ref(),
         );
+        self.swallow(&window);
         let bbox = self
             .workspaces
             .get_current_mut()
@@ -159,7 +167,9 @@ impl XwmHandler for State {
             unreachable!()
         };
         xsurface.configure(Some(bbox)).unwrap();
+        let ws_index = self.apply_window_rules(&window);
         self.refresh_layout();
+        self.focus_new_window(&window, ws_index);
         tracing::info!("map_window_xwayland");
     }
 
@@ -169,11 +179,27 @@ impl XwmHandler for State {
         window: smithay::xwayland::X11Surface,
     ) {
         let location = window.geometry().loc;
+        let wants_focus = match self.config.override_redirect_focus {
+            OverrideRedirectFocus::Always => true,
+            OverrideRedirectFocus::Never => false,
+            OverrideRedirectFocus::Auto => !matches!(
+                window.window_type(),
+                Some(
+                    WmWindowType::Tooltip
+                        | WmWindowType::Notification
+                        | WmWindowType::Splash
+                        | WmWindowType::Toolbar
+                )
+            ),
+        };
         let window = Window::new_x11_window(window);
         self.workspaces
             .get_current_mut()
             .space
             .map_element(window.clone(), location, true);
+        if wants_focus {
+            self.set_keyboard_focus(window.wl_surface().map(|s| s.as_ref().clone()));
+        }
     }
     fn unmapped_window(
         &mut self,
@@ -186,12 +212,21 @@ impl XwmHandler for State {
             .elements()
             .find(|e| matches!(e.x11_surface(), Some(w) if w == &window))
             .cloned();
-        if let Some(elem) = maybe {
-            ws.space.unmap_elem(&elem);
+        if let Some(elem) = maybe.as_ref() {
+            ws.space.unmap_elem(elem);
         }
         if !window.is_override_redirect() {
             window.set_mapped(false).unwrap();
-            ws.active_window = None
+            ws.active_window = None;
+            if let Some(elem) = maybe {
+                self.unswallow(&elem);
+            }
+        } else {
+            // Hand focus back if the override-redirect window had it
+            let focused = self.seat.get_keyboard().and_then(|k| k.current_focus());
+            if focused.is_some() && focused == window.wl_surface().map(|s| s.as_ref().clone()) {
+                self.set_keyboard_focus_auto();
+            }
         }
         tracing::info!("unmapped")
     }
@@ -350,11 +385,7 @@ impl XwmHandler for State {
         };
 
         let start_loc = ws.space.element_location(element).unwrap();
-        let grab = MovePointerGrab {
-            start_data,
-            window: element.clone(),
-            start_loc,
-        };
+        let grab = MovePointerGrab::new(start_data, element.clone(), start_loc);
 
         let pointer = self.pointer.clone();
         pointer.set_grab(self, grab, SERIAL_COUNTER.next_serial(), Focus::Clear);
@@ -449,12 +480,18 @@ impl XwmHandler for State {
         tracing::trace!(?selection, ?mime_types, "Got Selection from X11",);
         // TODO check, that focused windows is X11 window before doing this
         match selection {
-            SelectionTarget::Clipboard => {
-                set_data_device_selection(&self.display_handle, &self.seat, mime_types, ())
-            }
-            SelectionTarget::Primary => {
-                set_primary_selection(&self.display_handle, &self.seat, mime_types, ())
-            }
+            SelectionTarget::Clipboard => set_data_device_selection(
+                &self.display_handle,
+                &self.seat,
+                mime_types,
+                SelectionData::Xwayland,
+            ),
+            SelectionTarget::Primary => set_primary_selection(
+                &self.display_handle,
+                &self.seat,
+                mime_types,
+                SelectionData::Xwayland,
+            ),
         }
     }
 
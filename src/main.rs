@@ -1,4 +1,5 @@
 mod handlers;
+mod protocols;
 mod state;
 mod udev;
 mod utils;
@@ -0,0 +1,281 @@
+use smithay::{
+    output::Output,
+    reexports::{
+        wayland_protocols_wlr::foreign_toplevel::v1::server::{
+            zwlr_foreign_toplevel_handle_v1::{self, ZwlrForeignToplevelHandleV1},
+            zwlr_foreign_toplevel_manager_v1::{self, ZwlrForeignToplevelManagerV1},
+        },
+        wayland_server::{
+            Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource,
+        },
+    },
+};
+
+const VERSION: u32 = 3;
+
+/// What taskbars get to know about a toplevel.
+#[derive(Clone, PartialEq, Default)]
+pub struct ToplevelInfo {
+    pub title: String,
+    pub app_id: String,
+    pub activated: bool,
+    pub fullscreen: bool,
+    pub output: Option<Output>,
+}
+
+struct Toplevel {
+    id: u64,
+    info: ToplevelInfo,
+    /// One handle per bound manager.
+    handles: Vec<ZwlrForeignToplevelHandleV1>,
+}
+
+/// The `zwlr_foreign_toplevel_manager_v1` global, used by taskbars to list,
+/// activate and close windows. Toplevels are told apart by an id the
+/// compositor picks.
+pub struct ForeignToplevelManagerState {
+    managers: Vec<ZwlrForeignToplevelManagerV1>,
+    toplevels: Vec<Toplevel>,
+}
+
+pub struct ForeignToplevelGlobalData {
+    filter: Box<dyn Fn(&Client) -> bool + Send + Sync>,
+}
+
+impl ForeignToplevelManagerState {
+    /// Creates the global, visible to the clients `filter` accepts.
+    pub fn new<D, F>(display: &DisplayHandle, filter: F) -> Self
+    where
+        D: GlobalDispatch<ZwlrForeignToplevelManagerV1, ForeignToplevelGlobalData>
+            + Dispatch<ZwlrForeignToplevelManagerV1, ()>
+            + Dispatch<ZwlrForeignToplevelHandleV1, u64>
+            + ForeignToplevelHandler
+            + 'static,
+        F: Fn(&Client) -> bool + Send + Sync + 'static,
+    {
+        display.create_global::<D, ZwlrForeignToplevelManagerV1, _>(
+            VERSION,
+            ForeignToplevelGlobalData {
+                filter: Box::new(filter),
+            },
+        );
+        Self {
+            managers: Vec::new(),
+            toplevels: Vec::new(),
+        }
+    }
+
+    /// Announces toplevel `id`, or sends what changed about it since the last call.
+    pub fn update<D>(&mut self, dh: &DisplayHandle, id: u64, info: ToplevelInfo)
+    where
+        D: Dispatch<ZwlrForeignToplevelHandleV1, u64> + 'static,
+    {
+        match self.toplevels.iter_mut().find(|toplevel| toplevel.id == id) {
+            Some(toplevel) => {
+                if toplevel.info == info {
+                    return;
+                }
+                toplevel.handles.retain(|handle| handle.is_alive());
+                for handle in &toplevel.handles {
+                    send_info(handle, &info, Some(&toplevel.info));
+                }
+                toplevel.info = info;
+            }
+            None => {
+                self.managers.retain(|manager| manager.is_alive());
+                let handles = self
+                    .managers
+                    .iter()
+                    .filter_map(|manager| announce::<D>(dh, manager, id, &info))
+                    .collect();
+                self.toplevels.push(Toplevel { id, info, handles });
+            }
+        }
+    }
+
+    /// Closes the handles of the toplevels `keep` rejects.
+    pub fn retain(&mut self, mut keep: impl FnMut(u64) -> bool) {
+        self.toplevels.retain(|toplevel| {
+            if keep(toplevel.id) {
+                return true;
+            }
+            for handle in &toplevel.handles {
+                handle.closed();
+            }
+            false
+        });
+    }
+}
+
+pub trait ForeignToplevelHandler {
+    fn foreign_toplevel_manager_state(&mut self) -> &mut ForeignToplevelManagerState;
+    /// A taskbar asked to focus toplevel `id`.
+    fn activate(&mut self, id: u64);
+    /// A taskbar asked to close toplevel `id`.
+    fn close(&mut self, id: u64);
+}
+
+/// Creates the handle of toplevel `id` for the client of `manager` and sends it over.
+fn announce<D>(
+    dh: &DisplayHandle,
+    manager: &ZwlrForeignToplevelManagerV1,
+    id: u64,
+    info: &ToplevelInfo,
+) -> Option<ZwlrForeignToplevelHandleV1>
+where
+    D: Dispatch<ZwlrForeignToplevelHandleV1, u64> + 'static,
+{
+    let client = manager.client()?;
+    let handle = client
+        .create_resource::<ZwlrForeignToplevelHandleV1, _, D>(dh, manager.version(), id)
+        .ok()?;
+    manager.toplevel(&handle);
+    send_info(&handle, info, None);
+    Some(handle)
+}
+
+/// Sends the parts of `info` that differ from `old`, everything without it.
+fn send_info(
+    handle: &ZwlrForeignToplevelHandleV1,
+    info: &ToplevelInfo,
+    old: Option<&ToplevelInfo>,
+) {
+    if old.is_none_or(|old| old.title != info.title) {
+        handle.title(info.title.clone());
+    }
+    if old.is_none_or(|old| old.app_id != info.app_id) {
+        handle.app_id(info.app_id.clone());
+    }
+    let old_output = old.and_then(|old| old.output.as_ref());
+    if old_output != info.output.as_ref() {
+        if let Some(client) = handle.client() {
+            if let Some(output) = old_output {
+                for wl_output in output.client_outputs(&client) {
+                    handle.output_leave(&wl_output);
+                }
+            }
+            if let Some(output) = &info.output {
+                for wl_output in output.client_outputs(&client) {
+                    handle.output_enter(&wl_output);
+                }
+            }
+        }
+    }
+    if old.is_none_or(|old| old.activated != info.activated || old.fullscreen != info.fullscreen) {
+        let mut states = Vec::new();
+        if info.activated {
+            states.push(zwlr_foreign_toplevel_handle_v1::State::Activated);
+        }
+        if info.fullscreen {
+            states.push(zwlr_foreign_toplevel_handle_v1::State::Fullscreen);
+        }
+        handle.state(
+            states
+                .into_iter()
+                .flat_map(|state| (state as u32).to_ne_bytes())
+                .collect(),
+        );
+    }
+    handle.done();
+}
+
+impl<D> GlobalDispatch<ZwlrForeignToplevelManagerV1, ForeignToplevelGlobalData, D>
+    for ForeignToplevelManagerState
+where
+    D: GlobalDispatch<ZwlrForeignToplevelManagerV1, ForeignToplevelGlobalData>
+        + Dispatch<ZwlrForeignToplevelManagerV1, ()>
+        + Dispatch<ZwlrForeignToplevelHandleV1, u64>
+        + ForeignToplevelHandler
+        + 'static,
+{
+    fn bind(
+        state: &mut D,
+        dh: &DisplayHandle,
+        _client: &Client,
+        resource: New<ZwlrForeignToplevelManagerV1>,
+        _global_data: &ForeignToplevelGlobalData,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        let manager = data_init.init(resource, ());
+        let toplevels = state.foreign_toplevel_manager_state();
+        for toplevel in &mut toplevels.toplevels {
+            if let Some(handle) = announce::<D>(dh, &manager, toplevel.id, &toplevel.info) {
+                toplevel.handles.push(handle);
+            }
+        }
+        toplevels.managers.push(manager);
+    }
+
+    fn can_view(client: Client, global_data: &ForeignToplevelGlobalData) -> bool {
+        (global_data.filter)(&client)
+    }
+}
+
+impl<D> Dispatch<ZwlrForeignToplevelManagerV1, (), D> for ForeignToplevelManagerState
+where
+    D: Dispatch<ZwlrForeignToplevelManagerV1, ()> + ForeignToplevelHandler + 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        manager: &ZwlrForeignToplevelManagerV1,
+        request: zwlr_foreign_toplevel_manager_v1::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zwlr_foreign_toplevel_manager_v1::Request::Stop => {
+                state
+                    .foreign_toplevel_manager_state()
+                    .managers
+                    .retain(|m| m != manager);
+                manager.finished();
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl<D> Dispatch<ZwlrForeignToplevelHandleV1, u64, D> for ForeignToplevelManagerState
+where
+    D: Dispatch<ZwlrForeignToplevelHandleV1, u64> + ForeignToplevelHandler + 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        handle: &ZwlrForeignToplevelHandleV1,
+        request: zwlr_foreign_toplevel_handle_v1::Request,
+        id: &u64,
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zwlr_foreign_toplevel_handle_v1::Request::Activate { .. } => state.activate(*id),
+            zwlr_foreign_toplevel_handle_v1::Request::Close => state.close(*id),
+            zwlr_foreign_toplevel_handle_v1::Request::Destroy => {
+                for toplevel in &mut state.foreign_toplevel_manager_state().toplevels {
+                    toplevel.handles.retain(|h| h != handle);
+                }
+            }
+            // Windows are neither minimized nor maximized here, and
+            // fullscreen stays with the window's own requests
+            _ => {}
+        }
+    }
+}
+
+#[macro_export]
+macro_rules! delegate_foreign_toplevel {
+    ($ty: ty) => {
+        smithay::reexports::wayland_server::delegate_global_dispatch!($ty: [
+            smithay::reexports::wayland_protocols_wlr::foreign_toplevel::v1::server::zwlr_foreign_toplevel_manager_v1::ZwlrForeignToplevelManagerV1: $crate::protocols::foreign_toplevel::ForeignToplevelGlobalData
+        ] => $crate::protocols::foreign_toplevel::ForeignToplevelManagerState);
+        smithay::reexports::wayland_server::delegate_dispatch!($ty: [
+            smithay::reexports::wayland_protocols_wlr::foreign_toplevel::v1::server::zwlr_foreign_toplevel_manager_v1::ZwlrForeignToplevelManagerV1: ()
+        ] => $crate::protocols::foreign_toplevel::ForeignToplevelManagerState);
+        smithay::reexports::wayland_server::delegate_dispatch!($ty: [
+            smithay::reexports::wayland_protocols_wlr::foreign_toplevel::v1::server::zwlr_foreign_toplevel_handle_v1::ZwlrForeignToplevelHandleV1: u64
+        ] => $crate::protocols::foreign_toplevel::ForeignToplevelManagerState);
+    };
+}
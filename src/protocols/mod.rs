@@ -0,0 +1,3 @@
+pub mod foreign_toplevel;
+pub mod output_power;
+pub mod screencopy;
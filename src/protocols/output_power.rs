@@ -0,0 +1,214 @@
+use smithay::{
+    output::Output,
+    reexports::{
+        wayland_protocols_wlr::output_power_management::v1::server::{
+            zwlr_output_power_manager_v1::{self, ZwlrOutputPowerManagerV1},
+            zwlr_output_power_v1::{self, ZwlrOutputPowerV1},
+        },
+        wayland_server::{
+            Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource, WEnum,
+        },
+    },
+};
+
+const VERSION: u32 = 1;
+
+/// The `zwlr_output_power_manager_v1` global, used by swayidle and wlopm.
+pub struct OutputPowerManagerState {
+    /// Live power controls, each with the output it controls.
+    powers: Vec<(ZwlrOutputPowerV1, Output)>,
+}
+
+pub struct OutputPowerGlobalData {
+    filter: Box<dyn Fn(&Client) -> bool + Send + Sync>,
+}
+
+impl OutputPowerManagerState {
+    /// Creates the global, visible to the clients `filter` accepts.
+    pub fn new<D, F>(display: &DisplayHandle, filter: F) -> Self
+    where
+        D: GlobalDispatch<ZwlrOutputPowerManagerV1, OutputPowerGlobalData>
+            + Dispatch<ZwlrOutputPowerManagerV1, ()>
+            + Dispatch<ZwlrOutputPowerV1, ()>
+            + OutputPowerHandler
+            + 'static,
+        F: Fn(&Client) -> bool + Send + Sync + 'static,
+    {
+        display.create_global::<D, ZwlrOutputPowerManagerV1, _>(
+            VERSION,
+            OutputPowerGlobalData {
+                filter: Box::new(filter),
+            },
+        );
+        Self { powers: Vec::new() }
+    }
+
+    /// Tells the clients controlling `output` that it was turned on or off.
+    pub fn mode_changed(&mut self, output: &Output, on: bool) {
+        self.powers.retain(|(power, _)| power.is_alive());
+        for (power, _) in self.powers.iter().filter(|(_, o)| o == output) {
+            power.mode(mode(on));
+        }
+    }
+
+    /// Fails the controls of an output that went away, they are inert afterwards.
+    pub fn output_removed(&mut self, output: &Output) {
+        self.powers.retain(|(power, o)| {
+            if o == output {
+                power.failed();
+                false
+            } else {
+                power.is_alive()
+            }
+        });
+    }
+}
+
+pub trait OutputPowerHandler {
+    fn output_power_manager_state(&mut self) -> &mut OutputPowerManagerState;
+    /// Whether `output` is on, None when it can't be controlled.
+    fn output_power(&mut self, output: &Output) -> Option<bool>;
+    /// Turns `output` on or off, false when that failed.
+    fn set_output_power_mode(&mut self, output: &Output, on: bool) -> bool;
+}
+
+fn mode(on: bool) -> zwlr_output_power_v1::Mode {
+    if on {
+        zwlr_output_power_v1::Mode::On
+    } else {
+        zwlr_output_power_v1::Mode::Off
+    }
+}
+
+impl<D> GlobalDispatch<ZwlrOutputPowerManagerV1, OutputPowerGlobalData, D>
+    for OutputPowerManagerState
+where
+    D: GlobalDispatch<ZwlrOutputPowerManagerV1, OutputPowerGlobalData>
+        + Dispatch<ZwlrOutputPowerManagerV1, ()>
+        + Dispatch<ZwlrOutputPowerV1, ()>
+        + OutputPowerHandler
+        + 'static,
+{
+    fn bind(
+        _state: &mut D,
+        _dh: &DisplayHandle,
+        _client: &Client,
+        resource: New<ZwlrOutputPowerManagerV1>,
+        _global_data: &OutputPowerGlobalData,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        data_init.init(resource, ());
+    }
+
+    fn can_view(client: Client, global_data: &OutputPowerGlobalData) -> bool {
+        (global_data.filter)(&client)
+    }
+}
+
+impl<D> Dispatch<ZwlrOutputPowerManagerV1, (), D> for OutputPowerManagerState
+where
+    D: Dispatch<ZwlrOutputPowerManagerV1, ()>
+        + Dispatch<ZwlrOutputPowerV1, ()>
+        + OutputPowerHandler
+        + 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        _manager: &ZwlrOutputPowerManagerV1,
+        request: zwlr_output_power_manager_v1::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        let (id, output) = match request {
+            zwlr_output_power_manager_v1::Request::GetOutputPower { id, output } => (id, output),
+            zwlr_output_power_manager_v1::Request::Destroy => return,
+            _ => unreachable!(),
+        };
+        let power = data_init.init(id, ());
+
+        let Some((output, on)) = Output::from_resource(&output)
+            .and_then(|output| Some((output.clone(), state.output_power(&output)?)))
+        else {
+            // Gone or disabled outputs can't be controlled
+            power.failed();
+            return;
+        };
+        power.mode(mode(on));
+        state
+            .output_power_manager_state()
+            .powers
+            .push((power, output));
+    }
+}
+
+impl<D> Dispatch<ZwlrOutputPowerV1, (), D> for OutputPowerManagerState
+where
+    D: Dispatch<ZwlrOutputPowerV1, ()> + OutputPowerHandler + 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        power: &ZwlrOutputPowerV1,
+        request: zwlr_output_power_v1::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zwlr_output_power_v1::Request::SetMode { mode } => {
+                let on = match mode {
+                    WEnum::Value(zwlr_output_power_v1::Mode::On) => true,
+                    WEnum::Value(zwlr_output_power_v1::Mode::Off) => false,
+                    _ => {
+                        power.post_error(
+                            zwlr_output_power_v1::Error::InvalidMode,
+                            "unknown power mode",
+                        );
+                        return;
+                    }
+                };
+                // Failed controls are inert
+                let Some(output) = state
+                    .output_power_manager_state()
+                    .powers
+                    .iter()
+                    .find(|(p, _)| p == power)
+                    .map(|(_, output)| output.clone())
+                else {
+                    return;
+                };
+                if !state.set_output_power_mode(&output, on) {
+                    power.failed();
+                    state
+                        .output_power_manager_state()
+                        .powers
+                        .retain(|(p, _)| p != power);
+                }
+            }
+            zwlr_output_power_v1::Request::Destroy => {
+                state
+                    .output_power_manager_state()
+                    .powers
+                    .retain(|(p, _)| p != power);
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+#[macro_export]
+macro_rules! delegate_output_power {
+    ($ty: ty) => {
+        smithay::reexports::wayland_server::delegate_global_dispatch!($ty: [
+            smithay::reexports::wayland_protocols_wlr::output_power_management::v1::server::zwlr_output_power_manager_v1::ZwlrOutputPowerManagerV1: $crate::protocols::output_power::OutputPowerGlobalData
+        ] => $crate::protocols::output_power::OutputPowerManagerState);
+        smithay::reexports::wayland_server::delegate_dispatch!($ty: [
+            smithay::reexports::wayland_protocols_wlr::output_power_management::v1::server::zwlr_output_power_manager_v1::ZwlrOutputPowerManagerV1: ()
+        ] => $crate::protocols::output_power::OutputPowerManagerState);
+        smithay::reexports::wayland_server::delegate_dispatch!($ty: [
+            smithay::reexports::wayland_protocols_wlr::output_power_management::v1::server::zwlr_output_power_v1::ZwlrOutputPowerV1: ()
+        ] => $crate::protocols::output_power::OutputPowerManagerState);
+    };
+}
@@ -0,0 +1,336 @@
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use smithay::{
+    output::Output,
+    reexports::{
+        wayland_protocols_wlr::screencopy::v1::server::{
+            zwlr_screencopy_frame_v1::{self, ZwlrScreencopyFrameV1},
+            zwlr_screencopy_manager_v1::{self, ZwlrScreencopyManagerV1},
+        },
+        wayland_server::{
+            protocol::{wl_buffer::WlBuffer, wl_shm},
+            Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource,
+        },
+    },
+    utils::{Logical, Physical, Rectangle},
+    wayland::shm::with_buffer_contents,
+};
+
+const VERSION: u32 = 3;
+
+/// Only shm buffers in this format are offered to clients.
+pub const SHM_FORMAT: wl_shm::Format = wl_shm::Format::Argb8888;
+
+/// The `zwlr_screencopy_manager_v1` global, used by grim and other screenshot tools.
+pub struct ScreencopyManagerState;
+
+pub struct ScreencopyGlobalData {
+    filter: Box<dyn Fn(&Client) -> bool + Send + Sync>,
+}
+
+impl ScreencopyManagerState {
+    /// Creates the global, visible to the clients `filter` accepts.
+    pub fn new<D, F>(display: &DisplayHandle, filter: F) -> Self
+    where
+        D: GlobalDispatch<ZwlrScreencopyManagerV1, ScreencopyGlobalData>
+            + Dispatch<ZwlrScreencopyManagerV1, ()>
+            + Dispatch<ZwlrScreencopyFrameV1, FrameData>
+            + ScreencopyHandler
+            + 'static,
+        F: Fn(&Client) -> bool + Send + Sync + 'static,
+    {
+        display.create_global::<D, ZwlrScreencopyManagerV1, _>(
+            VERSION,
+            ScreencopyGlobalData {
+                filter: Box::new(filter),
+            },
+        );
+        Self
+    }
+}
+
+pub trait ScreencopyHandler {
+    /// A client handed over a buffer for a capture of `screencopy.output()`.
+    /// Dropping the capture without submitting it fails the frame.
+    fn frame(&mut self, screencopy: Screencopy);
+}
+
+/// What a frame captures, None when it failed right away.
+pub struct FrameData {
+    info: Option<FrameInfo>,
+    copied: AtomicBool,
+}
+
+struct FrameInfo {
+    output: Output,
+    region: Rectangle<i32, Physical>,
+    overlay_cursor: bool,
+}
+
+/// A capture waiting for the next frame of its output.
+pub struct Screencopy {
+    frame: ZwlrScreencopyFrameV1,
+    buffer: WlBuffer,
+    output: Output,
+    region: Rectangle<i32, Physical>,
+    overlay_cursor: bool,
+    with_damage: bool,
+    submitted: bool,
+}
+
+impl Screencopy {
+    pub fn output(&self) -> &Output {
+        &self.output
+    }
+
+    /// Captured part of the output, in its buffer coordinates.
+    pub fn region(&self) -> Rectangle<i32, Physical> {
+        self.region
+    }
+
+    pub fn buffer(&self) -> &WlBuffer {
+        &self.buffer
+    }
+
+    pub fn overlay_cursor(&self) -> bool {
+        self.overlay_cursor
+    }
+
+    /// Whether the client waits for the region to change before the copy.
+    pub fn with_damage(&self) -> bool {
+        self.with_damage
+    }
+
+    /// Reports what changed since the last copy, relative to `region`.
+    pub fn damage(&self, damage: &[Rectangle<i32, Physical>]) {
+        if self.frame.version() < 2 {
+            return;
+        }
+        for rect in damage {
+            self.frame.damage(
+                rect.loc.x.max(0) as u32,
+                rect.loc.y.max(0) as u32,
+                rect.size.w.max(0) as u32,
+                rect.size.h.max(0) as u32,
+            );
+        }
+    }
+
+    /// Tells the client the buffer is filled, `presented` is the clock time of the frame.
+    pub fn submit(mut self, y_invert: bool, presented: Duration) {
+        if y_invert {
+            self.frame.flags(zwlr_screencopy_frame_v1::Flags::YInvert);
+        }
+        let secs = presented.as_secs();
+        self.frame.ready(
+            (secs >> 32) as u32,
+            (secs & 0xFFFF_FFFF) as u32,
+            presented.subsec_nanos(),
+        );
+        self.submitted = true;
+    }
+}
+
+impl Drop for Screencopy {
+    fn drop(&mut self) {
+        if !self.submitted {
+            self.frame.failed();
+        }
+    }
+}
+
+/// `region` (or the whole output) in the output's buffer coordinates, None
+/// when the output has no mode or the region lies outside of it.
+fn buffer_region(
+    output: &Output,
+    region: Option<Rectangle<i32, Logical>>,
+) -> Option<Rectangle<i32, Physical>> {
+    let mode = output.current_mode()?;
+    let transform = output.current_transform();
+    let size = transform.transform_size(mode.size);
+    let region = match region {
+        Some(region) => region
+            .to_f64()
+            .to_physical(output.current_scale().fractional_scale())
+            .to_i32_round()
+            .intersection(Rectangle::from_size(size))?,
+        None => Rectangle::from_size(size),
+    };
+    Some(transform.invert().transform_rect_in(region, &size))
+}
+
+impl<D> GlobalDispatch<ZwlrScreencopyManagerV1, ScreencopyGlobalData, D> for ScreencopyManagerState
+where
+    D: GlobalDispatch<ZwlrScreencopyManagerV1, ScreencopyGlobalData>
+        + Dispatch<ZwlrScreencopyManagerV1, ()>
+        + Dispatch<ZwlrScreencopyFrameV1, FrameData>
+        + ScreencopyHandler
+        + 'static,
+{
+    fn bind(
+        _state: &mut D,
+        _dh: &DisplayHandle,
+        _client: &Client,
+        resource: New<ZwlrScreencopyManagerV1>,
+        _global_data: &ScreencopyGlobalData,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        data_init.init(resource, ());
+    }
+
+    fn can_view(client: Client, global_data: &ScreencopyGlobalData) -> bool {
+        (global_data.filter)(&client)
+    }
+}
+
+impl<D> Dispatch<ZwlrScreencopyManagerV1, (), D> for ScreencopyManagerState
+where
+    D: Dispatch<ZwlrScreencopyManagerV1, ()>
+        + Dispatch<ZwlrScreencopyFrameV1, FrameData>
+        + ScreencopyHandler
+        + 'static,
+{
+    fn request(
+        _state: &mut D,
+        _client: &Client,
+        _manager: &ZwlrScreencopyManagerV1,
+        request: zwlr_screencopy_manager_v1::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        let (frame, overlay_cursor, output, region) = match request {
+            zwlr_screencopy_manager_v1::Request::CaptureOutput {
+                frame,
+                overlay_cursor,
+                output,
+            } => (frame, overlay_cursor, output, None),
+            zwlr_screencopy_manager_v1::Request::CaptureOutputRegion {
+                frame,
+                overlay_cursor,
+                output,
+                x,
+                y,
+                width,
+                height,
+            } => (
+                frame,
+                overlay_cursor,
+                output,
+                Some(Rectangle::new((x, y).into(), (width, height).into())),
+            ),
+            zwlr_screencopy_manager_v1::Request::Destroy => return,
+            _ => unreachable!(),
+        };
+
+        let info = Output::from_resource(&output).and_then(|output| {
+            let region = buffer_region(&output, region)?;
+            Some(FrameInfo {
+                output,
+                region,
+                overlay_cursor: overlay_cursor != 0,
+            })
+        });
+        let Some(size) = info.as_ref().map(|info| info.region.size) else {
+            // Gone or disabled outputs can't be captured
+            let frame = data_init.init(
+                frame,
+                FrameData {
+                    info: None,
+                    copied: AtomicBool::new(false),
+                },
+            );
+            frame.failed();
+            return;
+        };
+
+        let frame = data_init.init(
+            frame,
+            FrameData {
+                info,
+                copied: AtomicBool::new(false),
+            },
+        );
+        frame.buffer(SHM_FORMAT, size.w as u32, size.h as u32, size.w as u32 * 4);
+        if frame.version() >= 3 {
+            frame.buffer_done();
+        }
+    }
+}
+
+impl<D> Dispatch<ZwlrScreencopyFrameV1, FrameData, D> for ScreencopyManagerState
+where
+    D: Dispatch<ZwlrScreencopyFrameV1, FrameData> + ScreencopyHandler + 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        frame: &ZwlrScreencopyFrameV1,
+        request: zwlr_screencopy_frame_v1::Request,
+        data: &FrameData,
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        let (buffer, with_damage) = match request {
+            zwlr_screencopy_frame_v1::Request::Copy { buffer } => (buffer, false),
+            zwlr_screencopy_frame_v1::Request::CopyWithDamage { buffer } => (buffer, true),
+            zwlr_screencopy_frame_v1::Request::Destroy => return,
+            _ => unreachable!(),
+        };
+        // Failed frames already told the client
+        let Some(info) = data.info.as_ref() else {
+            return;
+        };
+        if data.copied.swap(true, Ordering::Relaxed) {
+            frame.post_error(
+                zwlr_screencopy_frame_v1::Error::AlreadyUsed,
+                "frame was already copied",
+            );
+            return;
+        }
+
+        let size = info.region.size;
+        let valid = with_buffer_contents(&buffer, |_, _, shm| {
+            shm.format == SHM_FORMAT
+                && shm.width == size.w
+                && shm.height == size.h
+                && shm.stride == size.w * 4
+        })
+        .unwrap_or(false);
+        if !valid {
+            frame.post_error(
+                zwlr_screencopy_frame_v1::Error::InvalidBuffer,
+                "expected the advertised shm buffer",
+            );
+            return;
+        }
+
+        state.frame(Screencopy {
+            frame: frame.clone(),
+            buffer,
+            output: info.output.clone(),
+            region: info.region,
+            overlay_cursor: info.overlay_cursor,
+            with_damage,
+            submitted: false,
+        });
+    }
+}
+
+#[macro_export]
+macro_rules! delegate_screencopy {
+    ($ty: ty) => {
+        smithay::reexports::wayland_server::delegate_global_dispatch!($ty: [
+            smithay::reexports::wayland_protocols_wlr::screencopy::v1::server::zwlr_screencopy_manager_v1::ZwlrScreencopyManagerV1: $crate::protocols::screencopy::ScreencopyGlobalData
+        ] => $crate::protocols::screencopy::ScreencopyManagerState);
+        smithay::reexports::wayland_server::delegate_dispatch!($ty: [
+            smithay::reexports::wayland_protocols_wlr::screencopy::v1::server::zwlr_screencopy_manager_v1::ZwlrScreencopyManagerV1: ()
+        ] => $crate::protocols::screencopy::ScreencopyManagerState);
+        smithay::reexports::wayland_server::delegate_dispatch!($ty: [
+            smithay::reexports::wayland_protocols_wlr::screencopy::v1::server::zwlr_screencopy_frame_v1::ZwlrScreencopyFrameV1: $crate::protocols::screencopy::FrameData
+        ] => $crate::protocols::screencopy::ScreencopyManagerState);
+    };
+}
@@ -1,20 +1,30 @@
 use std::{
     cell::RefCell,
     ffi::OsString,
+    process::Child,
     sync::{atomic::AtomicBool, Arc},
-    time::Instant,
+    time::{Duration, Instant},
 };
 
 use smithay::{
     backend::session::Session,
-    desktop::{layer_map_for_output, PopupManager, Window, WindowSurface, WindowSurfaceType},
+    desktop::{
+        layer_map_for_output, space::SpaceElement, PopupManager, Space, Window, WindowSurface,
+        WindowSurfaceType,
+    },
     input::{
         keyboard::{Keysym, XkbConfig},
-        pointer::PointerHandle,
+        pointer::{CursorImageStatus, MotionEvent, PointerHandle},
         Seat, SeatState,
     },
+    output::Output,
     reexports::{
-        calloop::{generic::Generic, Interest, LoopHandle, LoopSignal, Mode, PostAction},
+        calloop::{
+            generic::Generic,
+            timer::{TimeoutAction, Timer},
+            Interest, LoopHandle, LoopSignal, Mode, PostAction, RegistrationToken,
+        },
+        input,
         wayland_server::{
             backend::{ClientData, ClientId, DisconnectReason},
             protocol::wl_surface::WlSurface,
@@ -23,7 +33,11 @@ use smithay::{
     },
     utils::{Clock, Logical, Monotonic, Point, Rectangle},
     wayland::{
-        compositor::{CompositorClientState, CompositorState},
+        compositor::{get_parent, CompositorClientState, CompositorState},
+        content_type::ContentTypeState,
+        cursor_shape::CursorShapeManagerState,
+        idle_inhibit::IdleInhibitManagerState,
+        idle_notify::IdleNotifierState,
         input_method::InputMethodManagerState,
         keyboard_shortcuts_inhibit::KeyboardShortcutsInhibitState,
         output::OutputManagerState,
@@ -34,6 +48,7 @@ use smithay::{
             data_device::DataDeviceState, primary_selection::PrimarySelectionState,
             wlr_data_control::DataControlState,
         },
+        session_lock::SessionLockManagerState,
         shell::{
             wlr_layer::{self, WlrLayerShellState},
             xdg::{decoration::XdgDecorationState, XdgShellState},
@@ -56,11 +71,28 @@ use smithay::{
 };
 
 use crate::{
+    protocols::{
+        foreign_toplevel::ForeignToplevelManagerState, output_power::OutputPowerManagerState,
+        screencopy::ScreencopyManagerState,
+    },
     udev::UdevData,
     utils::{
-        config::Config,
-        layout::LayoutBehavior,
-        workspaces::{place_on_center, WindowMode, Workspaces},
+        animation::WorkspaceSlide,
+        arrangement::Arrangements,
+        autohide::{is_hidden, usable_zone},
+        config::{Config, FocusNewWindows, FocusOnEmpty, FullscreenBehavior, Margins},
+        grab::Drag,
+        idle::{Idle, IDLE_CHECK_INTERVAL},
+        layout::{LayoutBehavior, LayoutState},
+        lock::SessionLock,
+        overlay::Overlay,
+        selection::PrimaryCache,
+        stats::RenderStats,
+        titlebar::titlebar_height,
+        workspaces::{
+            constrain_to_outputs, place_on_center, save_floating_geometry, window_center,
+            window_output_name, UnmappedWindow, WindowMode, Workspaces,
+        },
     },
 };
 use crate::{utils::workspaces::is_fullscreen, SERIAL_COUNTER};
@@ -93,6 +125,8 @@ pub struct State {
     pub keyboard_shortcuts_inhibit_state: KeyboardShortcutsInhibitState,
 
     pub output_manager_state: OutputManagerState,
+    pub output_power_state: OutputPowerManagerState,
+    pub foreign_toplevel_state: ForeignToplevelManagerState,
 
     pub xdg_foreign_state: XdgForeignState,
     pub xdg_shell_state: XdgShellState,
@@ -111,6 +145,47 @@ pub struct State {
 
     // input-related fields
     pub suppressed_keys: Vec<Keysym>,
+    /// Pressed mouse buttons that ran a bind, their release isn't forwarded.
+    pub suppressed_buttons: Vec<u32>,
+    pub drag: Option<Drag>,
+    /// Cursor image requested by the client under the pointer.
+    pub cursor_status: CursorImageStatus,
+    pub mod_tap_start: Option<Instant>,
+    /// Window shown by `CycleWindows` until the modifiers are released.
+    pub window_cycle: Option<Window>,
+    pub pending_exit: Option<Instant>,
+    pub scroll_accumulator: f64,
+    /// Fingers, start time and travelled distance of a swipe held back from
+    /// clients until it is known whether a binding in `gestures` matches it.
+    pub swipe: Option<(u32, u32, Point<f64, Logical>)>,
+    /// Timer repeating the action of a held bind.
+    pub key_repeat: Option<RegistrationToken>,
+    pub keyboards: Vec<input::Device>,
+
+    // debugging
+    /// Outputs whose render elements are logged with their next frame.
+    pub dump_render_elements: Vec<String>,
+    /// Set by `KOWINWM_DUMP_ELEMENTS`, logs the elements of every frame.
+    pub dump_every_frame: bool,
+    pub debug_overlay: bool,
+    pub render_stats: RenderStats,
+
+    pub idle_notifier_state: IdleNotifierState<Self>,
+    pub idle: Idle,
+
+    pub session_lock_state: SessionLockManagerState,
+    /// Set from the lock request until the unlock, nothing but the lock surfaces is shown.
+    pub session_lock: Option<SessionLock>,
+
+    /// Compositor-drawn UI holding the keyboard.
+    pub overlay: Option<Box<dyn Overlay>>,
+    /// Programs started from `autostart`, terminated on shutdown.
+    pub autostart_children: Vec<Child>,
+    pub workspace_slide: Option<WorkspaceSlide>,
+    pub arrangements: Arrangements,
+    /// Toplevels that unmapped themselves, kept until they map again or are destroyed.
+    pub unmapped_windows: Vec<UnmappedWindow>,
+    pub primary_cache: PrimaryCache,
 
     #[cfg(feature = "xwayland")]
     pub xwayland_shell_state: xwayland_shell::XWaylandShellState,
@@ -164,23 +239,58 @@ impl State {
             layout: &current_layout,
             ..Default::default()
         };
-        seat.add_keyboard(xkb_config, 200, 25).unwrap();
+        seat.add_keyboard(
+            xkb_config,
+            config.keyboard.repeat_delay,
+            config.keyboard.repeat_rate,
+        )
+        .unwrap();
         let pointer = seat.add_pointer();
         let listening_socket = ListeningSocketSource::new_auto().unwrap();
         let config = Config::get_config().unwrap_or_default();
 
         TabletManagerState::new::<Self>(&dh);
 
+        // Named cursors arrive through SeatHandler::cursor_image
+        CursorShapeManagerState::new::<Self>(&dh);
+
         InputMethodManagerState::new::<Self, _>(&dh, |_client| true);
 
         PointerGesturesState::new::<Self>(&dh);
 
+        ContentTypeState::new::<Self>(&dh);
+
         SecurityContextState::new::<Self, _>(&dh, |client| {
             client
                 .get_data::<ClientState>()
                 .is_none_or(|client_state| client_state.security_context.is_none())
         });
 
+        // Sandboxed clients don't get to read the screen
+        ScreencopyManagerState::new::<Self, _>(&dh, |client| {
+            client
+                .get_data::<ClientState>()
+                .is_none_or(|client_state| client_state.security_context.is_none())
+        });
+
+        let output_power_state = OutputPowerManagerState::new::<Self, _>(&dh, |client| {
+            client
+                .get_data::<ClientState>()
+                .is_none_or(|client_state| client_state.security_context.is_none())
+        });
+
+        let foreign_toplevel_state = ForeignToplevelManagerState::new::<Self, _>(&dh, |client| {
+            client
+                .get_data::<ClientState>()
+                .is_none_or(|client_state| client_state.security_context.is_none())
+        });
+
+        let session_lock_state = SessionLockManagerState::new::<Self, _>(&dh, |client| {
+            client
+                .get_data::<ClientState>()
+                .is_none_or(|client_state| client_state.security_context.is_none())
+        });
+
         // Get the name of the listening socket.
         // Clients will connect to this socket.
         let socket_name = listening_socket.socket_name().to_os_string();
@@ -214,6 +324,24 @@ impl State {
             )
             .expect("Failed to init wayland server source");
 
+        let idle_notifier_state = IdleNotifierState::<Self>::new(&dh, loop_handle.clone());
+        IdleInhibitManagerState::new::<Self>(&dh);
+        loop_handle
+            .insert_source(Timer::from_duration(IDLE_CHECK_INTERVAL), |_, _, state| {
+                TimeoutAction::ToDuration(state.check_idle())
+            })
+            .expect("Failed to init the idle timer.");
+
+        if config.ping_interval > 0 {
+            let interval = Duration::from_secs(config.ping_interval);
+            loop_handle
+                .insert_source(Timer::from_duration(interval), move |_, _, state| {
+                    state.ping_clients();
+                    TimeoutAction::ToDuration(interval)
+                })
+                .expect("Failed to init the ping timer.");
+        }
+
         Self {
             clock,
 
@@ -224,11 +352,13 @@ impl State {
             running: Arc::new(AtomicBool::new(true)),
 
             output_manager_state,
+            output_power_state,
+            foreign_toplevel_state,
             pointer_location: (0.0, 0.0).into(),
             pointer,
             backend_data,
             loop_handle,
-            workspaces: Workspaces::new(config.workspaces),
+            workspaces: Workspaces::new(config.workspaces, config.master_ratio),
             display_handle: dh,
             loop_signal,
             start_time,
@@ -253,6 +383,34 @@ impl State {
 
             // input-related fields
             suppressed_keys: Vec::new(),
+            suppressed_buttons: Vec::new(),
+            drag: None,
+            cursor_status: CursorImageStatus::default_named(),
+            mod_tap_start: None,
+            window_cycle: None,
+            swipe: None,
+            key_repeat: None,
+            pending_exit: None,
+            scroll_accumulator: 0.0,
+            keyboards: Vec::new(),
+
+            dump_render_elements: Vec::new(),
+            dump_every_frame: std::env::var_os("KOWINWM_DUMP_ELEMENTS").is_some(),
+            debug_overlay: std::env::var_os("KOWINWM_DEBUG_OVERLAY").is_some(),
+            render_stats: RenderStats::new(),
+
+            idle_notifier_state,
+            idle: Idle::new(),
+
+            session_lock_state,
+            session_lock: None,
+
+            overlay: None,
+            autostart_children: Vec::new(),
+            workspace_slide: None,
+            arrangements: Arrangements::default(),
+            unmapped_windows: Vec::new(),
+            primary_cache: PrimaryCache::default(),
 
             #[cfg(feature = "xwayland")]
             xwayland_shell_state,
@@ -269,25 +427,38 @@ impl State {
         smithay::reexports::wayland_server::protocol::wl_surface::WlSurface,
         Point<f64, Logical>,
     )> {
+        if self.is_locked() {
+            return self.lock_surface_under();
+        }
         let ws = self.workspaces.get_current();
         let pos = self.pointer_location;
-        let output = ws.space.outputs().find(|o| {
-            let geometry = ws.space.output_geometry(o).unwrap();
-            geometry.contains(pos.to_i32_round())
-        })?;
-        let output_geo = ws.space.output_geometry(output).unwrap();
+        let (output, output_geo, output_pos) = output_under(&ws.space, pos)?;
 
         let mut under = None;
-        let layers = layer_map_for_output(&output);
-
-        if let Some(fullscreen) = is_fullscreen(ws.space.elements()) {
+        let layers = layer_map_for_output(output);
+
+        let pinned = self.config.fullscreen_behavior == FullscreenBehavior::Pinned;
+        if let Some(over) = is_fullscreen(ws.space.elements())
+            .filter(|_| pinned)
+            .and_then(|fullscreen| {
+                ws.above_fullscreen(fullscreen)
+                    .iter()
+                    .rev()
+                    .find_map(|w| self.window_contains_pointer(w))
+            })
+        {
+            under = Some(over);
+        } else if let Some(fullscreen) = is_fullscreen(ws.space.elements()) {
             let geo = ws.space.element_geometry(fullscreen).unwrap();
+            let loc = geo.loc - fullscreen.geometry().loc;
             under = fullscreen
-                .wl_surface()
-                .map(|s| (s.as_ref().clone(), geo.loc - fullscreen.geometry().loc));
+                .surface_under(pos - loc.to_f64(), WindowSurfaceType::ALL)
+                .map(|(surface, surface_loc)| (surface, surface_loc + loc))
+                .or_else(|| fullscreen.wl_surface().map(|s| (s.as_ref().clone(), loc)));
         } else if let Some(layer) = layers
-            .layer_under(wlr_layer::Layer::Overlay, pos)
-            .or_else(|| layers.layer_under(wlr_layer::Layer::Top, pos))
+            .layer_under(wlr_layer::Layer::Overlay, output_pos)
+            .or_else(|| layers.layer_under(wlr_layer::Layer::Top, output_pos))
+            .filter(|layer| !is_hidden(layer))
         //.or_else(|| {
         //    layers.layers().find(|l| {
         //        l.layer() == wlr_layer::Layer::Overlay || l.layer() == wlr_layer::Layer::Top
@@ -296,23 +467,18 @@ impl State {
         {
             let layer_loc = layers.layer_geometry(layer).unwrap().loc;
             under = layer
-                .surface_under(
-                    pos - output_geo.loc.to_f64() - layer_loc.to_f64(),
-                    WindowSurfaceType::ALL,
-                )
+                .surface_under(output_pos - layer_loc.to_f64(), WindowSurfaceType::ALL)
                 .map(|(surface, loc)| (surface, loc + layer_loc + output_geo.loc));
         } else if let Some(data) = self.window_under() {
             under = Some(data);
         } else if let Some(layer) = layers
-            .layer_under(wlr_layer::Layer::Bottom, pos)
-            .or_else(|| layers.layer_under(wlr_layer::Layer::Background, pos))
+            .layer_under(wlr_layer::Layer::Bottom, output_pos)
+            .or_else(|| layers.layer_under(wlr_layer::Layer::Background, output_pos))
+            .filter(|layer| !is_hidden(layer))
         {
             let layer_loc = layers.layer_geometry(layer).unwrap().loc;
             under = layer
-                .surface_under(
-                    pos - output_geo.loc.to_f64() - layer_loc.to_f64(),
-                    WindowSurfaceType::ALL,
-                )
+                .surface_under(output_pos - layer_loc.to_f64(), WindowSurfaceType::ALL)
                 .map(|(surface, loc)| (surface, loc + layer_loc + output_geo.loc));
         }
 
@@ -355,6 +521,12 @@ impl State {
     }
 
     pub fn set_keyboard_focus(&mut self, surface: Option<WlSurface>) {
+        // Nothing but the lock surfaces gets keys while the session is locked
+        let surface = if self.is_locked() {
+            self.lock_focus(surface)
+        } else {
+            surface
+        };
         if let Some(keyboard) = self.seat.get_keyboard() {
             let serial = SERIAL_COUNTER.next_serial();
             keyboard.set_focus(self, surface, serial);
@@ -362,7 +534,12 @@ impl State {
     }
 
     pub fn set_keyboard_focus_auto(&mut self) {
-        if let Some(under) = self.surface_under().map(|s| s.0) {
+        if self.is_locked() {
+            let focus = self.lock_focus(None);
+            self.set_keyboard_focus(focus);
+            return;
+        }
+        if let Some(under) = self.surface_under().map(|s| root_surface(&s.0)) {
             let ws = self.workspaces.get_current_mut();
             let active = ws
                 .space
@@ -372,28 +549,185 @@ impl State {
 
             if let Some(a) = active {
                 ws.space.raise_element(&a, true);
+                // Stepping through the windows doesn't reorder them yet
+                if self.window_cycle.is_none() {
+                    ws.touch_mru(&a);
+                }
                 ws.active_window = Some(a.clone());
             }
             self.set_keyboard_focus(Some(under));
+        } else if self.config.focus_on_empty == FocusOnEmpty::Clear {
+            self.workspaces.get_current_mut().active_window = None;
+            self.set_keyboard_focus(None);
         }
     }
 
-    pub fn refresh_layout(&mut self) {
+    /// Raises and focuses the next window of the current workspace in
+    /// most-recently-used order, or the previous one with `reverse`. The
+    /// order only changes once `end_window_cycle` commits the choice.
+    pub fn cycle_windows(&mut self, reverse: bool) {
         let ws = self.workspaces.get_current_mut();
-        ws.space.refresh();
-        let fullscreen = is_fullscreen(ws.space.elements()).cloned();
-        let offset = self.config.border.gap + self.config.border.thickness;
+        let windows = ws.mru_windows();
+        let count = windows.len();
+        if count < 2 {
+            return;
+        }
+        // A shown window that closed restarts the cycle from the front
+        let current = self
+            .window_cycle
+            .as_ref()
+            .and_then(|shown| windows.iter().position(|w| w == shown))
+            .unwrap_or(0);
+        let next = if reverse {
+            (current + count - 1) % count
+        } else {
+            (current + 1) % count
+        };
+        let window = windows[next].clone();
 
-        let output_geometry = ws.space.outputs().next().and_then(|o| {
-            let geo = ws.space.output_geometry(&o)?;
-            let map = layer_map_for_output(&o);
-            let zone = map.non_exclusive_zone();
-            Some(Rectangle::new(geo.loc + zone.loc, zone.size))
-        });
-        let geo = match output_geometry {
-            Some(g) => g,
-            None => return,
+        self.window_cycle = Some(window.clone());
+        self.focus_window(&window);
+    }
+
+    /// Raises and focuses `window` of the current workspace, moving the
+    /// pointer onto it so focus follows.
+    pub fn focus_window(&mut self, window: &Window) {
+        let ws = self.workspaces.get_current_mut();
+        ws.space.raise_element(window, true);
+        ws.active_window = Some(window.clone());
+        if let Some(center) = window_center(&ws.space, window) {
+            self.pointer_location = center;
+        }
+        self.set_keyboard_focus_auto();
+    }
+
+    /// Makes the window `cycle_windows` ended on the most recent one.
+    pub fn end_window_cycle(&mut self) {
+        if let Some(window) = self.window_cycle.take() {
+            self.workspaces.get_current_mut().touch_mru(&window);
+        }
+    }
+
+    /// Focuses a freshly mapped window on workspace `ws_index`, following `focus_new_windows`.
+    pub fn focus_new_window(&mut self, window: &Window, ws_index: usize) {
+        let focus = match self.config.focus_new_windows {
+            FocusNewWindows::Always => true,
+            FocusNewWindows::Never => false,
+            FocusNewWindows::Smart => ws_index == self.workspaces.active_ws(),
+        };
+        if !focus {
+            return;
+        }
+
+        let ws = &mut self.workspaces.workspaces[ws_index];
+        if self.config.fullscreen_behavior == FullscreenBehavior::Exclusive
+            && is_fullscreen(ws.space.elements()).is_some_and(|full| full != window)
+        {
+            return;
+        }
+        ws.space.raise_element(window, true);
+        ws.touch_mru(window);
+        ws.active_window = Some(window.clone());
+        for toplevel in ws.space.elements().filter_map(|w| w.toplevel()) {
+            toplevel.send_pending_configure();
+        }
+        self.set_keyboard_focus(window.wl_surface().map(|s| s.as_ref().clone()));
+    }
+
+    /// Pulls floating windows of the current workspace back onto the outputs.
+    fn constrain_floating_windows(&mut self, ws_index: usize) {
+        if !self.config.constrain_floating {
+            return;
+        }
+        let ws = &mut self.workspaces.workspaces[ws_index];
+        let floating: Vec<Window> = ws
+            .space
+            .elements()
+            .filter(|w| {
+                w.user_data()
+                    .get::<RefCell<WindowMode>>()
+                    .map(|d| *d.borrow() == WindowMode::Floating)
+                    .unwrap_or(false)
+            })
+            .cloned()
+            .collect();
+        for window in floating {
+            let Some(geo) = ws.space.element_geometry(&window) else {
+                continue;
+            };
+            let loc = constrain_to_outputs(
+                &ws.space,
+                geo.loc,
+                geo.size,
+                self.config.floating_visible_strip,
+                titlebar_height(&window, self.config.titlebars),
+            );
+            if loc != geo.loc {
+                ws.space.map_element(window, loc, false);
+            }
+        }
+    }
+
+    pub fn refresh_layout(&mut self) {
+        self.refresh_layout_for(self.workspaces.active_ws());
+    }
+
+    pub fn refresh_all_layouts(&mut self) {
+        for index in 0..self.workspaces.workspaces.len() {
+            self.refresh_layout_for(index);
+        }
+    }
+
+    /// Tiles workspace `ws_index`, shown or not. Pointer raising and focus
+    /// only apply to the current workspace.
+    pub fn refresh_layout_for(&mut self, ws_index: usize) {
+        if ws_index >= self.workspaces.workspaces.len() {
+            return;
+        }
+        let current = ws_index == self.workspaces.active_ws();
+        self.workspaces.workspaces[ws_index].space.refresh();
+        self.constrain_floating_windows(ws_index);
+        let ws = &mut self.workspaces.workspaces[ws_index];
+        let fullscreen = is_fullscreen(ws.space.elements()).cloned();
+        let single = ws.single_visible_tile().is_some();
+        let border = &self.config.border;
+        let (gap_inner, gap_outer, thickness) = if self.config.smart_gaps && single {
+            (0, 0, 0)
+        } else if self.config.hide_border_single && single {
+            (border.gap_inner, border.gap_outer, 0)
+        } else {
+            (border.gap_inner, border.gap_outer, border.thickness)
         };
+        // Every tile keeps half the inner gap around it, the output edges
+        // make up the difference to the outer gap
+        let half_gap = gap_inner / 2;
+        let edges = Margins {
+            top: gap_outer - half_gap,
+            left: gap_outer - half_gap,
+            bottom: gap_outer - (gap_inner - half_gap),
+            right: gap_outer - (gap_inner - half_gap),
+        };
+
+        let areas: Vec<(Output, Rectangle<i32, Logical>)> = ws
+            .space
+            .outputs()
+            .filter_map(|o| {
+                let geo = ws.space.output_geometry(o)?;
+                let map = layer_map_for_output(o);
+                let zone = usable_zone(&map);
+                let area = Rectangle::new(geo.loc + zone.loc, zone.size);
+                let margins = self
+                    .config
+                    .outputs
+                    .get(&o.name())
+                    .and_then(|data| data.reserved_margins)
+                    .unwrap_or_default();
+                Some((o.clone(), edges.shrink(margins.shrink(area))))
+            })
+            .collect();
+        if areas.is_empty() {
+            return;
+        }
 
         let mut tiled_windows: Vec<Window> = ws
             .space
@@ -426,39 +760,71 @@ impl State {
         }
 
         let mut active = None;
-        for elem in ws.layout.placement(tiled_windows.iter(), geo) {
-            if let Some(ref full) = fullscreen {
-                if full == elem.window {
-                    continue;
-                }
+        // Tabs (and monocle windows) share one rectangle, raising by pointer would shuffle them
+        let tabbed = ws.layout.is_stacked();
+        let first_output = areas[0].0.clone();
+        for (output, geo) in &areas {
+            // Windows of unknown outputs, and all tabs (they share one bar), go on the first one
+            let windows: Vec<Window> = tiled_windows
+                .iter()
+                .filter(|w| {
+                    let assigned = window_output_name(w)
+                        .filter(|name| !tabbed && areas.iter().any(|(o, _)| o.name() == *name));
+                    match assigned {
+                        Some(name) => name == output.name(),
+                        None => *output == first_output,
+                    }
+                })
+                .cloned()
+                .collect();
+            if windows.is_empty() {
+                continue;
             }
-            let geometry: Rectangle<i32, Logical> = Rectangle::new(
-                (elem.geometry.loc.x + offset, elem.geometry.loc.y + offset).into(),
-                (
-                    elem.geometry.size.w - offset * 2,
-                    elem.geometry.size.h - offset * 2,
-                )
-                    .into(),
-            );
-            match elem.window.underlying_surface() {
-                WindowSurface::Wayland(xdg) => {
-                    xdg.with_pending_state(|state| {
-                        state.size = Some(geometry.size);
-                    });
-                    xdg.send_configure();
-                    ws.space
-                        .map_element(elem.window.clone(), geometry.loc, false);
+            for elem in ws.layout.placement(windows.iter(), *geo) {
+                if let Some(ref full) = fullscreen {
+                    if full == elem.window {
+                        continue;
+                    }
                 }
-                #[cfg(feature = "xwayland")]
-                WindowSurface::X11(x11) => {
-                    x11.configure(geometry).unwrap();
-                    ws.space
-                        .map_element(elem.window.clone(), geometry.loc, false);
+                let inset = half_gap + thickness;
+                let geometry: Rectangle<i32, Logical> = Rectangle::new(
+                    (elem.geometry.loc.x + inset, elem.geometry.loc.y + inset).into(),
+                    (
+                        elem.geometry.size.w - gap_inner - thickness * 2,
+                        elem.geometry.size.h - gap_inner - thickness * 2,
+                    )
+                        .into(),
+                );
+                match elem.window.underlying_surface() {
+                    WindowSurface::Wayland(xdg) => {
+                        xdg.with_pending_state(|state| {
+                            state.size = Some(geometry.size);
+                        });
+                        xdg.send_configure();
+                        ws.space
+                            .map_element(elem.window.clone(), geometry.loc, false);
+                    }
+                    #[cfg(feature = "xwayland")]
+                    WindowSurface::X11(x11) => {
+                        x11.configure(geometry).unwrap();
+                        ws.space
+                            .map_element(elem.window.clone(), geometry.loc, false);
+                    }
+                }
+                if current && elem.geometry.to_f64().contains(self.pointer_location) && !tabbed {
+                    ws.space.raise_element(elem.window, true);
+                    active = Some(elem.window.clone())
                 }
             }
-            if elem.geometry.to_f64().contains(self.pointer_location) {
-                ws.space.raise_element(elem.window, true);
-                active = Some(elem.window.clone())
+        }
+        // Mapping raised the tiles in placement order, the focused one stays on top
+        if tabbed {
+            if let Some(focused) = ws
+                .active_window
+                .clone()
+                .filter(|w| tiled_windows.contains(w))
+            {
+                ws.space.raise_element(&focused, false);
             }
         }
 
@@ -477,21 +843,49 @@ impl State {
         for window in floating_windows {
             if let Some(geometry) = ws.space.element_geometry(&window) {
                 ws.space.map_element(window.clone(), geometry.loc, false);
-                if geometry.to_f64().contains(self.pointer_location) {
+                save_floating_geometry(&ws.space, &window);
+                if current && geometry.to_f64().contains(self.pointer_location) {
                     active = Some(window)
                 }
             } else {
-                place_on_center(&mut ws.space, &window, 0);
+                place_on_center(
+                    &mut ws.space,
+                    &window,
+                    0,
+                    &self.config.floating,
+                    self.config.titlebars,
+                );
             }
         }
 
+        // Focus goes back to the fullscreen window once nothing covers it
+        if fullscreen.is_some() {
+            active = fullscreen;
+        }
         if ws.active_window.is_none() {
             ws.active_window = active.clone();
-            self.set_keyboard_focus(
-                active.and_then(|w| w.wl_surface().map(|s| s.as_ref().clone())),
-            );
+            if current {
+                self.set_keyboard_focus(
+                    active.and_then(|w| w.wl_surface().map(|s| s.as_ref().clone())),
+                );
+            }
         }
     }
+    /// Raises and focuses the window whose tab is under the pointer.
+    pub fn focus_tab_under_pointer(&mut self) -> bool {
+        let ws = self.workspaces.get_current_mut();
+        let LayoutState::Tabbed(tabbed) = &ws.layout else {
+            return false;
+        };
+        let Some(window) = tabbed.tab_at(self.pointer_location).cloned() else {
+            return false;
+        };
+        ws.space.raise_element(&window, true);
+        ws.active_window = Some(window.clone());
+        self.set_keyboard_focus(window.wl_surface().map(|s| s.as_ref().clone()));
+        true
+    }
+
     pub fn window_contains_pointer(
         &self,
         window: &Window,
@@ -499,7 +893,8 @@ impl State {
         smithay::reexports::wayland_server::protocol::wl_surface::WlSurface,
         Point<i32, Logical>,
     )> {
-        let offset = self.config.border.thickness + self.config.border.gap;
+        // Up to the middle of the gap to the next window
+        let offset = self.config.border.thickness + self.config.border.gap_inner / 2;
         let ws = self.workspaces.get_current();
         let geo = ws.space.element_geometry(window)?;
 
@@ -508,13 +903,55 @@ impl State {
         offset_geo.loc -= (offset, offset).into();
 
         if offset_geo.contains(self.pointer_location.to_i32_round()) {
+            let loc = geo.loc - window.geometry().loc;
+            // Subsurfaces are hit-tested against their buffer size, scale and
+            // transform; the gap around the window still targets the toplevel
             return window
-                .wl_surface()
-                .map(|s| (s.as_ref().clone(), geo.loc - window.geometry().loc));
+                .surface_under(self.pointer_location - loc.to_f64(), WindowSurfaceType::ALL)
+                .map(|(surface, surface_loc)| (surface, surface_loc + loc))
+                .or_else(|| window.wl_surface().map(|s| (s.as_ref().clone(), loc)));
         }
 
         None
     }
+    /// The output under the pointer, new windows open there.
+    pub fn pointer_output(&self) -> Option<Output> {
+        let ws = self.workspaces.get_current();
+        ws.space
+            .outputs()
+            .find(|o| {
+                ws.space
+                    .output_geometry(o)
+                    .is_some_and(|geo| geo.to_f64().contains(self.pointer_location))
+            })
+            .cloned()
+    }
+
+    pub fn warp_pointer_to_output_center(&mut self, output: &Output) {
+        let ws = self.workspaces.get_current();
+        let Some(geo) = ws.space.output_geometry(output) else {
+            return;
+        };
+        let center = geo.loc.to_f64() + geo.size.to_f64().downscale(2.0).to_point();
+        self.warp_pointer(center);
+    }
+
+    pub fn warp_pointer(&mut self, location: Point<f64, Logical>) {
+        self.pointer_location = location;
+        let under = self.surface_under();
+        let pointer = self.pointer.clone();
+        pointer.motion(
+            self,
+            under,
+            &MotionEvent {
+                location,
+                serial: SERIAL_COUNTER.next_serial(),
+                time: Duration::from(self.clock.now()).as_millis() as u32,
+            },
+        );
+        pointer.frame(self);
+    }
+
     pub fn window_for_surface(&self, surface: &WlSurface) -> Option<Window> {
         self.workspaces
             .get_current()
@@ -525,6 +962,29 @@ impl State {
     }
 }
 
+/// The output under `pos`, its geometry and `pos` relative to it. Layer maps
+/// work in these output-local logical coordinates, whatever the output scale.
+pub fn output_under<E: SpaceElement + PartialEq>(
+    space: &Space<E>,
+    pos: Point<f64, Logical>,
+) -> Option<(&Output, Rectangle<i32, Logical>, Point<f64, Logical>)> {
+    space.outputs().find_map(|output| {
+        let geometry = space.output_geometry(output)?;
+        geometry
+            .contains(pos.to_i32_round())
+            .then(|| (output, geometry, pos - geometry.loc.to_f64()))
+    })
+}
+
+/// Walks up the subsurface tree to the surface carrying the role.
+pub fn root_surface(surface: &WlSurface) -> WlSurface {
+    let mut root = surface.clone();
+    while let Some(parent) = get_parent(&root) {
+        root = parent;
+    }
+    root
+}
+
 #[derive(Default)]
 pub struct ClientState {
     pub compositor_state: CompositorClientState,
@@ -534,3 +994,30 @@ impl ClientData for ClientState {
     fn initialized(&self, _client_id: ClientId) {}
     fn disconnected(&self, _client_id: ClientId, _reason: DisconnectReason) {}
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_utils::output;
+
+    #[test]
+    fn layer_hit_test_on_scaled_output() {
+        let mut space = Space::<Window>::default();
+        let left = output("DP-1", (1920, 1080), 1, (0, 0));
+        let right = output("DP-2", (3840, 2160), 2, (1920, 0));
+        space.map_output(&left, (0, 0));
+        space.map_output(&right, (1920, 0));
+
+        let (output, geometry, local) = output_under(&space, (2020.0, 1050.0).into()).unwrap();
+        assert_eq!(output.name(), "DP-2");
+        assert_eq!(
+            geometry,
+            Rectangle::new((1920, 0).into(), (1920, 1080).into())
+        );
+        // A 40px bar along the bottom, hit in logical pixels not buffer ones
+        let bar_loc = Point::<i32, Logical>::from((0, 1040));
+        assert_eq!(local - bar_loc.to_f64(), Point::from((100.0, 10.0)));
+
+        assert!(output_under(&space, (3900.0, 10.0).into()).is_none());
+    }
+}
@@ -1,7 +1,13 @@
 use crate::{
     state::State,
     udev::surface::Surface,
-    utils::render::{border::compile_shaders, CustomRenderElements, GlMultiRenderer},
+    utils::{
+        cursor::{frame_at, Cursor},
+        render::{
+            blur::compile_blur_shader, border::compile_shaders, dim::compile_dim_shader,
+            rounded::compile_rounded_shader, CustomRenderElements, GlMultiRenderer,
+        },
+    },
     FALLBACK_CURSOR_DATA,
 };
 use smithay::{
@@ -18,31 +24,37 @@ use smithay::{
             DrmDeviceFd, DrmNode,
         },
         egl::{EGLDevice, EGLDisplay},
-        renderer::element::texture::TextureBuffer,
+        renderer::{
+            damage::OutputDamageTracker,
+            element::texture::TextureBuffer,
+            gles::{GlesRenderer, GlesTexture},
+        },
         session::Session,
     },
-    desktop::utils::OutputPresentationFeedback,
+    desktop::{layer_map_for_output, utils::OutputPresentationFeedback, Space, Window},
+    input::pointer::CursorIcon,
     output::{Mode as WlMode, Output, PhysicalProperties},
     reexports::{
         calloop::RegistrationToken,
         drm::{
             control::{
-                connector::Info,
+                connector::{self, Info},
                 crtc::{self},
-                ModeTypeFlags,
+                Device as ControlDevice, ModeTypeFlags,
             },
             Device as DrmDeviceTrait,
         },
         gbm::Modifier,
         rustix::fs::OFlags,
     },
-    utils::{DeviceFd, Logical, Point, Transform},
+    utils::{DeviceFd, Logical, Physical, Point, Transform},
 };
 use smithay_drm_extras::{
     display_info::{self},
     drm_scanner::DrmScanner,
 };
-use std::{collections::HashMap, fmt::LowerExp, path::PathBuf};
+use std::{collections::HashMap, fmt::LowerExp, path::PathBuf, time::Duration};
+use xcursor::{parser::Image, CursorTheme};
 
 const SUPPORTED_FORMATS: &[Fourcc] = &[
     Fourcc::Abgr2101010,
@@ -100,6 +112,12 @@ impl State {
             .as_mut()
             .add_node(render_node, gbm.clone())
             .unwrap();
+        tracing::info!(
+            "Added gpu {} with render node {}, primary is {}",
+            node,
+            render_node,
+            self.backend_data.primary_gpu
+        );
 
         let registration_token = self
             .loop_handle
@@ -108,14 +126,10 @@ impl State {
             })
             .unwrap();
 
+        let buffer_flags = allocator_flags(&drm, &gbm);
         let allocator = Some(render_node)
             .is_some()
-            .then(|| {
-                GbmAllocator::new(
-                    gbm.clone(),
-                    GbmBufferFlags::RENDERING | GbmBufferFlags::SCANOUT,
-                )
-            })
+            .then(|| GbmAllocator::new(gbm.clone(), buffer_flags))
             .or_else(|| {
                 self.backend_data
                     .devices
@@ -185,7 +199,10 @@ impl State {
                 .as_mut()
                 .remove_node(&device.render_node);
 
-            for surface in device.surfaces.values() {
+            for surface in device.surfaces.values_mut() {
+                if let Some(token) = surface.frame_timer.take() {
+                    self.loop_handle.remove(token);
+                }
                 self.display_handle
                     .disable_global::<State>(surface.global_id.clone());
 
@@ -196,6 +213,89 @@ impl State {
         }
     }
     pub fn connected(&mut self, connector: Info, crtc: crtc::Handle, node: DrmNode) {
+        let name = output_name(&connector);
+        if let Some(output_data) = self.config.outputs.get(&name) {
+            if !output_data.enabled {
+                tracing::info!("Output {} disabled in config, skipping", name);
+                // Kept around so it can still be turned on with ToggleOutput
+                self.backend_data
+                    .disabled_outputs
+                    .insert(name, (node, crtc, connector));
+                return;
+            }
+        }
+        self.enable_output(connector, crtc, node);
+    }
+
+    /// Tears down the output driven by `crtc` and unmaps it from all workspaces.
+    pub fn disconnected(&mut self, node: DrmNode, crtc: crtc::Handle) {
+        let Some(device) = self.backend_data.devices.get_mut(&node) else {
+            tracing::error!("Received connector event for unknown device: {:?}", node);
+            return;
+        };
+        let Some(mut surface) = device.surfaces.remove(&crtc) else {
+            return;
+        };
+        // A deferred repaint would look for the surface that is gone now
+        if let Some(token) = surface.frame_timer.take() {
+            self.loop_handle.remove(token);
+        }
+        if let Err(err) = surface.drm_output.with_compositor(|c| c.clear()) {
+            tracing::warn!("Failed to disable {}: {}", surface.output.name(), err);
+        }
+        self.display_handle
+            .disable_global::<State>(surface.global_id.clone());
+        self.output_power_state.output_removed(&surface.output);
+        self.save_arrangement();
+        for workspace in self.workspaces.workspaces.iter_mut() {
+            workspace.space.unmap_output(&surface.output);
+        }
+        self.refresh_layout();
+        self.restore_arrangement();
+    }
+
+    /// Turns a connected output off, or back on, at runtime.
+    pub fn toggle_output(&mut self, name: &str) -> Result<(), String> {
+        if let Some((node, crtc, connector)) = self.backend_data.disabled_outputs.remove(name) {
+            self.enable_output(connector, crtc, node);
+            return Ok(());
+        }
+
+        let enabled: Vec<(DrmNode, crtc::Handle, String, connector::Handle)> =
+            self.backend_data
+                .devices
+                .iter()
+                .flat_map(|(node, device)| {
+                    device.surfaces.iter().map(|(crtc, surface)| {
+                        (*node, *crtc, surface.output.name(), surface.connector)
+                    })
+                })
+                .collect();
+        let Some((node, crtc, _, connector)) = enabled
+            .iter()
+            .find(|(_, _, output, _)| output == name)
+            .cloned()
+        else {
+            return Err(format!("no output named {name}"));
+        };
+        if enabled.len() == 1 {
+            return Err("refusing to disable the only output".to_string());
+        }
+
+        let info = self.backend_data.devices[&node]
+            .drm_output_manager
+            .device()
+            .get_connector(connector, false)
+            .map_err(|err| err.to_string())?;
+        self.disconnected(node, crtc);
+        self.backend_data
+            .disabled_outputs
+            .insert(name.to_string(), (node, crtc, info));
+        Ok(())
+    }
+
+    fn enable_output(&mut self, connector: Info, crtc: crtc::Handle, node: DrmNode) {
+        self.save_arrangement();
         let device = if let Some(device) = self.backend_data.devices.get_mut(&node) {
             device
         } else {
@@ -207,26 +307,39 @@ impl State {
             .gpus
             .single_renderer(&device.render_node)
             .unwrap();
-        let name = format!(
-            "{}-{}",
-            connector.interface().as_str(),
-            connector.interface_id()
-        );
+        let name = output_name(&connector);
         tracing::info!("New output connected, name: {}", name);
-        // Check if this output is in the config
         let config_output = self.config.outputs.get(&name);
-        if let Some(output_data) = config_output {
-            if !output_data.enabled {
-                tracing::info!("Output {} disabled in config, skipping", name);
-                return;
-            }
-        }
 
-        let drm_mode = *connector
+        let preferred_mode = *connector
             .modes()
             .iter()
             .find(|mode| mode.mode_type().contains(ModeTypeFlags::PREFERRED))
             .unwrap_or(&connector.modes()[0]);
+        // Pick the real mode the config asks for, so the advertised refresh
+        // (in mHz) is the one the CRTC actually runs at
+        let drm_mode = config_output
+            .and_then(|config| {
+                connector.modes().iter().copied().find(|mode| {
+                    let (w, h) = mode.size();
+                    config
+                        .resolution
+                        .is_none_or(|res| res == (w as i32, h as i32))
+                        && config
+                            .refresh_rate
+                            .is_none_or(|refresh| refresh == mode.vrefresh() as i32)
+                })
+            })
+            .unwrap_or_else(|| {
+                if config_output.is_some_and(|c| c.resolution.is_some() || c.refresh_rate.is_some())
+                {
+                    tracing::warn!(
+                        "No mode of {} matches the config, using the preferred one",
+                        name
+                    );
+                }
+                preferred_mode
+            });
 
         let display_info =
             display_info::for_connector(device.drm_output_manager.device(), connector.handle());
@@ -253,16 +366,8 @@ impl State {
         );
         let global = output.create_global::<State>(&self.display_handle);
 
-        let mut output_mode = WlMode::from(drm_mode);
+        let output_mode = WlMode::from(drm_mode);
         let (transform, scale, position) = if let Some(config) = &config_output {
-            // Override refresh rate if provided
-            if let Some(refresh) = config.refresh_rate {
-                output_mode.refresh = refresh * 1000;
-            }
-            // Override resolution if provided
-            if let Some(res) = config.resolution {
-                output_mode.size = res.into();
-            }
             // Parse transform, scale, and position (with typo preserved)
             let transform = config.transform.clone().and_then(parse_transform);
             let scale = config.scale.map(|s| smithay::output::Scale::Fractional(s));
@@ -271,14 +376,16 @@ impl State {
         } else {
             (None, None, None)
         };
+        // Outputs without a configured position go right of the others
+        let position =
+            position.unwrap_or_else(|| free_output_position(&self.workspaces.get_current().space));
 
         output.set_preferred(output_mode);
-        output.change_current_state(Some(output_mode), transform, scale, position);
-        tracing::info!("{:?}", output.current_mode());
+        output.change_current_state(Some(output_mode), transform, scale, Some(position));
+        tracing::info!("{:?} at {:?}", output.current_mode(), position);
 
         for (index, ws) in self.workspaces.workspaces.iter_mut().enumerate() {
-            ws.space
-                .map_output(&output, position.unwrap_or((0, 0).into()));
+            ws.space.map_output(&output, position);
         }
 
         let driver = match device.drm_output_manager.device().get_driver() {
@@ -324,34 +431,262 @@ impl State {
             }
         };
 
-        let pointer_texture = TextureBuffer::from_memory(
-            renderer.as_mut(),
-            FALLBACK_CURSOR_DATA,
-            Fourcc::Abgr8888,
-            (64, 64),
-            false,
-            2,
-            Transform::Normal,
-            None,
-        )
-        .unwrap();
+        let pointer_scale = output.current_scale().fractional_scale();
+        let pointer_texture =
+            load_pointer_texture(renderer.as_mut(), self.config.cursor_size(), pointer_scale);
 
-        // compile border shaders
+        // compile border and dim shaders
         compile_shaders(renderer.as_mut());
+        compile_dim_shader(renderer.as_mut());
+        compile_blur_shader(renderer.as_mut());
+        compile_rounded_shader(renderer.as_mut());
 
         let surface = Surface {
             _device_id: node,
             _render_node: device.render_node,
             drm_output,
             pointer_texture,
+            cursor_textures: HashMap::new(),
+            pointer_scale,
+            last_frame: None,
+            tab_textures: HashMap::new(),
+            mode_warned: false,
+            last_queued: None,
+            frame_timer: None,
+            connector: connector.handle(),
+            powered: true,
+            blur_textures: HashMap::new(),
+            screencopies: Vec::new(),
+            screencopy_damage: OutputDamageTracker::from_output(&output),
             output: output.clone(),
             global_id: global,
         };
 
-        device.surfaces.insert(crtc, surface);
+        // A surface replaced on the same crtc takes its deferred repaint along
+        if let Some(token) = device
+            .surfaces
+            .insert(crtc, surface)
+            .and_then(|mut old| old.frame_timer.take())
+        {
+            self.loop_handle.remove(token);
+        }
 
+        self.restore_arrangement();
+        self.output_changed(&output);
         self.render(node, crtc).ok();
     }
+
+    /// Re-arranges the layer surfaces of `output` and re-tiles, for after its
+    /// mode, scale or transform changed.
+    pub fn output_changed(&mut self, output: &Output) {
+        layer_map_for_output(output).arrange();
+        // Every workspace tiles on this output
+        self.refresh_all_layouts();
+    }
+
+    /// Hands the configured cursor theme and size to clients started from now
+    /// on, and has every output upload its cursors again.
+    pub fn apply_cursor_config(&mut self) {
+        // SAFETY: All set_vars occur on the event loop thread
+        unsafe {
+            if let Some(theme) = &self.config.cursor_theme {
+                std::env::set_var("XCURSOR_THEME", theme);
+            }
+            if let Some(size) = self.config.cursor_size {
+                std::env::set_var("XCURSOR_SIZE", size.to_string());
+            }
+        }
+        for surface in self
+            .backend_data
+            .devices
+            .values_mut()
+            .flat_map(|device| device.surfaces.values_mut())
+        {
+            // No output has scale 0, the next frame loads them
+            surface.pointer_scale = 0.0;
+        }
+    }
+
+    /// Applies scale, transform and position from the config to the connected
+    /// outputs again, e.g. after `ReloadConfig`. Modes need a modeset and only
+    /// change on reconnect.
+    pub fn apply_output_config(&mut self) {
+        let outputs: Vec<Output> = self
+            .backend_data
+            .devices
+            .values()
+            .flat_map(|device| device.surfaces.values().map(|s| s.output.clone()))
+            .collect();
+        for output in outputs {
+            let Some(config) = self.config.outputs.get(&output.name()) else {
+                continue;
+            };
+            let transform = config.transform.clone().and_then(parse_transform);
+            let scale = config.scale.map(smithay::output::Scale::Fractional);
+            let position: Option<Point<i32, Logical>> = config.possition.map(Into::into);
+            output.change_current_state(None, transform, scale, position);
+            if let Some(position) = position {
+                for ws in self.workspaces.workspaces.iter_mut() {
+                    ws.space.map_output(&output, position);
+                }
+            }
+            self.output_changed(&output);
+        }
+    }
+}
+
+/// First location right of every output mapped in `space`, on the top edge.
+fn free_output_position(space: &Space<Window>) -> Point<i32, Logical> {
+    let right = space
+        .outputs()
+        .filter_map(|output| space.output_geometry(output))
+        .map(|geo| geo.loc.x + geo.size.w)
+        .max()
+        .unwrap_or(0);
+    (right, 0).into()
+}
+
+fn output_name(connector: &Info) -> String {
+    format!(
+        "{}-{}",
+        connector.interface().as_str(),
+        connector.interface_id()
+    )
+}
+
+/// Picks the buffer flags for the device allocator. Some drivers can't allocate
+/// scanout-capable buffers in any format and modifier their primary planes
+/// take, those fall back to rendering-only buffers.
+fn allocator_flags(drm: &drm::DrmDevice, gbm: &gbm::GbmDevice<DrmDeviceFd>) -> GbmBufferFlags {
+    let flags = GbmBufferFlags::RENDERING | GbmBufferFlags::SCANOUT;
+    let crtcs = drm
+        .resource_handles()
+        .map(|handles| handles.crtcs().to_vec())
+        .unwrap_or_default();
+    let scanout_ok = crtcs
+        .iter()
+        .filter_map(|crtc| drm.planes(crtc).ok())
+        .flat_map(|planes| planes.primary)
+        .any(|plane| {
+            plane
+                .formats
+                .iter()
+                .filter(|format| SUPPORTED_FORMATS.contains(&format.code))
+                .any(|format| {
+                    let result = if format.modifier == Modifier::Invalid {
+                        gbm.create_buffer_object::<()>(64, 64, format.code, flags)
+                    } else {
+                        gbm.create_buffer_object_with_modifiers2::<()>(
+                            64,
+                            64,
+                            format.code,
+                            std::iter::once(format.modifier),
+                            flags,
+                        )
+                    };
+                    result.is_ok()
+                })
+        });
+    if scanout_ok {
+        flags
+    } else {
+        tracing::warn!("Scanout buffer allocation failed, falling back to rendering-only buffers");
+        GbmBufferFlags::RENDERING
+    }
+}
+
+/// Size of the fallback cursor image in buffer pixels.
+const FALLBACK_CURSOR_SIZE: u32 = 64;
+
+/// A cursor uploaded for one output, with a frame per animation step.
+pub struct CursorTexture {
+    /// Textures with their hotspots.
+    frames: Vec<(TextureBuffer<GlesTexture>, Point<i32, Physical>)>,
+    /// Milliseconds each frame stays up.
+    delays: Vec<u32>,
+}
+
+impl CursorTexture {
+    /// The frame shown at `time` since the compositor started.
+    pub fn frame(&self, time: Duration) -> (TextureBuffer<GlesTexture>, Point<i32, Physical>) {
+        self.frames[frame_at(&self.delays, time)].clone()
+    }
+}
+
+/// Uploads the built-in cursor, used when the theme has neither the shape asked
+/// for nor a default one.
+pub fn load_pointer_texture(
+    renderer: &mut GlesRenderer,
+    cursor_size: u32,
+    output_scale: f64,
+) -> CursorTexture {
+    let image = Image {
+        size: FALLBACK_CURSOR_SIZE,
+        width: FALLBACK_CURSOR_SIZE,
+        height: FALLBACK_CURSOR_SIZE,
+        xhot: 0,
+        yhot: 0,
+        delay: 0,
+        pixels_rgba: FALLBACK_CURSOR_DATA.to_vec(),
+        pixels_argb: Vec::new(),
+    };
+    upload_cursor(renderer, &[image], cursor_size, output_scale)
+        .expect("the built-in cursor uploads")
+}
+
+/// Uploads the cursor for `icon` from `theme`, drawn for `cursor_size` logical
+/// pixels at the output scale. None when the theme lacks it.
+pub fn load_named_cursor_texture(
+    renderer: &mut GlesRenderer,
+    theme: &str,
+    cursor_size: u32,
+    icon: CursorIcon,
+    output_scale: f64,
+) -> Option<CursorTexture> {
+    // Themes ship bigger images for HiDPI, pick the one the output shows unscaled
+    let wanted = (cursor_size as f64 * output_scale).round() as u32;
+    let cursor = match Cursor::load(&CursorTheme::load(theme), icon, wanted) {
+        Ok(cursor) => cursor,
+        Err(err) => {
+            tracing::debug!("No {} cursor in theme {theme}: {err}", icon.name());
+            return None;
+        }
+    };
+    upload_cursor(renderer, &cursor.frames, cursor_size, output_scale)
+}
+
+/// Uploads the frames of a cursor with a buffer scale bringing them close to
+/// `cursor_size` logical pixels.
+fn upload_cursor(
+    renderer: &mut GlesRenderer,
+    images: &[Image],
+    cursor_size: u32,
+    output_scale: f64,
+) -> Option<CursorTexture> {
+    let mut frames = Vec::with_capacity(images.len());
+    let mut delays = Vec::with_capacity(images.len());
+    for image in images {
+        let buffer_scale = (image.size as f64 / cursor_size as f64).round().max(1.0) as i32;
+        let texture = TextureBuffer::from_memory(
+            renderer,
+            &image.pixels_rgba,
+            Fourcc::Abgr8888,
+            (image.width as i32, image.height as i32),
+            false,
+            buffer_scale,
+            Transform::Normal,
+            None,
+        )
+        .ok()?;
+        let hotspot = Point::<f64, Logical>::from((
+            image.xhot as f64 / buffer_scale as f64,
+            image.yhot as f64 / buffer_scale as f64,
+        ))
+        .to_physical_precise_round(output_scale);
+        frames.push((texture, hotspot));
+        delays.push(image.delay);
+    }
+    (!frames.is_empty()).then_some(CursorTexture { frames, delays })
 }
 
 fn parse_transform(s: String) -> Option<Transform> {
@@ -364,6 +699,32 @@ fn parse_transform(s: String) -> Option<Transform> {
         "flipped-90" | "flipped90" => Some(Transform::Flipped90),
         "flipped-180" | "flipped180" => Some(Transform::Flipped180),
         "flipped-270" | "flipped270" => Some(Transform::Flipped270),
-        _ => None,
+        _ => {
+            tracing::warn!("Unknown output transform {s:?}, keeping the current one");
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_utils::output;
+
+    #[test]
+    fn outputs_do_not_overlap() {
+        let mut space = Space::<Window>::default();
+        let configured = output("DP-1", (3840, 2160), 2, (0, 0));
+        space.map_output(&configured, (0, 0));
+
+        let other = output("HDMI-A-1", (1920, 1080), 1, (0, 0));
+        let position = free_output_position(&space);
+        assert_eq!(position, Point::from((1920, 0)));
+        space.map_output(&other, position);
+
+        let first = space.output_geometry(&configured).unwrap();
+        let second = space.output_geometry(&other).unwrap();
+        assert!(!first.overlaps(second));
+        assert_eq!(free_output_position(&space), Point::from((3840, 0)));
     }
 }
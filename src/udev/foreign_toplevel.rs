@@ -0,0 +1,85 @@
+use std::cell::RefCell;
+
+use smithay::desktop::Window;
+
+use crate::{
+    delegate_foreign_toplevel,
+    protocols::foreign_toplevel::{
+        ForeignToplevelHandler, ForeignToplevelManagerState, ToplevelInfo,
+    },
+    state::State,
+    utils::{
+        action::close_window,
+        workspaces::{window_app_id, window_id, window_output, window_title, WindowMode},
+    },
+};
+
+impl State {
+    /// Brings the taskbars up to date with the windows of all workspaces:
+    /// new and closed windows, titles, focus and fullscreen.
+    pub fn refresh_foreign_toplevels(&mut self) {
+        let active_ws = self.workspaces.active_ws();
+        let mut ids = Vec::new();
+        for (index, ws) in self.workspaces.workspaces.iter().enumerate() {
+            for window in ws.space.elements() {
+                let id = window_id(window);
+                ids.push(id);
+                let info = ToplevelInfo {
+                    title: window_title(window),
+                    app_id: window_app_id(window),
+                    activated: index == active_ws && ws.active_window.as_ref() == Some(window),
+                    fullscreen: window
+                        .user_data()
+                        .get::<RefCell<WindowMode>>()
+                        .is_some_and(|mode| matches!(*mode.borrow(), WindowMode::Fullscreen(_))),
+                    output: window_output(&ws.space, window),
+                };
+                self.foreign_toplevel_state
+                    .update::<Self>(&self.display_handle, id, info);
+            }
+        }
+        self.foreign_toplevel_state.retain(|id| ids.contains(&id));
+    }
+
+    /// The workspace holding the window with `id`, and the window.
+    fn window_by_id(&self, id: u64) -> Option<(usize, Window)> {
+        self.workspaces
+            .workspaces
+            .iter()
+            .enumerate()
+            .find_map(|(index, ws)| {
+                ws.space
+                    .elements()
+                    .find(|window| window_id(window) == id)
+                    .map(|window| (index, window.clone()))
+            })
+    }
+}
+
+impl ForeignToplevelHandler for State {
+    fn foreign_toplevel_manager_state(&mut self) -> &mut ForeignToplevelManagerState {
+        &mut self.foreign_toplevel_state
+    }
+
+    fn activate(&mut self, id: u64) {
+        if self.is_locked() {
+            return;
+        }
+        let Some((index, window)) = self.window_by_id(id) else {
+            return;
+        };
+        if index != self.workspaces.active_ws() {
+            self.switch_workspace(index);
+            self.refresh_layout();
+        }
+        self.focus_window(&window);
+    }
+
+    fn close(&mut self, id: u64) {
+        if let Some((_, window)) = self.window_by_id(id) {
+            close_window(&window);
+        }
+    }
+}
+
+delegate_foreign_toplevel!(State);
@@ -1,9 +1,23 @@
 mod device;
+mod foreign_toplevel;
+mod output_power;
+mod screencopy;
 mod surface;
 
-use std::{collections::HashMap, io, path::PathBuf, time::Duration};
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+    path::PathBuf,
+    time::Duration,
+};
+
+use serde::Serialize;
 
-use crate::{state::State, udev::device::Device};
+use crate::{
+    state::State,
+    udev::device::Device,
+    utils::{config::XwaylandMode, ipc::init_ipc},
+};
 use smithay::{
     backend::{
         allocator::dmabuf::Dmabuf,
@@ -12,6 +26,7 @@ use smithay::{
         renderer::{
             gles::GlesRenderer,
             multigpu::{gbm::GbmGlesBackend, GpuManager},
+            utils::with_renderer_surface_state,
             ImportDma, ImportEgl,
         },
         session::{libseat::LibSeatSession, Event as SessionEvent, Session},
@@ -22,6 +37,7 @@ use smithay::{
     desktop::{layer_map_for_output, space::SpaceElement},
     reexports::{
         calloop::EventLoop,
+        drm::control::{connector, crtc},
         input::Libinput,
         wayland_server::{protocol::wl_surface, Display},
     },
@@ -37,6 +53,8 @@ pub struct UdevData {
     gpus: GpuManager<GbmGlesBackend<GlesRenderer, DrmDeviceFd>>,
     devices: HashMap<DrmNode, Device>,
     dmabuf_state: Option<(DmabufState, DmabufGlobal)>,
+    /// Connected outputs that are turned off, by name.
+    disabled_outputs: HashMap<String, (DrmNode, crtc::Handle, connector::Info)>,
 }
 
 impl DmabufHandler for State {
@@ -66,10 +84,25 @@ impl DmabufHandler for State {
 delegate_dmabuf!(State);
 
 impl UdevData {
-    pub fn early_import(&mut self, surface: &wl_surface::WlSurface) {
+    /// Imports the buffer `surface` committed into the renderers of every gpu,
+    /// returns how many copies to secondary gpus that took.
+    pub fn early_import(&mut self, surface: &wl_surface::WlSurface) -> u64 {
         if let Err(err) = self.gpus.early_import(self.primary_gpu, surface) {
             tracing::warn!("Early buffer import failed: {}", err);
+            return 0;
+        }
+        let has_buffer =
+            with_renderer_surface_state(surface, |state| state.buffer().is_some()).unwrap_or(false);
+        if !has_buffer {
+            return 0;
         }
+        // Clients render on the primary gpu, the others get a copy
+        self.devices
+            .values()
+            .map(|device| device.render_node)
+            .filter(|node| *node != self.primary_gpu)
+            .collect::<HashSet<_>>()
+            .len() as u64
     }
 }
 
@@ -102,6 +135,7 @@ pub fn init_udev() {
         gpus,
         devices: HashMap::new(),
         dmabuf_state: None,
+        disabled_outputs: HashMap::new(),
     };
 
     /*
@@ -177,6 +211,8 @@ pub fn init_udev() {
                         });
                     }
                 }
+                // Coming back counts as activity, and wakes outputs blanked before
+                data.notify_activity();
             }
         })
         .unwrap();
@@ -230,25 +266,41 @@ pub fn init_udev() {
     unsafe {
         std::env::set_var("WAYLAND_DISPLAY", &state.socket_name);
     }
+    state.apply_cursor_config();
+
+    init_ipc(&mut state);
 
     /*
      * Start XWayland if supported
      */
 
-    #[cfg(feature = "xwayland")]
-    state.start_xwayland();
+    match state.config.xwayland {
+        XwaylandMode::Eager => {
+            #[cfg(feature = "xwayland")]
+            state.start_xwayland();
+        }
+        XwaylandMode::Lazy => {
+            tracing::warn!("Lazy Xwayland startup is not supported, starting it now");
+            #[cfg(feature = "xwayland")]
+            state.start_xwayland();
+        }
+        XwaylandMode::Disabled => {
+            // Keep X11 clients from finding a display of the parent session
+            // SAFETY: All set_vars occur on the event loop thread
+            unsafe {
+                std::env::remove_var("DISPLAY");
+            }
+        }
+    }
 
     /*
      * And run our loop
      */
 
     for program in autostart {
-        std::process::Command::new("/bin/sh")
-            .arg("-c")
-            .arg(&program)
-            .spawn()
-            .map_err(|e| tracing::info!("Failed to spawn '{program}': {e}"))
-            .ok();
+        if let Some(child) = state.spawn(&program) {
+            state.autostart_children.push(child);
+        }
     }
 
     event_loop
@@ -257,26 +309,23 @@ pub fn init_udev() {
                 ws.space.elements().for_each(|e| e.refresh());
             }
 
-            let output = data
-                .workspaces
-                .get_current()
-                .space
-                .outputs()
-                .next()
-                .unwrap();
-            for layer in layer_map_for_output(output).layers() {
-                layer.send_frame(
-                    output,
-                    data.start_time.elapsed(),
-                    Some(Duration::ZERO),
-                    |_, _| Some(output.clone()),
-                );
+            for output in data.workspaces.get_current().space.outputs() {
+                for layer in layer_map_for_output(output).layers() {
+                    layer.send_frame(
+                        output,
+                        data.start_time.elapsed(),
+                        Some(Duration::ZERO),
+                        |_, _| Some(output.clone()),
+                    );
+                }
             }
 
+            data.refresh_foreign_toplevels();
             data.display_handle.flush_clients().unwrap();
             data.popup_manager.cleanup();
         })
         .unwrap();
+    state.shutdown();
 }
 
 // Udev
@@ -335,19 +384,95 @@ impl State {
             DrmScanEvent::Disconnected {
                 crtc: Some(crtc), ..
             } => {
-                let device = if let Some(device) = self.backend_data.devices.get_mut(&node) {
-                    device
-                } else {
-                    tracing::error!("Received connector event for unknown device: {:?}", node);
-                    return;
-                };
-                device.surfaces.remove(&crtc);
+                self.backend_data
+                    .disabled_outputs
+                    .retain(|_, (n, c, _)| !(*n == node && *c == crtc));
+                self.disconnected(node, crtc);
             }
             _ => {}
         }
     }
 }
 
+/// A gpu as listed by the `gpus` ipc command.
+#[derive(Serialize)]
+pub struct GpuInfo {
+    pub device: String,
+    pub render_node: String,
+    pub outputs: Vec<String>,
+    pub primary: bool,
+}
+
+impl State {
+    /// Every gpu with the outputs it drives, sorted by device.
+    pub fn list_gpus(&self) -> Vec<GpuInfo> {
+        let mut devices: Vec<_> = self.backend_data.devices.iter().collect();
+        devices.sort_by_key(|(node, _)| node.minor());
+        devices
+            .into_iter()
+            .map(|(node, device)| {
+                let mut outputs: Vec<String> = device
+                    .surfaces
+                    .values()
+                    .map(|surface| surface.output.name())
+                    .collect();
+                outputs.sort();
+                GpuInfo {
+                    device: node.to_string(),
+                    render_node: device.render_node.to_string(),
+                    outputs,
+                    primary: device.render_node == self.backend_data.primary_gpu,
+                }
+            })
+            .collect()
+    }
+
+    /// Makes the gpu with device or render node `name` (e.g. `renderD129`) the
+    /// one clients render on. The dmabuf global is recreated with its formats,
+    /// clients bound to the old one pick the new one up when they rebind.
+    pub fn set_primary_gpu(&mut self, name: &str) -> Result<(), String> {
+        let render_node = self
+            .backend_data
+            .devices
+            .iter()
+            .find(|(node, device)| {
+                node.to_string() == name || device.render_node.to_string() == name
+            })
+            .map(|(_, device)| device.render_node)
+            .ok_or_else(|| format!("no gpu named {name}"))?;
+        if render_node == self.backend_data.primary_gpu {
+            return Ok(());
+        }
+
+        let formats = self
+            .backend_data
+            .gpus
+            .single_renderer(&render_node)
+            .map_err(|err| err.to_string())?
+            .dmabuf_formats()
+            .into_iter()
+            .collect::<Vec<_>>();
+        let feedback = DmabufFeedbackBuilder::new(render_node.dev_id(), formats)
+            .build()
+            .map_err(|err| err.to_string())?;
+        if let Some((mut dmabuf_state, global)) = self.backend_data.dmabuf_state.take() {
+            dmabuf_state.destroy_global::<State>(&self.display_handle, global);
+            let global = dmabuf_state
+                .create_global_with_default_feedback::<State>(&self.display_handle, &feedback);
+            self.backend_data.dmabuf_state = Some((dmabuf_state, global));
+        }
+
+        tracing::info!(
+            "Switching primary gpu from {} to {}",
+            self.backend_data.primary_gpu,
+            render_node
+        );
+        self.backend_data.primary_gpu = render_node;
+        self.redraw_all();
+        Ok(())
+    }
+}
+
 pub fn primary_gpu(seat: &str) -> (DrmNode, PathBuf) {
     // TODO: can't this be in smithay?
     // primary_gpu() does the same thing anyway just without `NodeType::Render` check
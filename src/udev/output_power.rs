@@ -0,0 +1,28 @@
+use smithay::output::Output;
+
+use crate::{
+    delegate_output_power,
+    protocols::output_power::{OutputPowerHandler, OutputPowerManagerState},
+    state::State,
+};
+
+impl OutputPowerHandler for State {
+    fn output_power_manager_state(&mut self) -> &mut OutputPowerManagerState {
+        &mut self.output_power_state
+    }
+
+    fn output_power(&mut self, output: &Output) -> Option<bool> {
+        self.backend_data
+            .devices
+            .values()
+            .flat_map(|device| device.surfaces.values())
+            .find(|surface| surface.output == *output)
+            .map(|surface| surface.powered)
+    }
+
+    fn set_output_power_mode(&mut self, output: &Output, on: bool) -> bool {
+        self.power_outputs(Some(output), on)
+    }
+}
+
+delegate_output_power!(State);
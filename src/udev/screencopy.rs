@@ -0,0 +1,139 @@
+use std::time::Duration;
+
+use smithay::{
+    backend::{
+        allocator::Fourcc,
+        renderer::{
+            damage::OutputDamageTracker,
+            element::{Element, Kind},
+            gles::GlesTexture,
+            Bind, ExportMem, Offscreen,
+        },
+    },
+    output::Output,
+    utils::{Physical, Rectangle},
+    wayland::shm::with_buffer_contents_mut,
+};
+
+use crate::{
+    delegate_screencopy,
+    protocols::screencopy::{Screencopy, ScreencopyHandler},
+    state::State,
+    udev::surface::Surface,
+    utils::render::{CustomRenderElements, GlMultiRenderer},
+};
+
+impl ScreencopyHandler for State {
+    fn frame(&mut self, screencopy: Screencopy) {
+        // Captures of outputs without a surface are dropped, which fails them
+        if let Some(surface) = self
+            .backend_data
+            .devices
+            .values_mut()
+            .flat_map(|device| device.surfaces.values_mut())
+            .find(|surface| surface.output == *screencopy.output())
+        {
+            surface.screencopies.push(screencopy);
+        }
+    }
+}
+
+delegate_screencopy!(State);
+
+/// Copies `elements`, the frame about to be shown on `surface`, into the
+/// pending captures. Captures waiting for damage stay pending while their
+/// region didn't change.
+pub fn render_screencopies(
+    renderer: &mut GlMultiRenderer<'_>,
+    surface: &mut Surface,
+    elements: &[CustomRenderElements<GlMultiRenderer<'_>>],
+    clear_color: [f32; 4],
+    presented: Duration,
+) {
+    // Damage since the previous capture of this output
+    let damage = match surface.screencopy_damage.damage_output(1, elements) {
+        Ok((damage, _)) => damage.cloned().unwrap_or_default(),
+        Err(err) => {
+            tracing::warn!(?err, "Failed to track screencopy damage");
+            return;
+        }
+    };
+
+    for screencopy in std::mem::take(&mut surface.screencopies) {
+        let region = screencopy.region();
+        let damage: Vec<Rectangle<i32, Physical>> = damage
+            .iter()
+            .filter_map(|rect| rect.intersection(region))
+            .map(|rect| Rectangle::new(rect.loc - region.loc, rect.size))
+            .collect();
+        if screencopy.with_damage() && damage.is_empty() {
+            surface.screencopies.push(screencopy);
+            continue;
+        }
+
+        match copy_elements(
+            renderer,
+            &surface.output,
+            elements,
+            &screencopy,
+            clear_color,
+        ) {
+            Ok(()) => {
+                screencopy.damage(&damage);
+                screencopy.submit(false, presented);
+            }
+            Err(err) => {
+                tracing::warn!("Screencopy of {} failed: {}", surface.output.name(), err);
+            }
+        }
+    }
+}
+
+/// Renders `elements` offscreen and copies the captured region into the client buffer.
+fn copy_elements(
+    renderer: &mut GlMultiRenderer<'_>,
+    output: &Output,
+    elements: &[CustomRenderElements<GlMultiRenderer<'_>>],
+    screencopy: &Screencopy,
+    clear_color: [f32; 4],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mode = output.current_mode().ok_or("output has no mode")?;
+    let elements: Vec<_> = elements
+        .iter()
+        .filter(|elem| screencopy.overlay_cursor() || elem.kind() != Kind::Cursor)
+        .collect();
+
+    let mut texture: GlesTexture =
+        renderer.create_buffer(Fourcc::Abgr8888, (mode.size.w, mode.size.h).into())?;
+    let mut framebuffer = renderer.bind(&mut texture)?;
+    OutputDamageTracker::from_output(output).render_output(
+        renderer,
+        &mut framebuffer,
+        0,
+        &elements,
+        clear_color,
+    )?;
+
+    let region = screencopy.region();
+    let mapping = renderer.copy_framebuffer(
+        &framebuffer,
+        Rectangle::new(
+            (region.loc.x, region.loc.y).into(),
+            (region.size.w, region.size.h).into(),
+        ),
+        Fourcc::Argb8888,
+    )?;
+    let pixels = renderer.map_texture(&mapping)?;
+
+    with_buffer_contents_mut(screencopy.buffer(), |ptr, len, data| {
+        let offset = data.offset as usize;
+        let size = (data.stride * data.height) as usize;
+        if offset + size > len || pixels.len() < size {
+            return Err("buffer too small");
+        }
+        // SAFETY: the range was checked against the pool size above
+        unsafe { std::ptr::copy_nonoverlapping(pixels.as_ptr(), ptr.add(offset), size) };
+        Ok(())
+    })??;
+    Ok(())
+}
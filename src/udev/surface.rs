@@ -1,10 +1,36 @@
-use std::{io, time::Duration};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    hash::{DefaultHasher, Hash, Hasher},
+    io,
+    time::{Duration, Instant},
+};
 
 use crate::{
+    protocols::screencopy::Screencopy,
     state::State,
+    udev::{
+        device::{load_named_cursor_texture, load_pointer_texture, CursorTexture},
+        screencopy::render_screencopies,
+    },
     utils::{
-        render::{border::BorderShader, CustomRenderElements},
-        workspaces::is_fullscreen,
+        autohide::is_hidden,
+        config::{Config, FullscreenBehavior},
+        layout::LayoutState,
+        render::{
+            blur::{BlurCache, BlurElement},
+            border::{hex_to_rgb, Bands, BorderShader},
+            dim::DimShader,
+            rounded::RoundedElement,
+            text::{text_texture, text_width, LINE_HEIGHT},
+            CustomRenderElements, GlMultiRenderer,
+        },
+        titlebar::{
+            button_geometry, has_titlebar, titlebar_geometry, TitlebarHit, TITLEBAR_HEIGHT,
+        },
+        workspaces::{
+            is_fullscreen, is_unresponsive, window_app_id, window_id, window_title, WindowMode,
+        },
     },
 };
 use smithay::{
@@ -17,27 +43,50 @@ use smithay::{
             DrmAccessError, DrmDeviceFd, DrmError, DrmNode,
         },
         renderer::{
+            damage::OutputDamageTracker,
             element::{
-                surface::WaylandSurfaceRenderElement,
+                default_primary_scanout_output_compare,
+                surface::{render_elements_from_surface_tree, WaylandSurfaceRenderElement},
                 texture::{TextureBuffer, TextureRenderElement},
-                AsRenderElements, Kind,
+                AsRenderElements, Element, Kind, RenderElementStates,
             },
-            gles::GlesTexture,
+            gles::{GlesRenderer, GlesTexture},
             multigpu::MultiRenderer,
         },
         SwapBuffersError,
     },
-    desktop::{layer_map_for_output, utils::OutputPresentationFeedback},
+    desktop::{
+        layer_map_for_output,
+        utils::{
+            send_frames_surface_tree, update_surface_primary_scanout_output,
+            with_surfaces_surface_tree, OutputPresentationFeedback,
+        },
+        PopupManager, Window,
+    },
+    input::pointer::{CursorIcon, CursorImageStatus, CursorImageSurfaceData},
     output::Output,
     reexports::{
-        calloop::timer::{TimeoutAction, Timer},
-        drm::control::crtc,
-        wayland_server::backend::GlobalId,
+        calloop::{
+            timer::{TimeoutAction, Timer},
+            RegistrationToken,
+        },
+        drm::control::{connector, crtc},
+        wayland_server::{backend::GlobalId, protocol::wl_surface::WlSurface},
+    },
+    utils::{IsAlive, Logical, Physical, Point, Rectangle, Scale, Size, Transform},
+    wayland::{
+        compositor::{send_surface_state, with_states, SurfaceData},
+        content_type::{ContentType, ContentTypeSurfaceCachedState},
+        fractional_scale::with_fractional_scale,
+        seat::WaylandFocus,
+        shell::wlr_layer::Layer,
     },
-    utils::Scale,
-    wayland::shell::wlr_layer::Layer,
 };
 
+const UNRESPONSIVE_COLOR: &str = "#CC0000";
+const LOCKED_BACKGROUND: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
+const OVERLAY_WIDTH: i32 = 480;
+
 pub struct Surface {
     pub _device_id: DrmNode,
     pub _render_node: DrmNode,
@@ -49,13 +98,121 @@ pub struct Surface {
         DrmDeviceFd,
     >,
     pub output: Output,
-    pub pointer_texture: TextureBuffer<GlesTexture>,
+    pub connector: connector::Handle,
+    /// Built-in cursor, for themes lacking even a default cursor.
+    pub pointer_texture: CursorTexture,
+    /// Theme cursors, loaded on first use. None when the theme lacks the shape.
+    pub cursor_textures: HashMap<CursorIcon, Option<CursorTexture>>,
+    /// Output scale the cursors were uploaded for.
+    pub pointer_scale: f64,
+    pub last_frame: Option<Instant>,
+    pub tab_textures: HashMap<(String, i32, bool), TextureBuffer<GlesTexture>>,
+    pub mode_warned: bool,
+    pub last_queued: Option<Instant>,
+    /// Timer of a deferred repaint, replaced by the next one scheduled.
+    pub frame_timer: Option<RegistrationToken>,
+    /// False while the output is DPMS-off.
+    pub powered: bool,
+    /// Backdrop copies of blurred windows, by window id.
+    pub blur_textures: HashMap<u64, BlurCache>,
+    /// Captures waiting for the next frame, they fail when dropped.
+    pub screencopies: Vec<Screencopy>,
+    pub screencopy_damage: OutputDamageTracker,
+}
+
+impl Surface {
+    /// The frame of the theme cursor for `icon` shown at `time`, None when the
+    /// theme lacks it.
+    fn theme_cursor(
+        &mut self,
+        renderer: &mut GlesRenderer,
+        config: &Config,
+        icon: CursorIcon,
+        time: Duration,
+    ) -> Option<(TextureBuffer<GlesTexture>, Point<i32, Physical>)> {
+        let output_scale = self.pointer_scale;
+        self.cursor_textures
+            .entry(icon)
+            .or_insert_with(|| {
+                load_named_cursor_texture(
+                    renderer,
+                    &config.cursor_theme(),
+                    config.cursor_size(),
+                    icon,
+                    output_scale,
+                )
+            })
+            .as_ref()
+            .map(|cursor| cursor.frame(time))
+    }
 }
 
 impl State {
     pub fn render(&mut self, node: DrmNode, crtc: crtc::Handle) -> Result<bool, SwapBuffersError> {
-        let device = self.backend_data.devices.get_mut(&node).unwrap();
-        let surface = device.surfaces.get_mut(&crtc).unwrap();
+        // Timers and idle callbacks can outlive the output they were queued for
+        let Some(device) = self.backend_data.devices.get_mut(&node) else {
+            return Ok(false);
+        };
+        let Some(surface) = device.surfaces.get_mut(&crtc) else {
+            return Ok(false);
+        };
+
+        // Nothing sensible can be rendered (or scheduled) without a mode
+        if surface.output.current_mode().is_none() {
+            if !surface.mode_warned {
+                tracing::warn!(
+                    "Output {} has no current mode, skipping render",
+                    surface.output.name()
+                );
+                surface.mode_warned = true;
+            }
+            return Ok(false);
+        }
+
+        // Powered-off outputs stay idle until set_output_power wakes them,
+        // their captures would never complete
+        if !surface.powered {
+            surface.screencopies.clear();
+            return Ok(false);
+        }
+
+        // This render supersedes a deferred one, so timers don't pile up
+        if let Some(token) = surface.frame_timer.take() {
+            self.loop_handle.remove(token);
+        }
+
+        // Respect the per-output fps cap by deferring the repaint
+        let min_frame_duration = self
+            .config
+            .outputs
+            .get(&surface.output.name())
+            .and_then(|data| data.max_fps)
+            .filter(|fps| *fps > 0)
+            .map(|fps| Duration::from_secs_f64(1.0 / fps as f64));
+        if let (Some(min_frame_duration), Some(last_queued)) =
+            (min_frame_duration, surface.last_queued)
+        {
+            let elapsed = last_queued.elapsed();
+            if elapsed < min_frame_duration {
+                self.render_stats.capped();
+                let timer = Timer::from_duration(min_frame_duration - elapsed);
+                let token = self
+                    .loop_handle
+                    .insert_source(timer, move |_, _, data| {
+                        data.render(node, crtc).ok();
+                        TimeoutAction::Drop
+                    })
+                    .expect("failed to schedule frame timer");
+                surface.frame_timer = Some(token);
+                return Ok(false);
+            }
+        }
+
+        let frame_start = Instant::now();
+        let frame_time = surface
+            .last_frame
+            .replace(frame_start)
+            .map(|last| frame_start - last);
 
         let mut renderer = self
             .backend_data
@@ -63,28 +220,153 @@ impl State {
             .single_renderer(&device.render_node)
             .unwrap();
 
+        if self
+            .workspace_slide
+            .as_ref()
+            .is_some_and(|slide| slide.done())
+        {
+            self.workspace_slide = None;
+        }
+
+        // Everything below is laid out relative to the output of this crtc
         let ws = self.workspaces.get_current();
-        let output = ws.space.outputs().next().unwrap();
+        let output = &surface.output.clone();
+        let output_geo = ws.space.output_geometry(output).unwrap_or_default();
+        let origin = output_geo.loc;
+        let on_output = |window: &Window| {
+            ws.space
+                .element_geometry(window)
+                .is_some_and(|geo| geo.overlaps(output_geo))
+        };
         let scale = Scale::from(output.current_scale().fractional_scale());
+        // A locked session shows its lock surface on black, and nothing else
+        let locked = self.is_locked();
+        let background = if locked {
+            LOCKED_BACKGROUND
+        } else {
+            self.config
+                .workspace_background(self.workspaces.active_ws())
+        };
+        let pointer_location = self.pointer_location - origin.to_f64();
+        let pointer_here = output_geo.to_f64().contains(self.pointer_location);
+
+        // Redraw cursors and text when the output scale changed since they were uploaded
+        let output_scale = surface.output.current_scale().fractional_scale();
+        if surface.pointer_scale != output_scale {
+            surface.pointer_texture =
+                load_pointer_texture(renderer.as_mut(), self.config.cursor_size(), output_scale);
+            surface.cursor_textures.clear();
+            surface.tab_textures.clear();
+            surface.pointer_scale = output_scale;
+        }
 
         // ------------------------------------------------------------
         // Render element collection (NO allocations inside loops)
         // ------------------------------------------------------------
         let mut elements: Vec<CustomRenderElements<_>> = Vec::with_capacity(128);
+        // Windows of the blur elements, in element order
+        let mut blurred = Vec::new();
 
         // ------------------------------------------------------------
         // Cursor
         // ------------------------------------------------------------
-        elements.push(CustomRenderElements::from(
-            TextureRenderElement::from_texture_buffer(
-                self.pointer_location.to_physical(scale),
-                &surface.pointer_texture,
-                None,
-                None,
-                None,
-                Kind::Cursor,
-            ),
-        ));
+        let drag = self.drag.as_ref().filter(|drag| drag.shown);
+        if let CursorImageStatus::Surface(cursor) = &self.cursor_status {
+            if !cursor.alive() {
+                self.cursor_status = CursorImageStatus::default_named();
+            }
+        }
+        // Animated cursors step through their frames as the render loop runs
+        let cursor_time = self.start_time.elapsed();
+        let grab_texture = drag.and_then(|_| {
+            surface.theme_cursor(
+                renderer.as_mut(),
+                &self.config,
+                CursorIcon::Grabbing,
+                cursor_time,
+            )
+        });
+        match (&self.cursor_status, grab_texture) {
+            (_, Some((texture, hotspot))) => {
+                elements.push(CustomRenderElements::from(
+                    TextureRenderElement::from_texture_buffer(
+                        pointer_location.to_physical(scale) - hotspot.to_f64(),
+                        &texture,
+                        None,
+                        None,
+                        None,
+                        Kind::Cursor,
+                    ),
+                ));
+            }
+            (CursorImageStatus::Hidden, _) => {}
+            (CursorImageStatus::Surface(cursor), _) => {
+                let hotspot = with_states(cursor, |states| {
+                    states
+                        .data_map
+                        .get::<CursorImageSurfaceData>()
+                        .map(|data| data.lock().unwrap().hotspot)
+                        .unwrap_or_default()
+                });
+                let location = pointer_location.to_i32_round() - hotspot;
+                elements.extend(
+                    render_elements_from_surface_tree::<_, WaylandSurfaceRenderElement<_>>(
+                        &mut renderer,
+                        cursor,
+                        location.to_physical_precise_round(scale),
+                        scale,
+                        1.0,
+                        Kind::Cursor,
+                    )
+                    .into_iter()
+                    .map(CustomRenderElements::Window),
+                );
+            }
+            (CursorImageStatus::Named(icon), _) => {
+                let (texture, hotspot) = surface
+                    .theme_cursor(renderer.as_mut(), &self.config, *icon, cursor_time)
+                    .or_else(|| {
+                        surface.theme_cursor(
+                            renderer.as_mut(),
+                            &self.config,
+                            CursorIcon::Default,
+                            cursor_time,
+                        )
+                    })
+                    .unwrap_or_else(|| surface.pointer_texture.frame(cursor_time));
+                elements.push(CustomRenderElements::from(
+                    TextureRenderElement::from_texture_buffer(
+                        pointer_location.to_physical(scale) - hotspot.to_f64(),
+                        &texture,
+                        None,
+                        None,
+                        None,
+                        Kind::Cursor,
+                    ),
+                ));
+            }
+        }
+        // A hidden cursor, or one without a buffer, pushes nothing
+        let cursor_elements = elements.len();
+
+        // Drag icon, right below the cursor
+        if let Some((icon, offset)) =
+            drag.and_then(|drag| Some((drag.icon.as_ref()?, drag.icon_offset)))
+        {
+            let location = pointer_location.to_i32_round() + offset;
+            elements.extend(
+                render_elements_from_surface_tree::<_, WaylandSurfaceRenderElement<_>>(
+                    &mut renderer,
+                    icon,
+                    location.to_physical_precise_round(scale),
+                    scale,
+                    1.0,
+                    Kind::Unspecified,
+                )
+                .into_iter()
+                .map(CustomRenderElements::Window),
+            );
+        }
 
         // ------------------------------------------------------------
         // Layer surfaces (TOP → BOTTOM, no Vec partition)
@@ -92,7 +374,10 @@ impl State {
         let layer_map = layer_map_for_output(output);
 
         for layer_surface in layer_map.layers().rev() {
-            if matches!(layer_surface.layer(), Layer::Background | Layer::Bottom) {
+            if locked
+                || matches!(layer_surface.layer(), Layer::Background | Layer::Bottom)
+                || is_hidden(layer_surface)
+            {
                 continue;
             }
 
@@ -116,10 +401,100 @@ impl State {
         // ------------------------------------------------------------
         let border = &self.config.border;
         let active = ws.active_window.as_ref();
-        let fullscreen = is_fullscreen(ws.space.elements());
+        let fullscreen = is_fullscreen(ws.space.elements().filter(|window| on_output(window)));
 
-        if let Some(win) = fullscreen {
-            let loc = ws.space.element_location(win).unwrap();
+        let mut frame_flags = FrameFlags::DEFAULT;
+        if locked {
+            if let Some(lock_surface) = self
+                .session_lock
+                .as_ref()
+                .and_then(|lock| lock.surface(output))
+            {
+                elements.extend(
+                    render_elements_from_surface_tree::<_, WaylandSurfaceRenderElement<_>>(
+                        &mut renderer,
+                        lock_surface.wl_surface(),
+                        Point::<i32, Physical>::default(),
+                        scale,
+                        1.0,
+                        Kind::Unspecified,
+                    )
+                    .into_iter()
+                    .map(CustomRenderElements::Window),
+                );
+            }
+        } else if let Some(slide) = &self.workspace_slide {
+            // Both workspaces' windows, shifted by the slide; decorations come back once it ends
+            let (from_shift, to_shift) =
+                slide.offsets(output_geo.size, self.config.workspace_animation.direction);
+            for (index, shift) in [
+                (self.workspaces.active_ws(), to_shift),
+                (slide.from, from_shift),
+            ] {
+                let workspace = &self.workspaces.workspaces[index];
+                let visible_tab = workspace.visible_tab();
+                for window in workspace.space.elements().rev() {
+                    if visible_tab.is_some()
+                        && Some(window) != visible_tab
+                        && window
+                            .user_data()
+                            .get::<RefCell<WindowMode>>()
+                            .is_some_and(|d| *d.borrow() == WindowMode::Tiled)
+                    {
+                        continue;
+                    }
+                    let Some(geo) = workspace.space.element_geometry(window) else {
+                        continue;
+                    };
+                    if !geo.overlaps(output_geo) {
+                        continue;
+                    }
+                    // Sticky windows stay put while the workspaces slide under them
+                    let shift = if self.workspaces.is_sticky(window) {
+                        Point::default()
+                    } else {
+                        shift
+                    };
+                    let loc = geo.loc + shift - window.geometry().loc - origin;
+                    for elem in window.render_elements(
+                        &mut renderer,
+                        loc.to_f64().to_physical(scale).to_i32_round(),
+                        scale,
+                        1.0,
+                    ) {
+                        elements.push(CustomRenderElements::Window(elem));
+                    }
+                }
+            }
+        } else if let Some(win) = fullscreen {
+            // Let video players and games go straight to the primary plane
+            if matches!(content_type(win), ContentType::Video | ContentType::Game) {
+                frame_flags |= FrameFlags::ALLOW_PRIMARY_PLANE_SCANOUT
+                    | FrameFlags::ALLOW_PRIMARY_PLANE_SCANOUT_ANY;
+            }
+            // Windows pinned on top of the fullscreen one, topmost first
+            if self.config.fullscreen_behavior == FullscreenBehavior::Pinned {
+                for window in ws.above_fullscreen(win).iter().rev() {
+                    if !on_output(window) {
+                        continue;
+                    }
+                    let loc =
+                        ws.space.element_location(window).unwrap() - window.geometry().loc - origin;
+                    let alpha = self
+                        .config
+                        .rule_opacity(&window_app_id(window), &window_title(window));
+                    for elem in window.render_elements(
+                        &mut renderer,
+                        loc.to_f64().to_physical(scale).to_i32_round(),
+                        scale,
+                        alpha,
+                    ) {
+                        elements.push(CustomRenderElements::Window(elem));
+                    }
+                }
+            }
+            // CSD shadows sit outside the window geometry, keep them off-screen
+            let loc = ws.space.element_location(win).unwrap() - win.geometry().loc - origin;
             for elem in win.render_elements(
                 &mut renderer,
                 loc.to_f64().to_physical(scale).to_i32_round(),
@@ -129,54 +504,278 @@ impl State {
                 elements.push(CustomRenderElements::Window(elem));
             }
         } else {
+            // The list is top-first: the dim goes right after the focused
+            // window's elements, or above all windows of outputs without it
+            let windows_start = elements.len();
+            let mut dim_at = None;
+
+            // The fullscreen branch above never draws borders either
+            let smart_tile = if self.config.smart_gaps || self.config.hide_border_single {
+                ws.single_visible_tile()
+            } else {
+                None
+            };
+
+            // Tab bar
+            let visible_tab = ws.visible_tab();
+            if let LayoutState::Tabbed(tabbed) = &ws.layout {
+                if surface.tab_textures.len() > 64 {
+                    surface.tab_textures.clear();
+                }
+                for (window, tab_geo) in tabbed.tabs() {
+                    if !tab_geo.overlaps(output_geo) {
+                        continue;
+                    }
+                    let is_visible = Some(window) == visible_tab;
+                    let key = (window_title(window), tab_geo.size.w, is_visible);
+                    let texture = surface.tab_textures.entry(key).or_insert_with_key(|key| {
+                        let color = if is_visible {
+                            &border.active
+                        } else {
+                            &border.inactive
+                        };
+                        let [r, g, b] = hex_to_rgb(color).unwrap_or([0.1, 0.1, 0.1]);
+                        let background =
+                            [(r * 255.0) as u8, (g * 255.0) as u8, (b * 255.0) as u8, 255];
+                        text_texture(
+                            renderer.as_mut(),
+                            &key.0,
+                            tab_geo.size.into(),
+                            scale.x,
+                            background,
+                            [255, 255, 255, 255],
+                        )
+                    });
+                    elements.push(CustomRenderElements::from(
+                        TextureRenderElement::from_texture_buffer(
+                            (tab_geo.loc - origin).to_f64().to_physical(scale),
+                            texture,
+                            None,
+                            None,
+                            Some(tab_geo.size),
+                            Kind::Unspecified,
+                        ),
+                    ));
+                }
+            }
+
             for window in ws.space.elements().rev() {
-                // Geometry cached once
-                let geo = ws.space.element_geometry(&window).unwrap();
-                let loc = ws.space.element_location(&window).unwrap();
+                // Hidden tabs are not rendered at all
+                if visible_tab.is_some()
+                    && Some(window) != visible_tab
+                    && window
+                        .user_data()
+                        .get::<RefCell<WindowMode>>()
+                        .map(|d| *d.borrow() == WindowMode::Tiled)
+                        .unwrap_or(false)
+                {
+                    continue;
+                }
+                if !on_output(window) {
+                    continue;
+                }
+
+                // Geometry cached once, relative to the output
+                let mut geo = ws.space.element_geometry(&window).unwrap();
+                geo.loc -= origin;
+                let loc = ws.space.element_location(&window).unwrap() - origin;
                 let win_geo = window.geometry();
 
                 // Window content
                 let offset = loc - win_geo.loc;
-                for elem in window.render_elements(
-                    &mut renderer,
-                    offset.to_f64().to_physical(scale).to_i32_round(),
-                    scale,
-                    1.0,
-                ) {
-                    elements.push(CustomRenderElements::Window(elem));
+                let rule_opacity = self
+                    .config
+                    .rule_opacity(&window_app_id(window), &window_title(window));
+                let alpha = if Some(window) == active {
+                    rule_opacity
+                } else {
+                    rule_opacity * self.config.inactive_opacity.clamp(0.0, 1.0)
+                };
+                let titlebar = self.config.titlebars && has_titlebar(window);
+                let location = offset.to_f64().to_physical(scale).to_i32_round();
+                if border.corner_radius > 0 {
+                    // The titlebar covers the top corners
+                    let mut clip = geo;
+                    if titlebar {
+                        clip.loc.y -= TITLEBAR_HEIGHT;
+                        clip.size.h += TITLEBAR_HEIGHT;
+                    }
+                    let output_size = output
+                        .current_mode()
+                        .map(|mode| output.current_transform().transform_size(mode.size))
+                        .unwrap_or_default();
+                    elements.extend(rounded_window_elements(
+                        &mut renderer,
+                        window,
+                        location,
+                        scale,
+                        alpha,
+                        clip.to_physical_precise_round(scale),
+                        output_size,
+                        output.current_transform(),
+                        border.corner_radius as f32 * scale.x as f32,
+                    ));
+                } else {
+                    for elem in window.render_elements(&mut renderer, location, scale, alpha) {
+                        elements.push(CustomRenderElements::Window(elem));
+                    }
                 }
 
-                // Border
-                let mut border_geo = geo;
-                border_geo.size += (border.thickness * 2, border.thickness * 2).into();
-                border_geo.loc -= (border.thickness, border.thickness).into();
+                // Blurred backdrop right below the window
+                if let Some(blur) = self
+                    .config
+                    .blur
+                    .as_ref()
+                    .filter(|blur| blur.app_ids.contains(&window_app_id(window)))
+                {
+                    if surface.blur_textures.len() > 64 {
+                        surface.blur_textures.clear();
+                    }
+                    let cache = surface.blur_textures.remove(&window_id(window));
+                    let output_size = output
+                        .current_mode()
+                        .map(|mode| output.current_transform().transform_size(mode.size))
+                        .unwrap_or_default();
+                    if let Some(elem) = BlurElement::new(
+                        renderer.as_mut(),
+                        cache,
+                        geo.to_physical_precise_round(scale),
+                        output_size,
+                        output.current_transform(),
+                        blur.radius,
+                    ) {
+                        blurred.push(window_id(window));
+                        elements.push(CustomRenderElements::Blur(elem));
+                    }
+                }
 
-                let (start, end) = if Some(window) == active {
-                    (
-                        border.active.clone(),
-                        border.end_active.clone().unwrap_or(border.active.clone()),
-                    )
-                } else {
-                    (
-                        border.inactive.clone(),
-                        border
-                            .end_inactive
+                // Titlebar of floating windows with server-side decorations
+                if titlebar {
+                    if surface.tab_textures.len() > 64 {
+                        surface.tab_textures.clear();
+                    }
+                    let bar = titlebar_geometry(geo);
+                    let is_active = Some(window) == active;
+                    let color = if is_active {
+                        &border.active
+                    } else {
+                        &border.inactive
+                    };
+                    let [r, g, b] = hex_to_rgb(color).unwrap_or([0.1, 0.1, 0.1]);
+                    let background = [(r * 255.0) as u8, (g * 255.0) as u8, (b * 255.0) as u8, 255];
+                    let parts = [
+                        (TitlebarHit::Close, "X".to_string()),
+                        (TitlebarHit::Maximize, "+".to_string()),
+                        (TitlebarHit::Bar, window_title(window)),
+                    ];
+                    for (part, text) in parts {
+                        let part_geo = match part {
+                            TitlebarHit::Bar => Rectangle::new(
+                                bar.loc,
+                                ((bar.size.w - TITLEBAR_HEIGHT * 2).max(1), bar.size.h).into(),
+                            ),
+                            button => button_geometry(bar, button),
+                        };
+                        let key = (text, part_geo.size.w, is_active);
+                        let texture = surface.tab_textures.entry(key).or_insert_with_key(|key| {
+                            text_texture(
+                                renderer.as_mut(),
+                                &key.0,
+                                part_geo.size.into(),
+                                scale.x,
+                                background,
+                                [255, 255, 255, 255],
+                            )
+                        });
+                        elements.push(CustomRenderElements::from(
+                            TextureRenderElement::from_texture_buffer(
+                                part_geo.loc.to_f64().to_physical(scale),
+                                texture,
+                                None,
+                                None,
+                                Some(part_geo.size),
+                                Kind::Unspecified,
+                            ),
+                        ));
+                    }
+                }
+
+                // Border, dropped by smart gaps for a lone tile
+                if Some(window) != smart_tile {
+                    let mut border_geo = geo;
+                    if titlebar {
+                        border_geo.loc.y -= TITLEBAR_HEIGHT;
+                        border_geo.size.h += TITLEBAR_HEIGHT;
+                    }
+                    border_geo.size += (border.thickness * 2, border.thickness * 2).into();
+                    border_geo.loc -= (border.thickness, border.thickness).into();
+
+                    let unresponsive = is_unresponsive(window);
+                    let (start, end) = if unresponsive {
+                        let color = border
+                            .unresponsive
                             .clone()
-                            .unwrap_or(border.inactive.clone()),
-                    )
-                };
+                            .unwrap_or(UNRESPONSIVE_COLOR.to_string());
+                        (color.clone(), color)
+                    } else if Some(window) == active {
+                        (
+                            border.active.clone(),
+                            border.end_active.clone().unwrap_or(border.active.clone()),
+                        )
+                    } else {
+                        (
+                            border.inactive.clone(),
+                            border
+                                .end_inactive
+                                .clone()
+                                .unwrap_or(border.inactive.clone()),
+                        )
+                    };
 
-                let border_elem = BorderShader::element(
-                    renderer.as_mut(),
-                    border_geo,
-                    1.0,
-                    border.angle.unwrap_or(0.0),
-                    &start,
-                    &end,
-                    border.thickness as f32,
-                );
+                    // The shader works in output pixels, the config in logical ones
+                    let px = scale.x as f32;
+                    let border_elem = BorderShader::element(
+                        renderer.as_mut(),
+                        border_geo,
+                        1.0,
+                        border.angle.unwrap_or(0.0),
+                        &start,
+                        &end,
+                        border.thickness as f32 * px,
+                        border.corner_radius as f32 * px,
+                        &if unresponsive {
+                            Bands::default()
+                        } else {
+                            Bands {
+                                inner: border.inner_color.as_deref(),
+                                outer: border.outer_color.as_deref(),
+                                outer_width: border
+                                    .outer_width
+                                    .map(|width| width as f32)
+                                    .unwrap_or(border.thickness as f32 / 2.0)
+                                    * px,
+                            }
+                        },
+                    );
+
+                    elements.push(CustomRenderElements::Shader(border_elem));
+                }
 
-                elements.push(CustomRenderElements::Shader(border_elem));
+                if Some(window) == active {
+                    dim_at = Some(elements.len());
+                }
+            }
+
+            // Dim everything below the focused window, but not the top layers
+            if let Some(dim) = self.config.dim_inactive.filter(|_| active.is_some()) {
+                elements.insert(
+                    dim_at.unwrap_or(windows_start),
+                    CustomRenderElements::Shader(DimShader::element(
+                        renderer.as_mut(),
+                        Rectangle::from_size(output_geo.size),
+                        dim.strength,
+                    )),
+                );
             }
         }
 
@@ -184,7 +783,10 @@ impl State {
         // Bottom layers
         // ------------------------------------------------------------
         for layer_surface in layer_map.layers().rev() {
-            if !matches!(layer_surface.layer(), Layer::Background | Layer::Bottom) {
+            if locked
+                || !matches!(layer_surface.layer(), Layer::Background | Layer::Bottom)
+                || is_hidden(layer_surface)
+            {
                 continue;
             }
 
@@ -203,14 +805,133 @@ impl State {
             }
         }
 
-        let frame_result: Result<RenderFrameResult<_, _, _>, SwapBuffersError> = surface
-            .drm_output
-            .render_frame::<_, _>(
+        // ------------------------------------------------------------
+        // Overlay (launcher and menus, right below the cursor)
+        // ------------------------------------------------------------
+        if let Some(overlay) = self.overlay.as_ref().filter(|_| pointer_here && !locked) {
+            let width = OVERLAY_WIDTH.min(output_geo.size.w);
+            let x = (output_geo.size.w - width) / 2;
+            let y = output_geo.size.h / 4;
+
+            for (index, (text, highlighted)) in overlay.lines().iter().enumerate() {
+                let background = if *highlighted {
+                    [139, 64, 0, 230]
+                } else {
+                    [20, 20, 20, 230]
+                };
+                let texture = text_texture(
+                    renderer.as_mut(),
+                    text,
+                    (width, LINE_HEIGHT),
+                    scale.x,
+                    background,
+                    [255, 255, 255, 255],
+                );
+                let loc: Point<i32, Logical> = (x, y + LINE_HEIGHT * index as i32).into();
+                elements.insert(
+                    cursor_elements + index,
+                    CustomRenderElements::from(TextureRenderElement::from_texture_buffer(
+                        loc.to_f64().to_physical(scale),
+                        &texture,
+                        None,
+                        None,
+                        Some((width, LINE_HEIGHT).into()),
+                        Kind::Unspecified,
+                    )),
+                );
+            }
+        }
+
+        // ------------------------------------------------------------
+        // Debug overlay (frame time, fps, element count)
+        // ------------------------------------------------------------
+        if self.debug_overlay {
+            let text = match frame_time {
+                Some(time) => format!(
+                    "{:.1}ms {:.0}fps {}el",
+                    time.as_secs_f64() * 1000.0,
+                    1.0 / time.as_secs_f64().max(f64::EPSILON),
+                    elements.len()
+                ),
+                None => format!("{}el", elements.len()),
+            };
+            let size = (text_width(&text), LINE_HEIGHT);
+            let texture = text_texture(
+                renderer.as_mut(),
+                &text,
+                size,
+                scale.x,
+                [0, 0, 0, 160],
+                [255, 255, 255, 255],
+            );
+            elements.insert(
+                cursor_elements,
+                CustomRenderElements::from(TextureRenderElement::from_texture_buffer(
+                    Point::<f64, Physical>::default(),
+                    &texture,
+                    None,
+                    None,
+                    Some(size.into()),
+                    Kind::Unspecified,
+                )),
+            );
+        }
+
+        // Blurred backdrops only redraw once something over their region changed
+        let scenes: Vec<u64> = elements
+            .iter()
+            .filter_map(|elem| match elem {
+                CustomRenderElements::Blur(blur) => Some(blur_scene(&elements, blur, scale)),
+                _ => None,
+            })
+            .collect();
+        let blurs = elements.iter_mut().filter_map(|elem| match elem {
+            CustomRenderElements::Blur(blur) => Some(blur),
+            _ => None,
+        });
+        for ((blur, scene), window) in blurs.zip(scenes).zip(blurred) {
+            blur.set_scene(scene);
+            surface.blur_textures.insert(window, blur.cache());
+        }
+
+        if !surface.screencopies.is_empty() {
+            render_screencopies(
                 &mut renderer,
+                surface,
                 &elements,
-                [0.1, 0.1, 0.1, 1.0],
-                FrameFlags::DEFAULT,
-            )
+                background,
+                Duration::from(self.clock.now()),
+            );
+        }
+
+        let output_name = surface.output.name();
+        let dump = self.dump_render_elements.contains(&output_name);
+        if dump || self.dump_every_frame {
+            tracing::info!("Render elements for {} (top to bottom):", output_name);
+            for (index, elem) in elements.iter().enumerate() {
+                let kind = match elem {
+                    CustomRenderElements::Texture(_) => "texture",
+                    CustomRenderElements::Window(_) => "surface",
+                    CustomRenderElements::Shader(_) => "shader",
+                    CustomRenderElements::Blur(_) => "blur",
+                    CustomRenderElements::Rounded(_) => "rounded surface",
+                };
+                tracing::info!(
+                    "  #{index} {kind} {:?} kind={:?} geometry={:?} alpha={}",
+                    elem.id(),
+                    elem.kind(),
+                    elem.geometry(scale),
+                    elem.alpha(),
+                );
+            }
+            // The action dumps each output once
+            self.dump_render_elements
+                .retain(|name| *name != output_name);
+        }
+
+        let frame_result: Result<RenderFrameResult<_, _, _>, SwapBuffersError> = surface
+            .drm_output
+            .render_frame::<_, _>(&mut renderer, &elements, background, frame_flags)
             .map_err(|err| match err {
                 smithay::backend::drm::compositor::RenderFrameError::PrepareFrame(err) => {
                     err.into()
@@ -221,8 +942,13 @@ impl State {
                 _ => unreachable!(),
             });
 
+        let mut render_states = None;
         let mut result = match frame_result {
-            Ok(frame_result) => Ok(!frame_result.is_empty),
+            Ok(frame_result) => {
+                let rendered = !frame_result.is_empty;
+                render_states = Some(frame_result.states);
+                Ok(rendered)
+            }
             Err(frame_result) => Err(frame_result),
         };
 
@@ -234,10 +960,15 @@ impl State {
                     .map_err(Into::<SwapBuffersError>::into);
                 if let Err(queueresult) = queueresult {
                     result = Err(queueresult);
+                } else {
+                    surface.last_queued = Some(frame_start);
                 }
             }
         }
 
+        self.render_stats
+            .frame(result.as_ref().ok().copied(), frame_start.elapsed());
+
         let reschedule = match &result {
             Ok(has_rendered) => !has_rendered,
             Err(err) => {
@@ -266,36 +997,328 @@ impl State {
 
         if reschedule {
             let output_refresh = match output.current_mode() {
-                Some(mode) => mode.refresh,
-                None => return result,
+                Some(mode) if mode.refresh > 0 => mode.refresh,
+                _ => return result,
             };
             // If reschedule is true we either hit a temporary failure or more likely rendering
             // did not cause any damage on the output. In this case we just re-schedule a repaint
             // after approx. one frame to re-test for damage.
             let reschedule_duration =
-                Duration::from_millis((1_000_000f32 / output_refresh as f32) as u64);
+                frame_duration(output_refresh).max(min_frame_duration.unwrap_or_default());
+            self.render_stats.rescheduled();
             tracing::trace!(
                 "reschedule repaint timer with delay {:?} on {:?}",
                 reschedule_duration,
                 crtc,
             );
             let timer = Timer::from_duration(reschedule_duration);
-            self.loop_handle
+            let token = self
+                .loop_handle
                 .insert_source(timer, move |_, _, data| {
                     data.render(node, crtc).ok();
                     TimeoutAction::Drop
                 })
                 .expect("failed to schedule frame timer");
+            surface.frame_timer = Some(token);
+        }
+
+        if let Some(render_states) = &render_states {
+            self.update_surface_scales(output, render_states);
         }
 
-        ws.space.elements().for_each(|window| {
-            window.send_frame(
+        ws.space
+            .elements()
+            .filter(|window| on_output(window))
+            .for_each(|window| {
+                window.send_frame(
+                    output,
+                    self.start_time.elapsed(),
+                    Some(Duration::ZERO),
+                    |_, _| Some(output.clone()),
+                );
+            });
+        if let (CursorImageStatus::Surface(cursor), true) = (&self.cursor_status, pointer_here) {
+            send_frames_surface_tree(
+                cursor,
                 output,
                 self.start_time.elapsed(),
                 Some(Duration::ZERO),
                 |_, _| Some(output.clone()),
             );
-        });
+        }
+        if let Some(icon) = self
+            .drag
+            .as_ref()
+            .and_then(|drag| drag.icon.as_ref())
+            .filter(|_| pointer_here)
+        {
+            send_frames_surface_tree(
+                icon,
+                output,
+                self.start_time.elapsed(),
+                Some(Duration::ZERO),
+                |_, _| Some(output.clone()),
+            );
+        }
+        if let Some(lock_surface) = self
+            .session_lock
+            .as_ref()
+            .and_then(|lock| lock.surface(output))
+        {
+            send_frames_surface_tree(
+                lock_surface.wl_surface(),
+                output,
+                self.start_time.elapsed(),
+                Some(Duration::ZERO),
+                |_, _| Some(output.clone()),
+            );
+        }
+        if locked && result.is_ok() {
+            self.lock_frame_shown(output);
+        }
         result
     }
+
+    /// Tells the clients shown on `output` the scale to draw at, when it
+    /// became their primary output or its scale changed since. Clients without
+    /// fractional-scale get the integer buffer scale.
+    fn update_surface_scales(&self, output: &Output, render_states: &RenderElementStates) {
+        let update = |surface: &WlSurface, states: &SurfaceData| {
+            let primary = update_surface_primary_scanout_output(
+                surface,
+                output,
+                states,
+                render_states,
+                default_primary_scanout_output_compare,
+            );
+            if let Some(primary) = primary {
+                let scale = primary.current_scale();
+                send_surface_state(
+                    surface,
+                    states,
+                    scale.integer_scale(),
+                    primary.current_transform(),
+                );
+                with_fractional_scale(states, |fractional_scale| {
+                    fractional_scale.set_preferred_scale(scale.fractional_scale());
+                });
+            }
+        };
+
+        for window in self.workspaces.get_current().space.elements() {
+            window.with_surfaces(update);
+        }
+        for layer_surface in layer_map_for_output(output).layers() {
+            layer_surface.with_surfaces(update);
+        }
+        if let CursorImageStatus::Surface(cursor) = &self.cursor_status {
+            with_surfaces_surface_tree(cursor, update);
+        }
+        if let Some(icon) = self.drag.as_ref().and_then(|drag| drag.icon.as_ref()) {
+            with_surfaces_surface_tree(icon, update);
+        }
+        if let Some(lock_surface) = self
+            .session_lock
+            .as_ref()
+            .and_then(|lock| lock.surface(output))
+        {
+            with_surfaces_surface_tree(lock_surface.wl_surface(), update);
+        }
+    }
+
+    /// Turns all outputs off or back on. Outputs that are off are neither
+    /// repainted nor used to drive frame callbacks.
+    pub fn set_output_power(&mut self, on: bool) {
+        self.power_outputs(None, on);
+    }
+
+    /// Turns `only`, or every output when None, off or back on. Returns
+    /// whether any output was found.
+    pub fn power_outputs(&mut self, only: Option<&Output>, on: bool) -> bool {
+        let mut found = false;
+        let mut changed = Vec::new();
+        let mut woken = Vec::new();
+        for (node, device) in self.backend_data.devices.iter_mut() {
+            for (crtc, surface) in device.surfaces.iter_mut() {
+                if only.is_some_and(|output| *output != surface.output) {
+                    continue;
+                }
+                found = true;
+                if surface.powered == on {
+                    continue;
+                }
+                surface.powered = on;
+                changed.push(surface.output.clone());
+                if on {
+                    // Nothing on screen is known anymore, like after a session resume
+                    surface.drm_output.with_compositor(|c| c.reset_buffers());
+                    woken.push((*node, *crtc));
+                } else if let Err(err) = surface.drm_output.with_compositor(|c| c.clear()) {
+                    tracing::warn!("Failed to power off {}: {}", surface.output.name(), err);
+                }
+            }
+        }
+        for output in changed {
+            self.output_power_state.mode_changed(&output, on);
+        }
+        for (node, crtc) in woken {
+            self.render(node, crtc).ok();
+        }
+        found
+    }
+
+    /// Removes the wl_output globals so clients stop using the outputs.
+    pub fn disable_output_globals(&mut self) {
+        for device in self.backend_data.devices.values() {
+            for surface in device.surfaces.values() {
+                self.display_handle
+                    .disable_global::<State>(surface.global_id.clone());
+            }
+        }
+    }
+
+    /// Forgets what every output is showing and repaints it from scratch, for
+    /// stale content after a driver hiccup.
+    pub fn redraw_all(&mut self) {
+        let mut outputs = Vec::new();
+        for (node, device) in self.backend_data.devices.iter_mut() {
+            for (crtc, surface) in device.surfaces.iter_mut() {
+                if !surface.powered {
+                    continue;
+                }
+                surface.drm_output.with_compositor(|c| c.reset_buffers());
+                outputs.push((*node, *crtc));
+            }
+        }
+        for (node, crtc) in outputs {
+            self.loop_handle.insert_idle(move |data| {
+                data.render(node, crtc).ok();
+            });
+        }
+    }
+}
+
+/// Length of one frame for a refresh rate in mHz, e.g. 60000 -> 16.67ms.
+/// Modes without a known rate count as 60Hz.
+pub fn frame_duration(refresh_mhz: i32) -> Duration {
+    let refresh_mhz = if refresh_mhz > 0 { refresh_mhz } else { 60_000 };
+    Duration::from_secs_f64(1_000.0 / refresh_mhz as f64)
+}
+
+/// Returns the `wp_content_type_v1` hint the client attached to the window's surface.
+pub fn content_type(window: &Window) -> ContentType {
+    window
+        .wl_surface()
+        .map(|surface| {
+            with_states(&surface, |states| {
+                *states
+                    .cached_state
+                    .get::<ContentTypeSurfaceCachedState>()
+                    .current()
+                    .content_type()
+            })
+        })
+        .unwrap_or(ContentType::None)
+}
+
+/// Render elements of `window` with its surfaces clipped to `clip`, a
+/// rectangle with corners of `radius`. Popups may reach outside the window
+/// and are left whole.
+fn rounded_window_elements<'a>(
+    renderer: &mut GlMultiRenderer<'a>,
+    window: &Window,
+    location: Point<i32, Physical>,
+    scale: Scale<f64>,
+    alpha: f32,
+    clip: Rectangle<i32, Physical>,
+    output_size: Size<i32, Physical>,
+    output_transform: Transform,
+    radius: f32,
+) -> Vec<CustomRenderElements<GlMultiRenderer<'a>>> {
+    let mut elements = Vec::new();
+    let surfaces = match window.toplevel() {
+        Some(toplevel) => {
+            let surface = toplevel.wl_surface();
+            for (popup, popup_offset) in PopupManager::popups_for_surface(surface) {
+                let offset = (window.geometry().loc + popup_offset - popup.geometry().loc)
+                    .to_physical_precise_round(scale);
+                elements.extend(
+                    render_elements_from_surface_tree::<_, WaylandSurfaceRenderElement<_>>(
+                        renderer,
+                        popup.wl_surface(),
+                        location + offset,
+                        scale,
+                        alpha,
+                        Kind::Unspecified,
+                    )
+                    .into_iter()
+                    .map(CustomRenderElements::Window),
+                );
+            }
+            render_elements_from_surface_tree::<_, WaylandSurfaceRenderElement<_>>(
+                renderer,
+                surface,
+                location,
+                scale,
+                alpha,
+                Kind::Unspecified,
+            )
+        }
+        // X11 windows have no popups of their own
+        None => window
+            .render_elements::<WaylandSurfaceRenderElement<_>>(renderer, location, scale, alpha),
+    };
+    for elem in surfaces {
+        elements.push(
+            match RoundedElement::new(
+                renderer.as_mut(),
+                elem,
+                clip,
+                output_size,
+                output_transform,
+                radius,
+            ) {
+                Ok(rounded) => CustomRenderElements::Rounded(rounded),
+                Err(elem) => CustomRenderElements::Window(elem),
+            },
+        );
+    }
+    elements
+}
+
+/// Fingerprint of the elements drawn over `blur`'s region, itself excluded.
+fn blur_scene(
+    elements: &[CustomRenderElements<GlMultiRenderer<'_>>],
+    blur: &BlurElement,
+    scale: Scale<f64>,
+) -> u64 {
+    let region = blur.geometry(scale);
+    let mut hasher = DefaultHasher::new();
+    for elem in elements.iter().filter(|elem| elem.id() != blur.id()) {
+        let geometry = elem.geometry(scale);
+        if geometry.overlaps(region) {
+            elem.id().hash(&mut hasher);
+            elem.current_commit().hash(&mut hasher);
+            (
+                geometry.loc.x,
+                geometry.loc.y,
+                geometry.size.w,
+                geometry.size.h,
+            )
+                .hash(&mut hasher);
+        }
+    }
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_duration_from_millihertz() {
+        assert_eq!(frame_duration(60_000).as_micros(), 16_666);
+        assert_eq!(frame_duration(144_000).as_micros(), 6_944);
+        assert_eq!(frame_duration(0), frame_duration(60_000));
+    }
 }
@@ -1,39 +1,103 @@
 use std::cell::RefCell;
-use std::process::Command;
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command};
+use std::time::{Duration, Instant};
 
 use serde::{Deserialize, Serialize};
 use smithay::backend::session::Session;
-use smithay::desktop::WindowSurface;
+use smithay::desktop::{Window, WindowSurface};
+use smithay::reexports::rustix::process::{kill_process_group, Pid, Signal};
+use smithay::reexports::wayland_server::Resource;
 use smithay::wayland::shell::xdg::XdgShellHandler;
 #[cfg(feature = "xwayland")]
 use smithay::xwayland::XwmHandler;
 
 use crate::state::State;
 use crate::utils::config::Config;
-use crate::utils::workspaces::{is_fullscreen, place_on_center, WindowMode};
+use crate::utils::launcher::Launcher;
+use crate::utils::swallow::window_pid;
+use crate::utils::workspaces::{
+    center_window, is_fullscreen, output_in_direction, restore_floating_geometry,
+    save_floating_geometry, window_app_id, window_title, WindowMode,
+};
 use crate::SERIAL_COUNTER;
 
+/// How long the second exit press has when `confirm_exit` is set.
+const EXIT_CONFIRM_TIMEOUT: Duration = Duration::from_secs(3);
+
 #[derive(PartialEq, Serialize, Deserialize, Clone)]
 #[serde(tag = "action", rename_all = "lowercase")]
 pub enum Action {
-    Exec { command: String },
+    Exec {
+        command: String,
+    },
     KillActive,
-    Workspace { index: usize },
-    MoveToWorkspace { index: usize },
+    /// Closes every window of the focused window's client, on all workspaces.
+    QuitApp,
+    Workspace {
+        index: usize,
+    },
+    MoveToWorkspace {
+        index: usize,
+    },
     Exit,
     Fullscreen,
-    MoveFocus { direction: Direction },
-    MoveWindow { direction: Direction },
-    VTSwitch(i32),
-    SwitchLayout,
+    MoveFocus {
+        direction: Direction,
+    },
+    MoveWindow {
+        direction: Direction,
+    },
+    /// Alt-tab through the windows of the workspace, most recently used
+    /// first. The choice sticks once the modifiers are released.
+    CycleWindows {
+        #[serde(default)]
+        reverse: bool,
+    },
+    VTSwitch {
+        vt: i32,
+    },
+    /// Next of the `keyboard.layouts`.
+    #[serde(alias = "switchlayout")]
+    SwitchKeyboardLayout,
     ReloadConfig,
+    ReloadKeymap,
     FloatingWindow,
+    /// Pins the focused window to every workspace, or unpins it.
+    ToggleSticky,
     MoveWindowMouse,
     ResizeWindowMouse,
     ToggleLayout,
+    ToggleTabbed,
+    /// Back to the layout used before the current one.
+    LastLayout,
+    /// Master/stack, monocle, grid and columns in turn.
+    CycleLayout,
+    ToggleWorkspaceFloating,
+    CycleNewWindowPosition,
     PrevWorkspace,
     NextWorkspace,
-    ResizeActive { direction: Direction, step: i32 },
+    ResizeActive {
+        direction: Direction,
+        step: i32,
+    },
+    DumpRenderElements,
+    ToggleDebugOverlay,
+    Launcher,
+    ToggleOutput {
+        name: String,
+    },
+    ResizeMaster {
+        delta: f32,
+    },
+    /// Resize the master area by `master_ratio_step`.
+    IncreaseMasterRatio,
+    DecreaseMasterRatio,
+    CenterWindow,
+    Redraw,
+    /// Turns the outputs off until the next input or `dpmson`.
+    DpmsOff,
+    DpmsOn,
 }
 
 #[derive(PartialEq, Serialize, Deserialize, Clone)]
@@ -46,75 +110,115 @@ pub enum Direction {
 }
 
 impl Action {
+    /// Whether holding the bind runs the action again at the key repeat rate.
+    pub fn repeats(&self) -> bool {
+        matches!(
+            self,
+            Action::ResizeMaster { .. }
+                | Action::IncreaseMasterRatio
+                | Action::DecreaseMasterRatio
+                | Action::ResizeActive { .. }
+        )
+    }
+
     pub fn execute(&self, state: &mut State) {
+        // Only VT switches get past a locked session
+        if state.is_locked() && !matches!(self, Action::VTSwitch { .. }) {
+            return;
+        }
         let pointer = state.seat.get_pointer().unwrap();
         let serial = SERIAL_COUNTER.next_serial();
         if pointer.is_grabbed() {
-            pointer.unset_grab(state, serial, 0);
+            let time = Duration::from(state.clock.now()).as_millis() as u32;
+            pointer.unset_grab(state, serial, time);
             match self {
                 Action::MoveWindowMouse | Action::ResizeWindowMouse => return,
                 _ => {}
             }
         }
         match self {
-            Action::VTSwitch(vt) => {
+            Action::VTSwitch { vt } => {
                 if let Err(err) = state.backend_data.session.change_vt(*vt) {
                     tracing::error!("Error changing vt: {}", err)
                 }
             }
             Action::Exit => {
-                state.loop_signal.stop();
+                if !state.config.confirm_exit {
+                    state.loop_signal.stop();
+                } else if let Some(command) = state.config.exit_confirm_command.clone() {
+                    state.spawn(&command);
+                } else if state
+                    .pending_exit
+                    .is_some_and(|pressed| pressed.elapsed() < EXIT_CONFIRM_TIMEOUT)
+                {
+                    state.loop_signal.stop();
+                } else {
+                    tracing::info!("Exit requested, repeat within a few seconds to confirm");
+                    state.pending_exit = Some(Instant::now());
+                }
             }
             Action::Exec { command } => {
-                tracing::debug!("Spawning '{command}'");
-                Command::new("/bin/sh")
-                    .arg("-c")
-                    .arg(command)
-                    .env(
-                        "WAYLAND_DISPLAY",
-                        state.socket_name.to_string_lossy().into_owned().clone(),
-                    )
-                    .spawn()
-                    .map_err(|e| tracing::info!("Failed to spawn '{command}': {e}"))
-                    .ok();
+                let command = state.expand_window_placeholders(command);
+                state.spawn(&command);
             }
             Action::ToggleLayout => {
                 let ws = state.workspaces.get_current_mut();
                 match ws.layout {
                     super::layout::LayoutState::Floating => {
-                        ws.layout = super::layout::LayoutState::default();
-                        for item in ws.space.elements() {
-                            if let Some(data) = item.user_data().get::<RefCell<WindowMode>>() {
-                                *data.borrow_mut() = WindowMode::Tiled;
-                            }
-                        }
+                        ws.set_layout(ws.master_stack());
                     }
-                    _ => {
-                        ws.layout = super::layout::LayoutState::Floating;
-                        for item in ws.space.elements() {
-                            if let Some(data) = item.user_data().get::<RefCell<WindowMode>>() {
-                                *data.borrow_mut() = WindowMode::Floating;
-                            }
-                        }
+                    _ => ws.set_layout(super::layout::LayoutState::Floating),
+                }
+                state.refresh_layout();
+            }
+            Action::ToggleTabbed => {
+                let ws = state.workspaces.get_current_mut();
+                match ws.layout {
+                    super::layout::LayoutState::Tabbed(_) => {
+                        ws.set_layout(ws.master_stack());
                     }
+                    _ => ws.set_layout(super::layout::LayoutState::Tabbed(Default::default())),
+                }
+                state.refresh_layout();
+            }
+            Action::CycleLayout => {
+                let ws = state.workspaces.get_current_mut();
+                ws.set_layout(ws.next_tiling_layout());
+                state.refresh_layout();
+            }
+            Action::LastLayout => {
+                let ws = state.workspaces.get_current_mut();
+                if let Some(layout) = ws.previous_layout.take() {
+                    ws.set_layout(layout);
+                    state.refresh_layout();
                 }
+            }
+            Action::ToggleWorkspaceFloating => {
+                state.workspaces.get_current_mut().toggle_all_floating();
                 state.refresh_layout();
             }
+            Action::CycleNewWindowPosition => {
+                state.config.new_window_position = state.config.new_window_position.next();
+            }
             Action::KillActive => {
                 let ws = state.workspaces.get_current();
                 let active = match ws.get_active_window() {
                     Some(w) => w,
                     None => return,
                 };
-                match active.underlying_surface() {
-                    WindowSurface::Wayland(xdg) => {
-                        xdg.send_close();
-                    }
-                    #[cfg(feature = "xwayland")]
-                    WindowSurface::X11(x11) => {
-                        x11.close();
-                    }
-                }
+                close_window(&active);
+            }
+            Action::QuitApp => {
+                let Some(active) = state.workspaces.get_current().get_active_window() else {
+                    return;
+                };
+                state
+                    .workspaces
+                    .workspaces
+                    .iter()
+                    .flat_map(|ws| ws.space.elements())
+                    .filter(|window| same_client(window, &active))
+                    .for_each(close_window);
             }
             Action::FloatingWindow => {
                 let ws = state.workspaces.get_current_mut();
@@ -131,13 +235,16 @@ impl Action {
                 match *user_data {
                     WindowMode::Tiled => {
                         *user_data = WindowMode::Floating;
-                        place_on_center(
+                        restore_floating_geometry(
                             &mut ws.space,
                             &active,
-                            state.config.border.gap + state.config.border.thickness,
+                            state.config.border.gap_outer + state.config.border.thickness,
+                            &state.config.floating,
+                            state.config.titlebars,
                         );
                     }
                     WindowMode::Floating => {
+                        save_floating_geometry(&ws.space, &active);
                         *user_data = WindowMode::Tiled;
                     }
                     _ => {}
@@ -145,20 +252,61 @@ impl Action {
                 drop(user_data);
                 state.refresh_layout();
             }
-            Action::ReloadConfig => state.config = Config::get_config().unwrap_or_default(),
-            Action::SwitchLayout => {
+            Action::ToggleSticky => {
+                let Some(active) = state.workspaces.get_current().get_active_window() else {
+                    return;
+                };
+                let sticky = state.workspaces.toggle_sticky(&active);
+                tracing::info!(
+                    "{} {}",
+                    if sticky { "Pinned" } else { "Unpinned" },
+                    window_title(&active)
+                );
+            }
+            Action::ReloadConfig => {
+                state.config = Config::get_config().unwrap_or_default();
+                state.apply_output_config();
+                state.apply_keyboard_config();
+                state.apply_cursor_config();
+            }
+            Action::ReloadKeymap => match Config::get_config() {
+                Some(config) => state.config.keymaps = config.keymaps,
+                None => tracing::warn!("Failed to read config, keeping current keymaps"),
+            },
+            Action::SwitchKeyboardLayout => {
                 let keyboard = state.seat.get_keyboard().unwrap();
                 keyboard.with_xkb_state(state, |mut data| {
                     data.cycle_next_layout();
                 });
+                if let Some((_, name)) = state.keyboard_layout() {
+                    tracing::info!("Switched keyboard layout to {}", name);
+                }
             }
             Action::Workspace { index } => {
-                state.workspaces.set_active_workspace(*index - 1);
+                // Index 0 wraps around and is rejected as out of range
+                state.switch_workspace(index.wrapping_sub(1));
                 state.refresh_layout();
+                if state.config.warp_cursor_on_workspace_switch {
+                    let ws = state.workspaces.get_current();
+                    let output = ws
+                        .get_active_window()
+                        .and_then(|w| ws.space.outputs_for_element(&w).first().cloned())
+                        .or_else(|| state.pointer_output());
+                    if let Some(output) = output {
+                        state.warp_pointer_to_output_center(&output);
+                    }
+                }
                 state.set_keyboard_focus_auto();
             }
             Action::MoveToWorkspace { index } => {
-                state.workspaces.move_window_to_ws(*index - 1);
+                let source = state.workspaces.active_ws();
+                state.workspaces.move_window_to_ws(
+                    index.wrapping_sub(1),
+                    &state.config.floating,
+                    state.config.titlebars,
+                );
+                // Close the gap the window left behind
+                state.refresh_layout_for(source);
                 state.refresh_layout();
                 state.set_keyboard_focus_auto();
             }
@@ -170,9 +318,18 @@ impl Action {
             }
             Action::MoveFocus { direction } => {
                 let ws = state.workspaces.get_current_mut();
-                ws.change_focus(direction, &mut state.pointer_location);
+                if !ws.change_focus(direction, &mut state.pointer_location) {
+                    // Nothing left that way on this output, continue on the next one
+                    if let Some(geo) =
+                        output_in_direction(&ws.space, state.pointer_location, direction)
+                    {
+                        state.pointer_location =
+                            geo.loc.to_f64() + geo.size.to_f64().downscale(2.0).to_point();
+                    }
+                }
                 state.set_keyboard_focus_auto();
             }
+            Action::CycleWindows { reverse } => state.cycle_windows(*reverse),
             Action::Fullscreen => {
                 let active_window = match &state.workspaces.get_current().active_window {
                     Some(active) => active,
@@ -183,17 +340,7 @@ impl Action {
                     //if fullscreen == acitve_window {
                     //    state.unfullscreen_request(acitve_window.toplevel().unwrap().clone());
                     //}
-                    match fullscreen.underlying_surface() {
-                        WindowSurface::Wayland(xdg) => {
-                            XdgShellHandler::unfullscreen_request(state, xdg.clone());
-                        }
-                        #[cfg(feature = "xwayland")]
-                        WindowSurface::X11(x11) => {
-                            if let Some(xwm) = state.xwm.as_ref() {
-                                XwmHandler::unfullscreen_request(state, xwm.id(), x11.clone());
-                            }
-                        }
-                    }
+                    state.unfullscreen(&fullscreen.clone());
                 } else {
                     match active_window.underlying_surface() {
                         WindowSurface::Wayland(xdg) => {
@@ -216,14 +363,69 @@ impl Action {
                 state.init_pointer_resize_grab(273, serial);
             }
             Action::PrevWorkspace => {
-                state
-                    .workspaces
-                    .set_active_workspace(state.workspaces.prev_workspace);
+                state.switch_workspace(state.workspaces.prev_workspace);
             }
             Action::NextWorkspace => {
-                state
+                state.switch_workspace(state.workspaces.active_workspace + 1);
+            }
+            Action::DumpRenderElements => {
+                state.dump_render_elements = state
                     .workspaces
-                    .set_active_workspace(state.workspaces.active_workspace + 1);
+                    .get_current()
+                    .space
+                    .outputs()
+                    .map(|output| output.name())
+                    .collect();
+            }
+            Action::ToggleDebugOverlay => {
+                state.debug_overlay = !state.debug_overlay;
+            }
+            Action::Redraw => state.redraw_all(),
+            Action::DpmsOff => state.blank_outputs(),
+            Action::DpmsOn => state.set_output_power(true),
+            Action::ToggleOutput { name } => {
+                if let Err(err) = state.toggle_output(name) {
+                    tracing::warn!("Failed to toggle output {}: {}", name, err);
+                }
+            }
+            Action::Launcher => {
+                state.overlay = match state.overlay {
+                    Some(_) => None,
+                    None => Some(Box::new(Launcher::new())),
+                };
+            }
+            Action::CenterWindow => {
+                let ws = state.workspaces.get_current_mut();
+                let Some(active) = ws.get_active_window() else {
+                    return;
+                };
+                let floating = active
+                    .user_data()
+                    .get::<RefCell<WindowMode>>()
+                    .is_some_and(|mode| *mode.borrow() == WindowMode::Floating);
+                if floating {
+                    center_window(
+                        &mut ws.space,
+                        &active,
+                        state.config.border.gap_outer + state.config.border.thickness,
+                        &state.config.floating,
+                        state.config.titlebars,
+                    );
+                }
+            }
+            Action::ResizeMaster { delta } => {
+                if state.workspaces.get_current_mut().resize_master(*delta) {
+                    state.refresh_layout();
+                }
+            }
+            Action::IncreaseMasterRatio | Action::DecreaseMasterRatio => {
+                let step = match self {
+                    Action::IncreaseMasterRatio => state.config.master_ratio_step,
+                    _ => -state.config.master_ratio_step,
+                };
+                if state.workspaces.get_current_mut().resize_master(step) {
+                    state.refresh_layout();
+                }
             }
             Action::ResizeActive { direction, step } => {
                 let ws = state.workspaces.get_current_mut();
@@ -276,3 +478,315 @@ impl Action {
         };
     }
 }
+
+impl State {
+    pub fn spawn(&self, command: &str) -> Option<Child> {
+        let err = match self.try_spawn(command) {
+            Ok(child) => return Some(child),
+            Err(err) => err,
+        };
+        tracing::warn!(
+            "Failed to spawn '{command}': {err} (errno {:?})",
+            err.raw_os_error()
+        );
+        let fallback = self.config.on_exec_failure.as_deref()?;
+        if fallback == command {
+            return None;
+        }
+        tracing::info!("Spawning fallback '{fallback}'");
+        match self.try_spawn(fallback) {
+            Ok(child) => Some(child),
+            Err(err) => {
+                tracing::warn!(
+                    "Failed to spawn fallback '{fallback}': {err} (errno {:?})",
+                    err.raw_os_error()
+                );
+                None
+            }
+        }
+    }
+
+    /// Takes `window` out of fullscreen, as if its client had asked.
+    pub fn unfullscreen(&mut self, window: &Window) {
+        match window.underlying_surface() {
+            WindowSurface::Wayland(xdg) => {
+                XdgShellHandler::unfullscreen_request(self, xdg.clone());
+            }
+            #[cfg(feature = "xwayland")]
+            WindowSurface::X11(x11) => {
+                if let Some(xwm) = self.xwm.as_ref() {
+                    XwmHandler::unfullscreen_request(self, xwm.id(), x11.clone());
+                }
+            }
+        }
+    }
+
+    /// Tears the session down once the event loop stopped: output globals go
+    /// away, Xwayland's window manager lets go, autostarted programs are
+    /// asked to terminate and clients get a last flush.
+    pub fn shutdown(&mut self) {
+        tracing::info!("Shutting down");
+        self.disable_output_globals();
+        #[cfg(feature = "xwayland")]
+        {
+            self.xwm = None;
+            self.xdisplay = None;
+        }
+        for mut child in self.autostart_children.drain(..) {
+            if let Ok(None) = child.try_wait() {
+                // The shell leads the program's process group, so both get the signal
+                if let Err(err) = kill_process_group(Pid::from_child(&child), Signal::TERM) {
+                    tracing::warn!("Failed to terminate autostarted program: {}", err);
+                }
+                child.wait().ok();
+            }
+        }
+        if let Err(err) = self.display_handle.flush_clients() {
+            tracing::warn!("Failed to flush clients: {}", err);
+        }
+    }
+
+    /// Replaces `{app_id}`, `{title}` and `{pid}` in `command` with the properties
+    /// of the active window, quoted for the shell. They are empty without one.
+    pub fn expand_window_placeholders(&self, command: &str) -> String {
+        if !command.contains('{') {
+            return command.to_string();
+        }
+        let active = self.workspaces.get_current().get_active_window();
+        let (app_id, title, pid) = match &active {
+            Some(window) => (
+                window_app_id(window),
+                window_title(window),
+                window_pid(window, &self.display_handle)
+                    .map(|pid| pid.to_string())
+                    .unwrap_or_default(),
+            ),
+            None => Default::default(),
+        };
+        expand_placeholders(command, &app_id, &title, &pid)
+    }
+
+    fn try_spawn(&self, command: &str) -> std::io::Result<Child> {
+        tracing::debug!("Spawning '{command}'");
+        Command::new("/bin/sh")
+            .arg("-c")
+            .arg(command)
+            .env(
+                "WAYLAND_DISPLAY",
+                self.socket_name.to_string_lossy().into_owned(),
+            )
+            .process_group(0)
+            .spawn()
+    }
+}
+
+pub fn close_window(window: &Window) {
+    match window.underlying_surface() {
+        WindowSurface::Wayland(xdg) => {
+            xdg.send_close();
+        }
+        #[cfg(feature = "xwayland")]
+        WindowSurface::X11(x11) => {
+            if let Err(err) = x11.close() {
+                tracing::warn!("Failed to close X11 window: {}", err);
+            }
+        }
+    }
+}
+
+/// Whether both windows belong to one client. X11 windows all come through
+/// Xwayland's connection, they are told apart by their pid instead.
+fn same_client(a: &Window, b: &Window) -> bool {
+    match (a.underlying_surface(), b.underlying_surface()) {
+        (WindowSurface::Wayland(a), WindowSurface::Wayland(b)) => {
+            a.wl_surface().client().is_some_and(|client| {
+                b.wl_surface()
+                    .client()
+                    .is_some_and(|other| client.id() == other.id())
+            })
+        }
+        #[cfg(feature = "xwayland")]
+        (WindowSurface::X11(a), WindowSurface::X11(b)) => {
+            a.pid().is_some_and(|pid| b.pid() == Some(pid))
+        }
+        #[cfg(feature = "xwayland")]
+        _ => false,
+    }
+}
+
+/// Fills the placeholders of `command` in a single pass, so text coming
+/// from one value is never expanded again by a later placeholder.
+fn expand_placeholders(command: &str, app_id: &str, title: &str, pid: &str) -> String {
+    let mut expanded = String::with_capacity(command.len());
+    let mut rest = command;
+    while let Some(start) = rest.find('{') {
+        expanded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let value = [("{app_id}", app_id), ("{title}", title), ("{pid}", pid)]
+            .into_iter()
+            .find(|(placeholder, _)| rest.starts_with(placeholder));
+        match value {
+            Some((placeholder, value)) => {
+                expanded.push_str(&shell_quote(value));
+                rest = &rest[placeholder.len()..];
+            }
+            None => {
+                expanded.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    expanded.push_str(rest);
+    expanded
+}
+
+/// Titles are chosen by clients, never let them reach the shell unquoted.
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn placeholders_are_quoted() {
+        assert_eq!(
+            expand_placeholders("notify {app_id} {pid}", "foot", "", "42"),
+            "notify 'foot' '42'"
+        );
+        assert_eq!(expand_placeholders("echo {}", "", "", ""), "echo {}");
+    }
+
+    #[test]
+    fn values_are_not_expanded_again() {
+        let title = "{pid}'; rm -rf ~ #";
+        let expanded = expand_placeholders("echo {title} {pid}", "", title, "7");
+        assert_eq!(expanded, "echo '{pid}'\\''; rm -rf ~ #' '7'");
+    }
+
+    /// One of every variant, the match fails to compile when one is added
+    /// without being listed here.
+    fn every_action() -> Vec<Action> {
+        let actions = vec![
+            Action::Exec {
+                command: "foot -e 'htop'".to_string(),
+            },
+            Action::KillActive,
+            Action::QuitApp,
+            Action::Workspace { index: 3 },
+            Action::MoveToWorkspace { index: 2 },
+            Action::Exit,
+            Action::Fullscreen,
+            Action::MoveFocus {
+                direction: Direction::Left,
+            },
+            Action::MoveWindow {
+                direction: Direction::Down,
+            },
+            Action::CycleWindows { reverse: true },
+            Action::VTSwitch { vt: 2 },
+            Action::SwitchKeyboardLayout,
+            Action::ReloadConfig,
+            Action::ReloadKeymap,
+            Action::FloatingWindow,
+            Action::ToggleSticky,
+            Action::MoveWindowMouse,
+            Action::ResizeWindowMouse,
+            Action::ToggleLayout,
+            Action::ToggleTabbed,
+            Action::LastLayout,
+            Action::CycleLayout,
+            Action::ToggleWorkspaceFloating,
+            Action::CycleNewWindowPosition,
+            Action::PrevWorkspace,
+            Action::NextWorkspace,
+            Action::ResizeActive {
+                direction: Direction::Right,
+                step: -20,
+            },
+            Action::DumpRenderElements,
+            Action::ToggleDebugOverlay,
+            Action::Launcher,
+            Action::ToggleOutput {
+                name: "HDMI-A-1".to_string(),
+            },
+            Action::ResizeMaster { delta: 0.25 },
+            Action::IncreaseMasterRatio,
+            Action::DecreaseMasterRatio,
+            Action::CenterWindow,
+            Action::Redraw,
+            Action::DpmsOff,
+            Action::DpmsOn,
+        ];
+        for action in &actions {
+            match action {
+                Action::Exec { .. }
+                | Action::KillActive
+                | Action::QuitApp
+                | Action::Workspace { .. }
+                | Action::MoveToWorkspace { .. }
+                | Action::Exit
+                | Action::Fullscreen
+                | Action::MoveFocus { .. }
+                | Action::MoveWindow { .. }
+                | Action::CycleWindows { .. }
+                | Action::VTSwitch { .. }
+                | Action::SwitchKeyboardLayout
+                | Action::ReloadConfig
+                | Action::ReloadKeymap
+                | Action::FloatingWindow
+                | Action::ToggleSticky
+                | Action::MoveWindowMouse
+                | Action::ResizeWindowMouse
+                | Action::ToggleLayout
+                | Action::ToggleTabbed
+                | Action::LastLayout
+                | Action::CycleLayout
+                | Action::ToggleWorkspaceFloating
+                | Action::CycleNewWindowPosition
+                | Action::PrevWorkspace
+                | Action::NextWorkspace
+                | Action::ResizeActive { .. }
+                | Action::DumpRenderElements
+                | Action::ToggleDebugOverlay
+                | Action::Launcher
+                | Action::ToggleOutput { .. }
+                | Action::ResizeMaster { .. }
+                | Action::IncreaseMasterRatio
+                | Action::DecreaseMasterRatio
+                | Action::CenterWindow
+                | Action::Redraw
+                | Action::DpmsOff
+                | Action::DpmsOn => {}
+            }
+        }
+        actions
+    }
+
+    #[test]
+    fn every_action_survives_toml() {
+        let mut config = Config::default();
+        config.keymaps.clear();
+        let actions = every_action();
+        for (index, action) in actions.iter().enumerate() {
+            config
+                .keymaps
+                .insert(format!("Super+F{index}"), action.clone());
+        }
+        let text = toml::to_string(&config).unwrap();
+        let parsed = Config::parse(&text).unwrap();
+        assert_eq!(parsed.keymaps.len(), actions.len());
+        for (index, action) in actions.iter().enumerate() {
+            let bind = format!("Super+F{index}");
+            assert!(
+                parsed.keymaps.get(&bind) == Some(action),
+                "{bind} did not survive"
+            );
+        }
+
+        // Written by hand the way the config documents it
+        let action: Action = toml::from_str("action = \"vtswitch\"\nvt = 3").unwrap();
+        assert!(action == Action::VTSwitch { vt: 3 });
+    }
+}
@@ -0,0 +1,71 @@
+use std::time::{Duration, Instant};
+
+use smithay::utils::{Logical, Point, Size};
+
+use crate::{state::State, utils::config::SlideDirection};
+
+/// A running slide between two workspaces, the current one slides in.
+pub struct WorkspaceSlide {
+    pub from: usize,
+    /// Whether the target workspace comes after `from`, it then enters from the right (or bottom).
+    forward: bool,
+    start: Instant,
+    duration: Duration,
+}
+
+impl WorkspaceSlide {
+    pub fn done(&self) -> bool {
+        self.start.elapsed() >= self.duration
+    }
+
+    /// Eased progress from 0 to 1.
+    fn progress(&self) -> f64 {
+        let t = (self.start.elapsed().as_secs_f64() / self.duration.as_secs_f64()).min(1.0);
+        1.0 - (1.0 - t).powi(3)
+    }
+
+    /// Offsets of the outgoing and the incoming workspace on an output of `size`.
+    pub fn offsets(
+        &self,
+        size: Size<i32, Logical>,
+        direction: SlideDirection,
+    ) -> (Point<i32, Logical>, Point<i32, Logical>) {
+        let length = match direction {
+            SlideDirection::Horizontal => size.w,
+            SlideDirection::Vertical => size.h,
+        } as f64;
+        let sign = if self.forward { 1.0 } else { -1.0 };
+        let out = (-sign * length * self.progress()).round() as i32;
+        let incoming = (sign * length * (1.0 - self.progress())).round() as i32;
+        match direction {
+            SlideDirection::Horizontal => ((out, 0).into(), (incoming, 0).into()),
+            SlideDirection::Vertical => ((0, out).into(), (0, incoming).into()),
+        }
+    }
+}
+
+impl State {
+    /// Switches to `index`, sliding the workspaces if `workspace_animation` is enabled.
+    pub fn switch_workspace(&mut self, index: usize) {
+        let from = self.workspaces.active_ws();
+        self.workspaces.set_active_workspace(index);
+        let to = self.workspaces.active_ws();
+        let animation = &self.config.workspace_animation;
+        if !animation.enabled || animation.duration_ms == 0 || from == to {
+            return;
+        }
+        self.workspace_slide = Some(WorkspaceSlide {
+            from,
+            forward: to > from,
+            start: Instant::now(),
+            duration: Duration::from_millis(animation.duration_ms),
+        });
+    }
+
+    /// Binds and clicks are ignored while workspaces slide.
+    pub fn input_locked(&self) -> bool {
+        self.workspace_slide
+            .as_ref()
+            .is_some_and(|slide| !slide.done())
+    }
+}
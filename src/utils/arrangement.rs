@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+
+use smithay::utils::{Logical, Point};
+
+use crate::{
+    state::State,
+    utils::workspaces::{set_window_output, window_id, window_output_name},
+};
+
+/// Where a window was under some output configuration.
+#[derive(Clone)]
+struct Placement {
+    window: u64,
+    workspace: usize,
+    loc: Point<i32, Logical>,
+    /// Output it was tiled on.
+    output: Option<String>,
+}
+
+/// Window arrangements by output configuration, keyed by the sorted names of
+/// the connected outputs. Saved before outputs change, restored when a known
+/// configuration comes back.
+#[derive(Default)]
+pub struct Arrangements(HashMap<String, Vec<Placement>>);
+
+impl State {
+    fn output_configuration(&self) -> String {
+        let mut names: Vec<String> = self
+            .workspaces
+            .get_current()
+            .space
+            .outputs()
+            .map(|o| o.name())
+            .collect();
+        names.sort();
+        names.join(",")
+    }
+
+    pub fn save_arrangement(&mut self) {
+        let key = self.output_configuration();
+        if key.is_empty() {
+            return;
+        }
+        let placements = self
+            .workspaces
+            .workspaces
+            .iter()
+            .enumerate()
+            .flat_map(|(index, ws)| {
+                ws.space.elements().filter_map(move |window| {
+                    Some(Placement {
+                        window: window_id(window),
+                        workspace: index,
+                        loc: ws.space.element_location(window)?,
+                        output: window_output_name(window),
+                    })
+                })
+            })
+            .collect();
+        self.arrangements.0.insert(key, placements);
+    }
+
+    /// Puts windows back where they were the last time these outputs were connected.
+    pub fn restore_arrangement(&mut self) {
+        let Some(placements) = self
+            .arrangements
+            .0
+            .get(&self.output_configuration())
+            .cloned()
+        else {
+            return;
+        };
+        for placement in placements {
+            let Some((index, window)) =
+                self.workspaces
+                    .workspaces
+                    .iter()
+                    .enumerate()
+                    .find_map(|(index, ws)| {
+                        ws.space
+                            .elements()
+                            .find(|w| window_id(w) == placement.window)
+                            .map(|w| (index, w.clone()))
+                    })
+            else {
+                continue;
+            };
+            let Some(target) = self.workspaces.workspaces.get_mut(placement.workspace) else {
+                continue;
+            };
+            if let Some(output) = placement
+                .output
+                .and_then(|name| target.space.outputs().find(|o| o.name() == name).cloned())
+            {
+                set_window_output(&window, &output);
+            }
+            target
+                .space
+                .map_element(window.clone(), placement.loc, false);
+            if index != placement.workspace {
+                let source = &mut self.workspaces.workspaces[index];
+                source.space.unmap_elem(&window);
+                if source.active_window.as_ref() == Some(&window) {
+                    source.active_window = None;
+                }
+            }
+        }
+        self.refresh_all_layouts();
+    }
+}
@@ -0,0 +1,135 @@
+use std::cell::Cell;
+
+use smithay::{
+    desktop::{layer_map_for_output, LayerMap, LayerSurface},
+    utils::{Logical, Rectangle},
+    wayland::{
+        compositor::with_states,
+        shell::wlr_layer::{Anchor, ExclusiveZone, LayerSurfaceCachedState},
+    },
+};
+
+use crate::{state::State, utils::action::Direction};
+
+/// How close to the output edge the pointer has to get to reveal a panel.
+const REVEAL_DISTANCE: f64 = 2.0;
+
+/// Marks a layer surface listed in `autohide_layers`, set while it is revealed.
+pub struct AutoHide(Cell<bool>);
+
+pub fn is_hidden(layer: &LayerSurface) -> bool {
+    layer
+        .user_data()
+        .get::<AutoHide>()
+        .is_some_and(|autohide| !autohide.0.get())
+}
+
+/// The edge a layer surface is attached to and the space it reserves there.
+fn layer_edge(layer: &LayerSurface) -> Option<(Direction, i32)> {
+    let (anchor, zone) = with_states(layer.wl_surface(), |states| {
+        let mut guard = states.cached_state.get::<LayerSurfaceCachedState>();
+        let data = guard.current();
+        (data.anchor, data.exclusive_zone)
+    });
+    let size = match zone {
+        ExclusiveZone::Exclusive(size) => size as i32,
+        _ => 0,
+    };
+    let top = anchor.contains(Anchor::TOP);
+    let bottom = anchor.contains(Anchor::BOTTOM);
+    let left = anchor.contains(Anchor::LEFT);
+    let right = anchor.contains(Anchor::RIGHT);
+    let edge = if top && !bottom && left == right {
+        Direction::Top
+    } else if bottom && !top && left == right {
+        Direction::Down
+    } else if left && !right && top == bottom {
+        Direction::Left
+    } else if right && !left && top == bottom {
+        Direction::Right
+    } else {
+        return None;
+    };
+    Some((edge, size))
+}
+
+/// The non-exclusive zone of `map`, with the space of hidden panels given back.
+pub fn usable_zone(map: &LayerMap) -> Rectangle<i32, Logical> {
+    let mut zone = map.non_exclusive_zone();
+    for layer in map.layers().filter(|layer| is_hidden(layer)) {
+        let Some((edge, size)) = layer_edge(layer) else {
+            continue;
+        };
+        match edge {
+            Direction::Top => {
+                zone.loc.y -= size;
+                zone.size.h += size;
+            }
+            Direction::Down => zone.size.h += size,
+            Direction::Left => {
+                zone.loc.x -= size;
+                zone.size.w += size;
+            }
+            Direction::Right => zone.size.w += size,
+        }
+    }
+    zone
+}
+
+impl State {
+    pub fn mark_autohide(&self, layer: &LayerSurface) {
+        if self
+            .config
+            .autohide_layers
+            .iter()
+            .any(|namespace| namespace == layer.namespace())
+        {
+            layer
+                .user_data()
+                .insert_if_missing(|| AutoHide(Cell::new(false)));
+        }
+    }
+
+    /// Reveals auto-hiding panels at the edge the pointer touches, hides the
+    /// ones it left, and re-tiles when the usable area changed.
+    pub fn update_autohide(&mut self) {
+        let pos = self.pointer_location;
+        let ws = self.workspaces.get_current();
+        let mut changed = false;
+        for output in ws.space.outputs() {
+            let Some(output_geo) = ws.space.output_geometry(output) else {
+                continue;
+            };
+            let on_output = output_geo.to_f64().contains(pos);
+            let local = pos - output_geo.loc.to_f64();
+            let size = output_geo.size.to_f64();
+            let map = layer_map_for_output(output);
+            for layer in map.layers() {
+                let Some(autohide) = layer.user_data().get::<AutoHide>() else {
+                    continue;
+                };
+                let Some((edge, _)) = layer_edge(layer) else {
+                    continue;
+                };
+                let at_edge = match edge {
+                    Direction::Top => local.y < REVEAL_DISTANCE,
+                    Direction::Down => local.y > size.h - REVEAL_DISTANCE,
+                    Direction::Left => local.x < REVEAL_DISTANCE,
+                    Direction::Right => local.x > size.w - REVEAL_DISTANCE,
+                };
+                // Once shown, the panel stays while the pointer is on it
+                let over = autohide.0.get()
+                    && map
+                        .layer_geometry(layer)
+                        .is_some_and(|geo| geo.to_f64().contains(local));
+                let revealed = on_output && (at_edge || over);
+                if autohide.0.replace(revealed) != revealed {
+                    changed = true;
+                }
+            }
+        }
+        if changed {
+            self.refresh_layout();
+        }
+    }
+}
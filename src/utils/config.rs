@@ -2,13 +2,28 @@ use std::{fs, path::PathBuf};
 
 use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
-use smithay::input::keyboard::{xkb, Keysym, ModifiersState};
+use smithay::{
+    input::keyboard::{xkb, Keysym, ModifiersState},
+    utils::{Logical, Rectangle, Size},
+};
 
-use crate::utils::action::{Action, Direction};
+use crate::utils::{
+    action::{Action, Direction},
+    render::border::hex_to_rgb,
+};
+
+const DEFAULT_BACKGROUND: [f32; 4] = [0.1, 0.1, 0.1, 1.0];
+const DEFAULT_CURSOR_SIZE: u32 = 24;
 
 #[derive(Deserialize, Serialize)]
 pub struct KeyboardConfig {
     pub layouts: Vec<String>,
+    /// Milliseconds a key is held before it repeats, for clients and repeating binds.
+    #[serde(default = "default_repeat_delay")]
+    pub repeat_delay: i32,
+    /// Repeats per second, 0 disables key repeat.
+    #[serde(default = "default_repeat_rate")]
+    pub repeat_rate: i32,
 }
 
 #[derive(Deserialize, Serialize)]
@@ -18,7 +33,163 @@ pub struct Config {
     pub keyboard: KeyboardConfig,
     pub outputs: IndexMap<String, OutputData>,
     pub autostart: Vec<String>,
+    /// Key binds, and mouse button binds such as `Super+btn_middle`.
     pub keymaps: IndexMap<String, Action>,
+    pub on_exec_failure: Option<String>,
+    /// What Super+scroll does, `workspace` when left out.
+    #[serde(default = "default_mod_scroll_action")]
+    pub mod_scroll_action: Option<ModScrollAction>,
+    #[serde(default)]
+    pub warp_cursor_on_workspace_switch: bool,
+    #[serde(default = "default_opacity")]
+    pub inactive_opacity: f32,
+    pub dim_inactive: Option<DimInactive>,
+    #[serde(default)]
+    pub new_window_position: NewWindowPosition,
+    #[serde(default)]
+    pub override_redirect_focus: OverrideRedirectFocus,
+    /// Seconds between liveness pings, 0 disables them.
+    #[serde(default = "default_ping_interval")]
+    pub ping_interval: u64,
+    /// Drop inner and outer gaps and the border when only one tiled window is visible.
+    #[serde(default)]
+    pub smart_gaps: bool,
+    /// Drop only the border of a lone tiled window, keeping its gaps.
+    #[serde(default)]
+    pub hide_border_single: bool,
+    /// Hide a terminal while a window launched from it is open.
+    #[serde(default)]
+    pub swallow: bool,
+    /// App ids of the terminals that may be swallowed.
+    #[serde(default)]
+    pub swallow_terminals: Vec<String>,
+    #[serde(default)]
+    pub window_rules: Vec<WindowRule>,
+    /// Action run when Super is tapped on its own.
+    pub on_mod_tap: Option<Action>,
+    #[serde(default)]
+    pub focus_new_windows: FocusNewWindows,
+    /// Require the exit bind twice, or `exit_confirm_command` to send `exit` over ipc.
+    #[serde(default)]
+    pub confirm_exit: bool,
+    pub exit_confirm_command: Option<String>,
+    pub blur: Option<Blur>,
+    /// libinput settings by device name, or `touchpad` / `pointer` for all of a kind.
+    #[serde(default)]
+    pub input_devices: IndexMap<String, InputDeviceConfig>,
+    #[serde(default)]
+    pub floating: Floating,
+    #[serde(default)]
+    pub fullscreen_behavior: FullscreenBehavior,
+    /// Touchpad swipes bound to actions, clients only get the swipes none of
+    /// them match.
+    #[serde(default)]
+    pub gestures: Vec<Gesture>,
+    /// Namespaces of layer surfaces (docks, panels) that hide until the pointer
+    /// touches their edge, windows take their space meanwhile.
+    #[serde(default)]
+    pub autohide_layers: Vec<String>,
+    /// Keep floating windows from being moved off every output.
+    #[serde(default = "default_true")]
+    pub constrain_floating: bool,
+    /// Pixels of a floating window that stay on an output when constrained.
+    #[serde(default = "default_visible_strip")]
+    pub floating_visible_strip: i32,
+    /// Draw titlebars on floating windows using server-side decorations.
+    #[serde(default)]
+    pub titlebars: bool,
+    #[serde(default)]
+    pub workspace_animation: WorkspaceAnimation,
+    /// Where a toplevel that unmapped itself (e.g. hid to the tray) goes when it maps again.
+    #[serde(default)]
+    pub unmapped_windows: UnmapBehavior,
+    #[serde(default)]
+    pub xwayland: XwaylandMode,
+    #[serde(default)]
+    pub focus_on_empty: FocusOnEmpty,
+    /// Per-workspace settings, `[[workspace]]` tables.
+    #[serde(default, rename = "workspace")]
+    pub workspace_settings: Vec<WorkspaceSettings>,
+    /// Width of the master area on new workspaces, as a fraction of the output.
+    #[serde(default = "default_master_ratio")]
+    pub master_ratio: f32,
+    /// Change of `master_ratio` per `increasemasterratio`/`decreasemasterratio`.
+    #[serde(default = "default_master_ratio_step")]
+    pub master_ratio_step: f32,
+    /// Bind that always leaves fullscreen, ends pointer grabs and lifts keyboard
+    /// shortcut inhibitors, whatever the focused client does.
+    #[serde(default = "default_escape_chord")]
+    pub escape_chord: String,
+    /// Seconds without input before the outputs turn off, 0 keeps them on.
+    /// Windows and layer surfaces inhibiting idle keep them on too.
+    #[serde(default)]
+    pub idle_timeout: u64,
+    /// XCursor theme of the pointer, `XCURSOR_THEME` or `default` when unset.
+    pub cursor_theme: Option<String>,
+    /// Pointer size in logical pixels, `XCURSOR_SIZE` or 24 when unset.
+    pub cursor_size: Option<u32>,
+}
+
+fn default_opacity() -> f32 {
+    1.0
+}
+
+fn default_ping_interval() -> u64 {
+    5
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_visible_strip() -> i32 {
+    40
+}
+
+fn default_repeat_delay() -> i32 {
+    200
+}
+
+fn default_repeat_rate() -> i32 {
+    25
+}
+
+/// `border.gap` inset every tile by the same amount, so it was half the
+/// space between two windows and all of the space to the output edge.
+fn migrate_gap(border: &mut toml::Table) {
+    let Some(gap) = border.remove("gap").and_then(|gap| gap.as_integer()) else {
+        return;
+    };
+    border
+        .entry("gap_inner")
+        .or_insert(toml::Value::Integer(gap * 2));
+    border
+        .entry("gap_outer")
+        .or_insert(toml::Value::Integer(gap));
+}
+
+fn default_gap_inner() -> i32 {
+    4
+}
+
+fn default_gap_outer() -> i32 {
+    2
+}
+
+fn default_master_ratio() -> f32 {
+    0.5
+}
+
+fn default_master_ratio_step() -> f32 {
+    0.05
+}
+
+fn default_mod_scroll_action() -> Option<ModScrollAction> {
+    Some(ModScrollAction::Workspace)
+}
+
+fn default_escape_chord() -> String {
+    "Super+Shift+Escape".to_string()
 }
 
 impl Default for Config {
@@ -26,15 +197,23 @@ impl Default for Config {
         let workspaces = 4;
         let border = Border {
             thickness: 2,
-            gap: 2,
+            gap_inner: default_gap_inner(),
+            gap_outer: default_gap_outer(),
             active: "#8B4000".to_string(),
             end_active: None,
             inactive: "#2A2A2A".to_string(),
             end_inactive: None,
             angle: None,
+            unresponsive: None,
+            inner_color: None,
+            outer_color: None,
+            outer_width: None,
+            corner_radius: 0,
         };
         let keyboard = KeyboardConfig {
             layouts: vec!["us".to_string()],
+            repeat_delay: default_repeat_delay(),
+            repeat_rate: default_repeat_rate(),
         };
         let mut outputs = IndexMap::new();
         outputs.insert(
@@ -48,13 +227,15 @@ impl Default for Config {
                 transform: None,
                 workspaces: None,
                 enabled: true,
+                reserved_margins: None,
+                max_fps: None,
             },
         );
         let autostart = vec![];
         let mut keymaps = IndexMap::new();
         keymaps.insert("Super+c".to_string(), Action::KillActive);
         keymaps.insert("Super+Shift+Enter".to_string(), Action::Exit);
-        keymaps.insert("Super+space".to_string(), Action::SwitchLayout);
+        keymaps.insert("Super+space".to_string(), Action::SwitchKeyboardLayout);
         keymaps.insert("Super+f".to_string(), Action::Fullscreen);
         keymaps.insert("Super+r".to_string(), Action::ReloadConfig);
         for index in 1..5 {
@@ -134,6 +315,54 @@ impl Default for Config {
             outputs,
             autostart,
             keymaps,
+            on_exec_failure: None,
+            mod_scroll_action: default_mod_scroll_action(),
+            warp_cursor_on_workspace_switch: false,
+            inactive_opacity: default_opacity(),
+            dim_inactive: None,
+            new_window_position: NewWindowPosition::Master,
+            override_redirect_focus: OverrideRedirectFocus::Auto,
+            ping_interval: default_ping_interval(),
+            smart_gaps: false,
+            hide_border_single: false,
+            swallow: false,
+            swallow_terminals: vec!["kitty".to_string()],
+            window_rules: vec![],
+            on_mod_tap: None,
+            focus_new_windows: FocusNewWindows::Smart,
+            confirm_exit: false,
+            exit_confirm_command: None,
+            blur: None,
+            input_devices: IndexMap::new(),
+            floating: Floating::default(),
+            fullscreen_behavior: FullscreenBehavior::Exclusive,
+            gestures: vec![
+                Gesture {
+                    fingers: 3,
+                    direction: Direction::Left,
+                    action: Action::NextWorkspace,
+                },
+                Gesture {
+                    fingers: 3,
+                    direction: Direction::Right,
+                    action: Action::PrevWorkspace,
+                },
+            ],
+            autohide_layers: vec![],
+            constrain_floating: default_true(),
+            floating_visible_strip: default_visible_strip(),
+            titlebars: false,
+            workspace_animation: WorkspaceAnimation::default(),
+            unmapped_windows: UnmapBehavior::Restore,
+            xwayland: XwaylandMode::Eager,
+            focus_on_empty: FocusOnEmpty::Keep,
+            workspace_settings: vec![],
+            master_ratio: default_master_ratio(),
+            master_ratio_step: default_master_ratio_step(),
+            escape_chord: default_escape_chord(),
+            idle_timeout: 0,
+            cursor_theme: None,
+            cursor_size: None,
         }
     }
 }
@@ -147,7 +376,7 @@ impl Config {
         config_path.push("config.toml");
         let data = if config_path.exists() {
             let file_data = std::fs::read_to_string(config_path).ok()?;
-            toml::from_str(&file_data).ok()
+            Config::parse(&file_data).ok()
         } else {
             let config = Config::default();
             let data = toml::to_string(&config).ok().unwrap();
@@ -157,17 +386,371 @@ impl Config {
         };
         data
     }
+
+    /// Parses a config file, carrying keys of older versions over to their
+    /// current names.
+    pub fn parse(data: &str) -> Result<Config, toml::de::Error> {
+        let mut table: toml::Table = toml::from_str(data)?;
+        if let Some(toml::Value::Table(border)) = table.get_mut("border") {
+            migrate_gap(border);
+        }
+        toml::Value::Table(table).try_into()
+    }
+
+    /// Action of the key bind for the `held` modifiers and a key with `raw_syms`.
+    pub fn key_action(&self, held: &ModifiersState, raw_syms: &[Keysym]) -> Option<&Action> {
+        self.keymaps.iter().find_map(|(bind, action)| {
+            let (modifiers, keysym) = parse_keybind(bind)?;
+            (modifiers_match(held, &modifiers) && raw_syms.contains(&keysym)).then_some(action)
+        })
+    }
+
+    /// XCursor theme the pointer is drawn with.
+    pub fn cursor_theme(&self) -> String {
+        self.cursor_theme
+            .clone()
+            .or_else(|| std::env::var("XCURSOR_THEME").ok())
+            .unwrap_or_else(|| "default".to_string())
+    }
+
+    /// Pointer size in logical pixels.
+    pub fn cursor_size(&self) -> u32 {
+        self.cursor_size
+            .or_else(|| {
+                std::env::var("XCURSOR_SIZE")
+                    .ok()
+                    .and_then(|s| s.parse().ok())
+            })
+            .filter(|size| *size > 0)
+            .unwrap_or(DEFAULT_CURSOR_SIZE)
+    }
+
+    /// Color behind the windows of workspace `index` (0-based).
+    pub fn workspace_background(&self, index: usize) -> [f32; 4] {
+        self.workspace_settings
+            .iter()
+            .find(|ws| ws.index == index + 1)
+            .and_then(|ws| ws.background.as_deref())
+            .and_then(|color| hex_to_rgb(color).ok())
+            .map(|[r, g, b]| [r, g, b, 1.0])
+            .unwrap_or(DEFAULT_BACKGROUND)
+    }
+
+    /// Window rules matching a window, in config order.
+    pub fn window_rules<'a>(
+        &'a self,
+        app_id: &'a str,
+        title: &'a str,
+    ) -> impl Iterator<Item = &'a WindowRule> {
+        self.window_rules
+            .iter()
+            .filter(move |rule| rule.matches(app_id, title))
+    }
+
+    /// Opacity set by the first window rule matching `app_id` and `title`.
+    pub fn rule_opacity(&self, app_id: &str, title: &str) -> f32 {
+        self.window_rules(app_id, title)
+            .find_map(|rule| rule.opacity)
+            .unwrap_or(1.0)
+            .clamp(0.0, 1.0)
+    }
+}
+
+/// Settings of the workspace with the (1-based) `index`, e.g.
+/// `[[workspace]] index = 2, background = "#1E2A38"`.
+#[derive(Deserialize, Serialize, Clone)]
+pub struct WorkspaceSettings {
+    pub index: usize,
+    pub background: Option<String>,
+}
+
+/// Settings applied to windows whose app id (X11 class) and title match,
+/// e.g. `{ app_id = "pavucontrol", floating = true }`. Both patterns may
+/// use `*` for any run of characters, a missing one matches every window.
+#[derive(Deserialize, Serialize, Clone)]
+pub struct WindowRule {
+    pub app_id: Option<String>,
+    pub title: Option<String>,
+    pub opacity: Option<f32>,
+    /// Opens the window floating, or tiled unless the workspace floats.
+    pub floating: Option<bool>,
+    /// Workspace (1-based) the window opens on.
+    pub workspace: Option<usize>,
+    /// Size of the window while it floats.
+    pub size: Option<(i32, i32)>,
+    /// Location of the window relative to its output while it floats.
+    pub position: Option<(i32, i32)>,
+}
+
+impl WindowRule {
+    pub fn matches(&self, app_id: &str, title: &str) -> bool {
+        self.app_id
+            .as_deref()
+            .is_none_or(|pattern| glob_match(pattern, app_id))
+            && self
+                .title
+                .as_deref()
+                .is_none_or(|pattern| glob_match(pattern, title))
+    }
+}
+
+/// Matches `text` against `pattern`, where `*` stands for any run of characters.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+    let mut parts: Vec<&str> = parts.collect();
+    // Without a `*` the pattern has to match the whole text
+    let Some(last) = parts.pop() else {
+        return rest.is_empty();
+    };
+    for part in parts {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+/// A swipe with `fingers` fingers in `direction`, e.g.
+/// `{ fingers = 3, direction = "left", action = "nextworkspace" }`.
+#[derive(Deserialize, Serialize, Clone)]
+pub struct Gesture {
+    pub fingers: u32,
+    pub direction: Direction,
+    #[serde(flatten)]
+    pub action: Action,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum ModScrollAction {
+    Workspace,
+    None,
+}
+
+/// Where a newly mapped tiled window is inserted.
+#[derive(Deserialize, Serialize, PartialEq, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum NewWindowPosition {
+    #[default]
+    Master,
+    StackTop,
+    StackBottom,
+}
+
+impl NewWindowPosition {
+    pub fn next(self) -> Self {
+        match self {
+            NewWindowPosition::Master => NewWindowPosition::StackTop,
+            NewWindowPosition::StackTop => NewWindowPosition::StackBottom,
+            NewWindowPosition::StackBottom => NewWindowPosition::Master,
+        }
+    }
+}
+
+/// Whether override-redirect X11 windows (menus, tooltips, dmenu) get keyboard focus.
+#[derive(Deserialize, Serialize, PartialEq, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum OverrideRedirectFocus {
+    /// Menus and untyped windows get focus, tooltips and notifications don't.
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+/// Whether a newly mapped window takes keyboard focus.
+#[derive(Deserialize, Serialize, PartialEq, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum FocusNewWindows {
+    Always,
+    Never,
+    /// Focus unless the window opens on a workspace that isn't shown.
+    #[default]
+    Smart,
+}
+
+/// What focus does when the pointer is over no window.
+#[derive(Deserialize, Serialize, PartialEq, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum FocusOnEmpty {
+    /// The last focused window keeps the keyboard.
+    #[default]
+    Keep,
+    /// Nothing has keyboard focus.
+    Clear,
+}
+
+/// How a fullscreen window shares the screen with the other windows of its workspace.
+#[derive(Deserialize, Serialize, PartialEq, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum FullscreenBehavior {
+    /// Only the fullscreen window is shown and new windows don't take focus from it.
+    #[default]
+    Exclusive,
+    /// Floating windows and the focused window are shown on top of it.
+    Pinned,
+}
+
+/// Slide between workspaces when switching, e.g.
+/// `workspace_animation = { enabled = true, duration_ms = 200, direction = "horizontal" }`.
+#[derive(Deserialize, Serialize, Clone, Copy)]
+#[serde(default)]
+pub struct WorkspaceAnimation {
+    pub enabled: bool,
+    pub duration_ms: u64,
+    pub direction: SlideDirection,
+}
+
+impl Default for WorkspaceAnimation {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            duration_ms: 200,
+            direction: SlideDirection::Horizontal,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SlideDirection {
+    #[default]
+    Horizontal,
+    Vertical,
+}
+
+/// Whether Xwayland is started, only has an effect with the `xwayland` feature.
+#[derive(Deserialize, Serialize, PartialEq, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum XwaylandMode {
+    Disabled,
+    /// Started with the compositor.
+    #[default]
+    Eager,
+    /// Meant to start with the first X11 client, started like `Eager` for now:
+    /// `XWayland::spawn` binds the X11 sockets itself and runs the server right
+    /// away, there is no way to wait for a client on them first.
+    Lazy,
+}
+
+/// What happens to a toplevel that maps again after unmapping itself.
+#[derive(Deserialize, Serialize, PartialEq, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum UnmapBehavior {
+    /// Back on its workspace, with its mode and position.
+    #[default]
+    Restore,
+    /// Inserted like a new window, on the current workspace.
+    Reinsert,
+}
+
+#[derive(Deserialize, Serialize, Clone, Default)]
+pub struct InputDeviceConfig {
+    pub natural_scroll: Option<bool>,
+    pub accel_speed: Option<f64>,
+    pub accel_profile: Option<AccelProfile>,
+    pub tap: Option<bool>,
+    pub left_handed: Option<bool>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum AccelProfile {
+    Flat,
+    Adaptive,
+}
+
+/// Size limits for floating windows. Sizes given as a fraction are relative to
+/// the usable area of the output.
+#[derive(Deserialize, Serialize, Clone, Copy)]
+#[serde(default)]
+pub struct Floating {
+    /// Size new floating windows get instead of the one the client asks for.
+    pub default_size: Option<f32>,
+    pub min_size: (i32, i32),
+    pub max_size: f32,
+    /// Space kept free between a centered window and the output edges.
+    pub margin: i32,
+}
+
+impl Default for Floating {
+    fn default() -> Self {
+        Self {
+            default_size: None,
+            min_size: (200, 100),
+            max_size: 1.0,
+            margin: 0,
+        }
+    }
+}
+
+impl Floating {
+    /// Size of a floating window that asked for `requested` in `area`.
+    pub fn window_size(
+        &self,
+        area: Size<i32, Logical>,
+        requested: Size<i32, Logical>,
+    ) -> Size<i32, Logical> {
+        let fraction = |value: i32, fraction: f32| (value as f32 * fraction.clamp(0.0, 1.0)) as i32;
+        let (w, h) = match self.default_size {
+            Some(size) => (fraction(area.w, size), fraction(area.h, size)),
+            None => (requested.w, requested.h),
+        };
+        let max = (
+            fraction(area.w, self.max_size).max(1),
+            fraction(area.h, self.max_size).max(1),
+        );
+        (
+            w.max(self.min_size.0).min(max.0),
+            h.max(self.min_size.1).min(max.1),
+        )
+            .into()
+    }
+}
+
+/// Blur what is behind (translucent) windows of the listed apps.
+#[derive(Deserialize, Serialize, Clone)]
+pub struct Blur {
+    /// Spacing between blur samples in pixels.
+    pub radius: f32,
+    pub app_ids: Vec<String>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy)]
+pub struct DimInactive {
+    pub strength: f32,
 }
 
 #[derive(Deserialize, Serialize)]
 pub struct Border {
     pub thickness: i32,
-    pub gap: i32,
+    /// Space between the borders of two tiled windows.
+    #[serde(default = "default_gap_inner")]
+    pub gap_inner: i32,
+    /// Space between tiled windows and the edge of the output.
+    #[serde(default = "default_gap_outer")]
+    pub gap_outer: i32,
     pub active: String,
     pub end_active: Option<String>,
     pub inactive: String,
     pub end_inactive: Option<String>,
     pub angle: Option<f32>,
+    /// Border color of windows that didn't answer the last ping.
+    pub unresponsive: Option<String>,
+    /// Two-tone border: fixed colors for the band next to the window and the
+    /// outer band, either one keeps the active/inactive colors when unset.
+    pub inner_color: Option<String>,
+    pub outer_color: Option<String>,
+    /// Width of the outer band, half the thickness by default.
+    pub outer_width: Option<i32>,
+    /// Radius of the window corners, 0 keeps them square.
+    #[serde(default)]
+    pub corner_radius: i32,
 }
 
 #[derive(Deserialize, Serialize)]
@@ -180,10 +763,75 @@ pub struct OutputData {
     pub transform: Option<String>,
     pub workspaces: Option<Vec<u8>>,
     pub enabled: bool,
+    pub reserved_margins: Option<Margins>,
+    pub max_fps: Option<u32>,
 }
 
+/// Extra space kept free on each output edge, on top of the layer-shell exclusive zones.
+#[derive(Deserialize, Serialize, Clone, Copy, Default)]
+pub struct Margins {
+    pub top: i32,
+    pub bottom: i32,
+    pub left: i32,
+    pub right: i32,
+}
+
+impl Margins {
+    pub fn shrink(&self, area: Rectangle<i32, Logical>) -> Rectangle<i32, Logical> {
+        Rectangle::new(
+            (area.loc.x + self.left, area.loc.y + self.top).into(),
+            (
+                (area.size.w - self.left - self.right).max(0),
+                (area.size.h - self.top - self.bottom).max(0),
+            )
+                .into(),
+        )
+    }
+}
+
+/// Parses a key bind such as `Super+Shift+q`. Letters resolve to the lowercase
+/// keysym whatever their case, matching the unshifted syms of the pressed key.
 pub fn parse_keybind(keybind: &str) -> Option<(ModifiersState, Keysym)> {
-    let parts: Vec<&str> = keybind.split('+').map(str::trim).collect();
+    let (modifiers, key_part) = split_bind(keybind)?;
+
+    // convert key name -> keysym
+    let keysym = xkb::keysym_from_name(&key_part, xkb::KEYSYM_CASE_INSENSITIVE);
+    // Unknown names (and a bare `Super+`) would match any key without a sym
+    if keysym == Keysym::NoSymbol {
+        return None;
+    }
+
+    Some((modifiers, keysym))
+}
+
+/// Whether the `held` modifiers are exactly the ones of a bind, locks don't count.
+pub fn modifiers_match(held: &ModifiersState, bind: &ModifiersState) -> bool {
+    held.logo == bind.logo
+        && held.shift == bind.shift
+        && held.ctrl == bind.ctrl
+        && held.alt == bind.alt
+}
+
+/// Parses a mouse button bind such as `Super+btn_middle` into the modifiers
+/// and the button code.
+pub fn parse_buttonbind(bind: &str) -> Option<(ModifiersState, u32)> {
+    let (modifiers, button) = split_bind(bind)?;
+    let code = match button.as_str() {
+        "btn_left" => 0x110,
+        "btn_right" => 0x111,
+        "btn_middle" => 0x112,
+        "btn_side" => 0x113,
+        "btn_extra" => 0x114,
+        "btn_forward" => 0x115,
+        "btn_back" => 0x116,
+        _ => return None,
+    };
+    Some((modifiers, code))
+}
+
+/// Splits a bind into its modifiers and the lowercased last part.
+fn split_bind(bind: &str) -> Option<(ModifiersState, String)> {
+    let parts: Vec<&str> = bind.split('+').map(str::trim).collect();
     if parts.is_empty() {
         return None;
     }
@@ -202,8 +850,41 @@ pub fn parse_keybind(keybind: &str) -> Option<(ModifiersState, Keysym)> {
         }
     }
 
-    // convert key name -> keysym
-    let keysym = xkb::keysym_from_name(&key_part, xkb::KEYSYM_CASE_INSENSITIVE);
+    Some((modifiers, key_part))
+}
 
-    Some((modifiers, keysym))
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The default config, with `border` edited by `edit`.
+    fn config_text(edit: impl FnOnce(&mut toml::Table)) -> String {
+        let mut table: toml::Table =
+            toml::from_str(&toml::to_string(&Config::default()).unwrap()).unwrap();
+        let Some(toml::Value::Table(border)) = table.get_mut("border") else {
+            panic!("no border table");
+        };
+        edit(border);
+        toml::to_string(&table).unwrap()
+    }
+
+    #[test]
+    fn old_gap_sets_both_gaps() {
+        let text = config_text(|border| {
+            border.remove("gap_inner");
+            border.remove("gap_outer");
+            border.insert("gap".to_string(), toml::Value::Integer(5));
+        });
+        let config = Config::parse(&text).unwrap();
+        assert_eq!(config.border.gap_inner, 10);
+        assert_eq!(config.border.gap_outer, 5);
+
+        // The new keys win over the old one
+        let text = config_text(|border| {
+            border.insert("gap".to_string(), toml::Value::Integer(5));
+        });
+        let config = Config::parse(&text).unwrap();
+        assert_eq!(config.border.gap_inner, default_gap_inner());
+        assert_eq!(config.border.gap_outer, default_gap_outer());
+    }
 }
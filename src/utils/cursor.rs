@@ -1,42 +1,65 @@
-use std::io::Read;
+use std::time::Duration;
 
-use xcursor::{CursorTheme, parser::{Image, parse_xcursor}};
+use smithay::input::pointer::CursorIcon;
+use xcursor::{
+    parser::{parse_xcursor, Image},
+    CursorTheme,
+};
 
+/// One shape of an XCursor theme, at the size closest to the one asked for.
+/// Animated cursors have several frames, static ones a single one.
 pub struct Cursor {
-    icons: Vec<Image>,
-    size: u32,
+    pub frames: Vec<Image>,
 }
 
 impl Cursor {
-    pub fn load() -> Cursor {
-        let name = std::env::var("XCURSOR_THEME")
-            .ok()
-            .unwrap_or_else(|| "default".into());
+    /// Loads the `icon` cursor of `theme` for `size` pixels. Older themes
+    /// may only have it under one of its X11 names.
+    pub fn load(theme: &CursorTheme, icon: CursorIcon, size: u32) -> Result<Cursor, Error> {
+        let path = std::iter::once(icon.name())
+            .chain(icon.alt_names().iter().copied())
+            .find_map(|name| theme.load_icon(name))
+            .ok_or(Error::MissingShape(icon.name()))?;
+        let data = std::fs::read(path)?;
+        let images = parse_xcursor(&data).ok_or(Error::Parse)?;
 
-        let size = std::env::var("XCURSOR_SIZE")
-            .ok()
-            .and_then(|s| s.parse().ok())
-            .unwrap_or(24);
+        // Files hold every frame at every size they were drawn for
+        let nearest = images
+            .iter()
+            .map(|image| image.size)
+            .min_by_key(|candidate| candidate.abs_diff(size))
+            .ok_or(Error::Parse)?;
+        let frames = images
+            .into_iter()
+            .filter(|image| image.size == nearest)
+            .collect();
+        Ok(Cursor { frames })
+    }
+}
 
-        let theme = CursorTheme::load(&name);
-        let icons = load_icon(&theme).map;
-    
+/// Index of the frame shown at `time`, animations loop from the compositor
+/// start. `delays` are in milliseconds.
+pub fn frame_at(delays: &[u32], time: Duration) -> usize {
+    let cycle: u32 = delays.iter().sum();
+    if cycle == 0 {
+        return 0;
+    }
+    let mut offset = (time.as_millis() % cycle as u128) as u32;
+    for (index, delay) in delays.iter().enumerate() {
+        if offset < *delay {
+            return index;
+        }
+        offset -= delay;
+    }
+    0
 }
 
 #[derive(thiserror::Error, Debug)]
-enum Error {
-    #[error("Theme has no default cursor")]
-    NoDefaultCursor,
+pub enum Error {
+    #[error("Theme has no {0} cursor")]
+    MissingShape(&'static str),
     #[error("Error opening xcursor file: {0}")]
     File(#[from] std::io::Error),
     #[error("Failed to parse XCursor file")]
     Parse,
 }
-
-fn load_icon(theme: &CursorTheme) -> Result<Vec<Image>, Error> {
-    let icon_path = theme.load_icon("default").ok_or(Error::NoDefaultCursor)?;
-    let mut cursor_file = std::fs::File::open(icon_path)?;
-    let mut cursor_data = Vec::new();
-    cursor_file.read_to_end(&mut cursor_data)?;
-    parse_xcursor(&cursor_data).ok_or(Error::Parse)
-}
@@ -2,11 +2,14 @@ use std::cell::RefCell;
 
 use smithay::{
     desktop::{Window, WindowSurface},
-    input::pointer::{
-        AxisFrame, ButtonEvent, Focus, GestureHoldBeginEvent, GestureHoldEndEvent,
-        GesturePinchBeginEvent, GesturePinchEndEvent, GesturePinchUpdateEvent,
-        GestureSwipeBeginEvent, GestureSwipeEndEvent, GestureSwipeUpdateEvent, GrabStartData,
-        MotionEvent, PointerGrab, PointerInnerHandle, RelativeMotionEvent,
+    input::{
+        pointer::{
+            AxisFrame, ButtonEvent, Focus, GestureHoldBeginEvent, GestureHoldEndEvent,
+            GesturePinchBeginEvent, GesturePinchEndEvent, GesturePinchUpdateEvent,
+            GestureSwipeBeginEvent, GestureSwipeEndEvent, GestureSwipeUpdateEvent, GrabStartData,
+            MotionEvent, PointerGrab, PointerInnerHandle, RelativeMotionEvent,
+        },
+        SeatHandler,
     },
     reexports::{
         wayland_protocols::xdg::shell::server::xdg_toplevel::{self, ResizeEdge},
@@ -16,12 +19,90 @@ use smithay::{
     wayland::seat::WaylandFocus,
 };
 
-use crate::{state::State, utils::workspaces::WindowMode};
+use crate::{
+    state::{root_surface, State},
+    utils::{
+        titlebar::titlebar_height,
+        workspaces::{constrain_to_outputs, WindowMode},
+    },
+};
+
+/// Distance the pointer travels from where a drag began before the drag icon
+/// and the grabbing cursor show, or a moved window starts following it, so a
+/// click that starts a drag doesn't flicker.
+const DRAG_THRESHOLD: f64 = 8.0;
+
+/// A drag-and-drop started by a client.
+pub struct Drag {
+    pub origin: Point<f64, Logical>,
+    pub icon: Option<WlSurface>,
+    /// Offset the icon's commits moved it by, relative to the pointer.
+    pub icon_offset: Point<i32, Logical>,
+    /// Set once the pointer left the threshold around `origin`.
+    pub shown: bool,
+}
+
+impl State {
+    /// Shows the running drag once the pointer moved far enough.
+    pub fn update_drag(&mut self) {
+        let location = self.pointer_location;
+        if let Some(drag) = self.drag.as_mut().filter(|drag| !drag.shown) {
+            let delta = location - drag.origin;
+            drag.shown = delta.x.hypot(delta.y) >= DRAG_THRESHOLD;
+        }
+    }
+}
 
 pub struct MovePointerGrab {
     pub start_data: GrabStartData<State>,
     pub window: Window,
     pub start_loc: Point<i32, Logical>,
+    /// Pointer location the window moves relative to, where it crossed the threshold.
+    origin: Point<f64, Logical>,
+    moving: bool,
+}
+
+impl MovePointerGrab {
+    pub fn new(
+        start_data: GrabStartData<State>,
+        window: Window,
+        start_loc: Point<i32, Logical>,
+    ) -> Self {
+        Self {
+            origin: start_data.location,
+            start_data,
+            window,
+            start_loc,
+            moving: false,
+        }
+    }
+}
+
+/// Location of a window moved from `start_loc` by the pointer going from
+/// `origin` to `location`.
+pub fn moved_location(
+    start_loc: Point<i32, Logical>,
+    origin: Point<f64, Logical>,
+    location: Point<f64, Logical>,
+) -> Point<i32, Logical> {
+    (start_loc.to_f64() + (location - origin)).to_i32_round()
+}
+
+/// Forwards `event` and ends `grab` once the last held button is released,
+/// with the serial and time of that release.
+fn end_on_release<D, G>(
+    grab: &mut G,
+    data: &mut D,
+    handle: &mut PointerInnerHandle<'_, D>,
+    event: &ButtonEvent,
+) where
+    D: SeatHandler + 'static,
+    G: PointerGrab<D> + 'static,
+{
+    handle.button(data, event);
+    if handle.current_pressed().is_empty() {
+        handle.unset_grab(grab, data, event.serial, event.time, true);
+    }
 }
 
 impl State {
@@ -37,16 +118,27 @@ impl State {
             .find(|element| {
                 element
                     .wl_surface()
-                    .map(|s| &*s == &surface.0)
+                    .map(|s| *s == root_surface(&surface.0))
                     .unwrap_or(false)
             })
             .unwrap()
             .clone();
+        self.init_window_move_grab(window, Some(surface), button, serial);
+    }
 
+    /// Starts moving `window` with the pointer, `focus` is the surface the grab started on.
+    pub fn init_window_move_grab(
+        &mut self,
+        window: Window,
+        focus: Option<(WlSurface, Point<f64, Logical>)>,
+        button: u32,
+        serial: Serial,
+    ) {
+        let ws = self.workspaces.get_current_mut();
         tracing::info!("start reposition");
         let start_data = GrabStartData {
-            focus: Some(surface),
-            button: 272,
+            focus,
+            button,
             location: self.pointer_location,
         };
         let window_geo = match ws.space.element_geometry(&window) {
@@ -64,11 +156,7 @@ impl State {
 
         //ws.space.map_element(window.clone(), start_loc, false);
 
-        let grab = MovePointerGrab {
-            start_data,
-            window: window.clone(),
-            start_loc: window_geo.loc,
-        };
+        let grab = MovePointerGrab::new(start_data, window.clone(), window_geo.loc);
 
         let pointer = self.seat.get_pointer().unwrap();
         pointer.set_grab(self, grab, serial, Focus::Clear);
@@ -86,15 +174,35 @@ impl PointerGrab<State> for MovePointerGrab {
         // While the grab is active, no client has pointer focus
         handle.motion(data, None, event);
 
+        // The window stays put until the pointer left the threshold, and then
+        // follows from there instead of jumping to catch up
+        if !self.moving {
+            let delta = event.location - self.origin;
+            if delta.x.hypot(delta.y) < DRAG_THRESHOLD {
+                return;
+            }
+            self.moving = true;
+            self.origin = event.location;
+        }
+
         if let Some(window_data) = self.window.user_data().get::<RefCell<WindowMode>>() {
             match *window_data.borrow() {
                 WindowMode::Tiled => {}
                 WindowMode::Floating => {
                     let ws = data.workspaces.get_current_mut();
-                    let delta = event.location - self.start_data.location;
-                    let new_location = self.start_loc.to_f64() + delta;
+                    let mut new_location =
+                        moved_location(self.start_loc, self.origin, event.location);
+                    if data.config.constrain_floating {
+                        new_location = constrain_to_outputs(
+                            &ws.space,
+                            new_location,
+                            self.window.geometry().size,
+                            data.config.floating_visible_strip,
+                            titlebar_height(&self.window, data.config.titlebars),
+                        );
+                    }
                     ws.space
-                        .map_element(self.window.clone(), new_location.to_i32_round(), false);
+                        .map_element(self.window.clone(), new_location, false);
                 }
                 _ => {}
             }
@@ -117,13 +225,7 @@ impl PointerGrab<State> for MovePointerGrab {
         handle: &mut PointerInnerHandle<'_, State>,
         event: &ButtonEvent,
     ) {
-        handle.button(data, event);
-        if handle.current_pressed().is_empty() {
-            handle.unset_grab(self, data, event.serial, event.time, true);
-            if !self.window.alive() {
-                return;
-            }
-        }
+        end_on_release(self, data, handle, event);
     }
 
     fn axis(
@@ -289,7 +391,7 @@ impl State {
         let window = match ws.space.elements().find(|element| {
             element
                 .wl_surface()
-                .map(|s| &*s == &surface.0)
+                .map(|s| *s == root_surface(&surface.0))
                 .unwrap_or(false)
         }) {
             Some(w) => w.clone(),
@@ -298,7 +400,7 @@ impl State {
 
         let start_data = GrabStartData {
             focus: Some(surface),
-            button: button,
+            button,
             location: self.pointer_location,
         };
 
@@ -471,13 +573,7 @@ impl PointerGrab<State> for ResizePointerGrub {
         handle: &mut PointerInnerHandle<'_, State>,
         event: &ButtonEvent,
     ) {
-        handle.button(data, event);
-        if handle.current_pressed().is_empty() {
-            handle.unset_grab(self, data, event.serial, event.time, true);
-            if !self.window.alive() {
-                return;
-            }
-        }
+        end_on_release(self, data, handle, event);
     }
 
     fn axis(
@@ -571,3 +667,197 @@ impl PointerGrab<State> for ResizePointerGrub {
 
     fn unset(&mut self, _data: &mut State) {}
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use smithay::backend::input::ButtonState;
+    use smithay::input::{pointer::PointerHandle, SeatState};
+
+    use crate::SERIAL_COUNTER;
+
+    /// A seat without a display, `State` needs the DRM backend.
+    struct Headless {
+        seat_state: SeatState<Headless>,
+    }
+
+    impl SeatHandler for Headless {
+        type KeyboardFocus = WlSurface;
+        type PointerFocus = WlSurface;
+        type TouchFocus = WlSurface;
+
+        fn seat_state(&mut self) -> &mut SeatState<Self> {
+            &mut self.seat_state
+        }
+    }
+
+    /// Releases like the move and resize grabs, forwards everything else.
+    struct ReleaseGrab(GrabStartData<Headless>);
+
+    impl PointerGrab<Headless> for ReleaseGrab {
+        fn motion(
+            &mut self,
+            data: &mut Headless,
+            handle: &mut PointerInnerHandle<'_, Headless>,
+            _focus: Option<(WlSurface, Point<f64, Logical>)>,
+            event: &MotionEvent,
+        ) {
+            handle.motion(data, None, event);
+        }
+
+        fn relative_motion(
+            &mut self,
+            data: &mut Headless,
+            handle: &mut PointerInnerHandle<'_, Headless>,
+            focus: Option<(WlSurface, Point<f64, Logical>)>,
+            event: &RelativeMotionEvent,
+        ) {
+            handle.relative_motion(data, focus, event);
+        }
+
+        fn button(
+            &mut self,
+            data: &mut Headless,
+            handle: &mut PointerInnerHandle<'_, Headless>,
+            event: &ButtonEvent,
+        ) {
+            end_on_release(self, data, handle, event);
+        }
+
+        fn axis(
+            &mut self,
+            data: &mut Headless,
+            handle: &mut PointerInnerHandle<'_, Headless>,
+            details: AxisFrame,
+        ) {
+            handle.axis(data, details)
+        }
+
+        fn frame(&mut self, data: &mut Headless, handle: &mut PointerInnerHandle<'_, Headless>) {
+            handle.frame(data);
+        }
+
+        fn gesture_swipe_begin(
+            &mut self,
+            data: &mut Headless,
+            handle: &mut PointerInnerHandle<'_, Headless>,
+            event: &GestureSwipeBeginEvent,
+        ) {
+            handle.gesture_swipe_begin(data, event)
+        }
+
+        fn gesture_swipe_update(
+            &mut self,
+            data: &mut Headless,
+            handle: &mut PointerInnerHandle<'_, Headless>,
+            event: &GestureSwipeUpdateEvent,
+        ) {
+            handle.gesture_swipe_update(data, event)
+        }
+
+        fn gesture_swipe_end(
+            &mut self,
+            data: &mut Headless,
+            handle: &mut PointerInnerHandle<'_, Headless>,
+            event: &GestureSwipeEndEvent,
+        ) {
+            handle.gesture_swipe_end(data, event)
+        }
+
+        fn gesture_pinch_begin(
+            &mut self,
+            data: &mut Headless,
+            handle: &mut PointerInnerHandle<'_, Headless>,
+            event: &GesturePinchBeginEvent,
+        ) {
+            handle.gesture_pinch_begin(data, event)
+        }
+
+        fn gesture_pinch_update(
+            &mut self,
+            data: &mut Headless,
+            handle: &mut PointerInnerHandle<'_, Headless>,
+            event: &GesturePinchUpdateEvent,
+        ) {
+            handle.gesture_pinch_update(data, event)
+        }
+
+        fn gesture_pinch_end(
+            &mut self,
+            data: &mut Headless,
+            handle: &mut PointerInnerHandle<'_, Headless>,
+            event: &GesturePinchEndEvent,
+        ) {
+            handle.gesture_pinch_end(data, event)
+        }
+
+        fn gesture_hold_begin(
+            &mut self,
+            data: &mut Headless,
+            handle: &mut PointerInnerHandle<'_, Headless>,
+            event: &GestureHoldBeginEvent,
+        ) {
+            handle.gesture_hold_begin(data, event)
+        }
+
+        fn gesture_hold_end(
+            &mut self,
+            data: &mut Headless,
+            handle: &mut PointerInnerHandle<'_, Headless>,
+            event: &GestureHoldEndEvent,
+        ) {
+            handle.gesture_hold_end(data, event)
+        }
+
+        fn start_data(&self) -> &GrabStartData<Headless> {
+            &self.0
+        }
+
+        fn unset(&mut self, _data: &mut Headless) {}
+    }
+
+    fn button(pointer: &PointerHandle<Headless>, data: &mut Headless, state: ButtonState) {
+        pointer.button(
+            data,
+            &ButtonEvent {
+                serial: SERIAL_COUNTER.next_serial(),
+                time: 0,
+                button: 0x110,
+                state,
+            },
+        );
+    }
+
+    #[test]
+    fn grab_ends_on_release() {
+        let mut seat_state = SeatState::new();
+        let mut seat = seat_state.new_seat("seat0");
+        let pointer = seat.add_pointer();
+        let mut data = Headless { seat_state };
+
+        // Started by a press, like a move bind or a client's move request
+        button(&pointer, &mut data, ButtonState::Pressed);
+        let grab = ReleaseGrab(GrabStartData {
+            focus: None,
+            button: 0x110,
+            location: (0.0, 0.0).into(),
+        });
+        pointer.set_grab(&mut data, grab, SERIAL_COUNTER.next_serial(), Focus::Clear);
+        assert!(pointer.is_grabbed());
+
+        button(&pointer, &mut data, ButtonState::Released);
+        assert!(!pointer.is_grabbed());
+    }
+
+    #[test]
+    fn window_follows_pointer_delta() {
+        let start = Point::from((100, 50));
+        let origin = Point::from((210.0, 120.0));
+        let location = Point::from((250.0, 90.0));
+        assert_eq!(
+            moved_location(start, origin, location),
+            Point::from((140, 20))
+        );
+        assert_eq!(moved_location(start, origin, origin), start);
+    }
+}
@@ -0,0 +1,120 @@
+use std::time::{Duration, Instant};
+
+use smithay::{
+    backend::session::Session,
+    delegate_idle_inhibit, delegate_idle_notify,
+    desktop::{layer_map_for_output, WindowSurfaceType},
+    reexports::wayland_server::{protocol::wl_surface::WlSurface, Resource},
+    wayland::{
+        idle_inhibit::IdleInhibitHandler,
+        idle_notify::{IdleNotifierHandler, IdleNotifierState},
+    },
+};
+
+use crate::state::{root_surface, State};
+
+/// Longest wait between two looks at the inhibitors and the timeout.
+pub const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Idle tracking for `idle_timeout`, clients get theirs through ext-idle-notify.
+pub struct Idle {
+    last_activity: Instant,
+    /// Surfaces asking to keep the screen on, counted while they are shown.
+    inhibitors: Vec<WlSurface>,
+    /// Whether the outputs were turned off for being idle.
+    blanked: bool,
+}
+
+impl Idle {
+    pub fn new() -> Self {
+        Self {
+            last_activity: Instant::now(),
+            inhibitors: Vec::new(),
+            blanked: false,
+        }
+    }
+}
+
+impl State {
+    /// Restarts the idle timers after user input.
+    pub fn notify_activity(&mut self) {
+        self.idle_notifier_state.notify_activity(&self.seat);
+        self.idle.last_activity = Instant::now();
+        if self.idle.blanked {
+            self.idle.blanked = false;
+            self.set_output_power(true);
+        }
+    }
+
+    /// Turns the outputs off until the next input.
+    pub fn blank_outputs(&mut self) {
+        self.idle.blanked = true;
+        self.set_output_power(false);
+    }
+
+    /// Whether an inhibiting surface is on screen, a window of the current
+    /// workspace or a layer surface.
+    fn idle_inhibited(&mut self) -> bool {
+        self.idle.inhibitors.retain(|surface| surface.is_alive());
+        let ws = self.workspaces.get_current();
+        self.idle.inhibitors.iter().map(root_surface).any(|root| {
+            self.window_for_surface(&root).is_some()
+                || ws.space.outputs().any(|output| {
+                    layer_map_for_output(output)
+                        .layer_for_surface(&root, WindowSurfaceType::TOPLEVEL)
+                        .is_some()
+                })
+        })
+    }
+
+    /// Passes the inhibitors on to ext-idle-notify and turns the outputs off
+    /// once `idle_timeout` passed. Returns the time until the next check.
+    pub fn check_idle(&mut self) -> Duration {
+        let inhibited = self.idle_inhibited();
+        self.idle_notifier_state.set_is_inhibited(inhibited);
+
+        // Being switched away isn't idling here, and the outputs aren't ours then
+        if inhibited || !self.backend_data.session.is_active() {
+            self.idle.last_activity = Instant::now();
+        }
+        if self.config.idle_timeout == 0 || self.idle.blanked {
+            return IDLE_CHECK_INTERVAL;
+        }
+
+        let timeout = Duration::from_secs(self.config.idle_timeout);
+        match timeout.checked_sub(self.idle.last_activity.elapsed()) {
+            Some(remaining) if !remaining.is_zero() => remaining.min(IDLE_CHECK_INTERVAL),
+            _ => {
+                tracing::info!("Idle for {}s, turning the outputs off", timeout.as_secs());
+                self.blank_outputs();
+                IDLE_CHECK_INTERVAL
+            }
+        }
+    }
+}
+
+impl IdleNotifierHandler for State {
+    fn idle_notifier_state(&mut self) -> &mut IdleNotifierState<Self> {
+        &mut self.idle_notifier_state
+    }
+}
+
+delegate_idle_notify!(State);
+
+impl IdleInhibitHandler for State {
+    fn inhibit(&mut self, surface: WlSurface) {
+        self.idle.inhibitors.push(surface);
+        let inhibited = self.idle_inhibited();
+        self.idle_notifier_state.set_is_inhibited(inhibited);
+    }
+
+    fn uninhibit(&mut self, surface: WlSurface) {
+        self.idle
+            .inhibitors
+            .retain(|inhibitor| *inhibitor != surface);
+        let inhibited = self.idle_inhibited();
+        self.idle_notifier_state.set_is_inhibited(inhibited);
+    }
+}
+
+delegate_idle_inhibit!(State);
@@ -0,0 +1,364 @@
+use std::{
+    io::{Read, Write},
+    os::unix::net::{UnixListener, UnixStream},
+    path::PathBuf,
+};
+
+use serde::{Deserialize, Serialize};
+use smithay::{
+    desktop::{Window, WindowSurface},
+    reexports::calloop::{generic::Generic, Interest, Mode, PostAction},
+    wayland::seat::WaylandFocus,
+};
+
+use crate::{
+    state::State,
+    utils::{
+        action::Action,
+        workspaces::{window_app_id, window_id, window_title},
+    },
+};
+
+/// Longest request a client may send, longer ones are refused.
+const MAX_IPC_REQUEST: usize = 4096;
+
+/// A request, one JSON object per connection terminated by a newline, e.g.
+/// `{"command": "dispatch", "action": {"action": "workspace", "index": 2}}`.
+#[derive(Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum Request {
+    /// Runs an action, written like a key bind in the config.
+    Dispatch {
+        action: Action,
+    },
+    #[serde(alias = "list_workspaces")]
+    Workspaces,
+    #[serde(alias = "list_windows")]
+    Windows,
+    FocusWindow {
+        id: u64,
+    },
+    CloseWindow {
+        id: u64,
+    },
+    ToggleOutput {
+        name: String,
+    },
+    Dpms {
+        on: bool,
+    },
+    Redraw,
+    KeyboardLayout,
+    Gpus,
+    Stats,
+    /// Device or render node of the new primary gpu, e.g. `renderD129`.
+    PrimaryGpu {
+        name: String,
+    },
+    /// Skips confirm_exit, meant for the confirmation command itself.
+    Exit,
+}
+
+/// The answer to a request, `{"ok": ...}` or `{"error": "..."}` on one line.
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Reply {
+    Ok(serde_json::Value),
+    Error(String),
+}
+
+impl Reply {
+    fn into_line(self) -> Vec<u8> {
+        let mut line = serde_json::to_vec(&self)
+            .unwrap_or_else(|err| format!("{{\"error\":{:?}}}", err.to_string()).into_bytes());
+        line.push(b'\n');
+        line
+    }
+}
+
+#[derive(Serialize)]
+struct WorkspaceInfo {
+    index: usize,
+    windows: usize,
+    layout: &'static str,
+    active: bool,
+}
+
+#[derive(Serialize)]
+struct WindowInfo {
+    id: u64,
+    workspace: usize,
+    app_id: String,
+    title: String,
+}
+
+#[derive(Serialize)]
+struct LayoutInfo {
+    index: usize,
+    name: String,
+}
+
+/// Binds the control socket and registers it with the event loop.
+pub fn init_ipc(state: &mut State) -> Option<PathBuf> {
+    let runtime_dir = std::env::var("XDG_RUNTIME_DIR").ok()?;
+    let path = PathBuf::from(runtime_dir).join(format!(
+        "kowinwm.{}.sock",
+        state.socket_name.to_string_lossy()
+    ));
+    std::fs::remove_file(&path).ok();
+
+    let listener = match UnixListener::bind(&path) {
+        Ok(listener) => listener,
+        Err(err) => {
+            tracing::warn!("Failed to bind ipc socket {}: {}", path.display(), err);
+            return None;
+        }
+    };
+    listener.set_nonblocking(true).ok()?;
+
+    state
+        .loop_handle
+        .insert_source(
+            Generic::new(listener, Interest::READ, Mode::Level),
+            |_, listener, state| {
+                while let Ok((stream, _)) = listener.accept() {
+                    state.add_ipc_client(stream);
+                }
+                Ok(PostAction::Continue)
+            },
+        )
+        .expect("Failed to init the ipc event source.");
+
+    // SAFETY: All set_vars occur on the event loop thread
+    unsafe {
+        std::env::set_var("KOWINWM_SOCKET", &path);
+    }
+    tracing::info!("Listening for ipc on {}", path.display());
+    Some(path)
+}
+
+impl State {
+    fn add_ipc_client(&mut self, stream: UnixStream) {
+        if stream.set_nonblocking(true).is_err() {
+            return;
+        }
+        let mut buffer = Vec::new();
+        let res = self.loop_handle.insert_source(
+            Generic::new(stream, Interest::READ, Mode::Level),
+            move |_, stream, state| {
+                let mut chunk = [0u8; 1024];
+                match (&**stream).read(&mut chunk) {
+                    Ok(0) => return Ok(PostAction::Remove),
+                    Ok(n) => buffer.extend_from_slice(&chunk[..n]),
+                    Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => {
+                        return Ok(PostAction::Continue)
+                    }
+                    Err(_) => return Ok(PostAction::Remove),
+                }
+                let Some(end) = buffer.iter().position(|b| *b == b'\n') else {
+                    if buffer.len() > MAX_IPC_REQUEST {
+                        let reply =
+                            Reply::Error(format!("request longer than {MAX_IPC_REQUEST} bytes"));
+                        if let Ok(stream) = stream.try_clone() {
+                            state.send_ipc_reply(stream, reply.into_line());
+                        }
+                        return Ok(PostAction::Remove);
+                    }
+                    return Ok(PostAction::Continue);
+                };
+
+                let reply = state.handle_ipc_command(&buffer[..end]);
+                match stream.try_clone() {
+                    Ok(stream) => state.send_ipc_reply(stream, reply.into_line()),
+                    Err(err) => tracing::warn!("Failed to reply to ipc client: {}", err),
+                }
+                Ok(PostAction::Remove)
+            },
+        );
+        if let Err(err) = res {
+            tracing::warn!("Failed to add ipc client: {}", err);
+        }
+    }
+
+    /// Writes `reply` whenever the client is ready to read it, so a client
+    /// that stops reading can't stall the compositor.
+    fn send_ipc_reply(&mut self, stream: UnixStream, reply: Vec<u8>) {
+        let mut written = 0;
+        let res = self.loop_handle.insert_source(
+            Generic::new(stream, Interest::WRITE, Mode::Level),
+            move |_, stream, _| loop {
+                match (&**stream).write(&reply[written..]) {
+                    Ok(0) => return Ok(PostAction::Remove),
+                    Ok(n) => {
+                        written += n;
+                        if written == reply.len() {
+                            return Ok(PostAction::Remove);
+                        }
+                    }
+                    Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => {
+                        return Ok(PostAction::Continue)
+                    }
+                    Err(_) => return Ok(PostAction::Remove),
+                }
+            },
+        );
+        if let Err(err) = res {
+            tracing::warn!("Failed to add ipc reply: {}", err);
+        }
+    }
+
+    pub fn handle_ipc_command(&mut self, request: &[u8]) -> Reply {
+        let result = serde_json::from_slice::<Request>(request)
+            .map_err(|err| format!("invalid request: {err}"))
+            .and_then(|request| self.handle_ipc_request(request));
+        match result {
+            Ok(value) => Reply::Ok(value),
+            Err(err) => Reply::Error(err),
+        }
+    }
+
+    fn handle_ipc_request(&mut self, request: Request) -> Result<serde_json::Value, String> {
+        match request {
+            Request::Dispatch { action } => {
+                action.execute(self);
+                Ok(serde_json::Value::Null)
+            }
+            Request::Workspaces => to_json(self.ipc_workspaces()),
+            Request::Windows => to_json(self.ipc_windows()),
+            Request::FocusWindow { id } => self.ipc_with_window(id, |state, ws_index, window| {
+                Action::Workspace {
+                    index: ws_index + 1,
+                }
+                .execute(state);
+                let ws = state.workspaces.get_current_mut();
+                ws.space.raise_element(&window, true);
+                ws.active_window = Some(window.clone());
+                state.set_keyboard_focus(window.wl_surface().map(|s| s.as_ref().clone()));
+            }),
+            Request::CloseWindow { id } => {
+                self.ipc_with_window(id, |_, _, window| match window.underlying_surface() {
+                    WindowSurface::Wayland(xdg) => xdg.send_close(),
+                    #[cfg(feature = "xwayland")]
+                    WindowSurface::X11(x11) => {
+                        x11.close().ok();
+                    }
+                })
+            }
+            Request::ToggleOutput { name } => {
+                self.toggle_output(&name).map(|_| serde_json::Value::Null)
+            }
+            Request::Dpms { on } => {
+                self.set_output_power(on);
+                Ok(serde_json::Value::Null)
+            }
+            Request::Redraw => {
+                self.redraw_all();
+                Ok(serde_json::Value::Null)
+            }
+            Request::KeyboardLayout => {
+                let (index, name) = self
+                    .keyboard_layout()
+                    .ok_or_else(|| "no keyboard".to_string())?;
+                to_json(LayoutInfo { index, name })
+            }
+            Request::Gpus => to_json(self.list_gpus()),
+            Request::Stats => to_json(self.render_stats.take_report()),
+            Request::PrimaryGpu { name } => {
+                self.set_primary_gpu(&name).map(|_| serde_json::Value::Null)
+            }
+            Request::Exit => {
+                self.loop_signal.stop();
+                Ok(serde_json::Value::Null)
+            }
+        }
+    }
+
+    fn ipc_windows(&self) -> Vec<WindowInfo> {
+        let mut windows = Vec::new();
+        for (index, ws) in self.workspaces.workspaces.iter().enumerate() {
+            for window in ws.space.elements() {
+                windows.push(WindowInfo {
+                    id: window_id(window),
+                    workspace: index + 1,
+                    app_id: window_app_id(window),
+                    title: window_title(window),
+                });
+            }
+        }
+        windows
+    }
+
+    fn ipc_workspaces(&self) -> Vec<WorkspaceInfo> {
+        self.workspaces
+            .workspaces
+            .iter()
+            .enumerate()
+            .map(|(index, ws)| WorkspaceInfo {
+                index: index + 1,
+                windows: ws.space.elements().count(),
+                layout: ws.layout.name(),
+                active: index == self.workspaces.active_ws(),
+            })
+            .collect()
+    }
+
+    fn ipc_with_window<F>(&mut self, id: u64, f: F) -> Result<serde_json::Value, String>
+    where
+        F: FnOnce(&mut State, usize, Window),
+    {
+        let (ws_index, window) = self
+            .workspaces
+            .workspaces
+            .iter()
+            .enumerate()
+            .find_map(|(index, ws)| {
+                ws.space
+                    .elements()
+                    .find(|w| window_id(w) == id)
+                    .map(|w| (index, w.clone()))
+            })
+            .ok_or_else(|| format!("no window with id {id}"))?;
+        f(self, ws_index, window);
+        Ok(serde_json::Value::Null)
+    }
+}
+
+fn to_json(value: impl Serialize) -> Result<serde_json::Value, String> {
+    serde_json::to_value(value).map_err(|err| err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requests_are_json() {
+        let request: Request = serde_json::from_str(
+            r#"{"command": "dispatch", "action": {"action": "workspace", "index": 2}}"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            request,
+            Request::Dispatch {
+                action: Action::Workspace { index: 2 }
+            }
+        ));
+        let request: Request = serde_json::from_str(r#"{"command": "windows"}"#).unwrap();
+        assert!(matches!(request, Request::Windows));
+        let request: Request = serde_json::from_str(r#"{"command": "list_workspaces"}"#).unwrap();
+        assert!(matches!(request, Request::Workspaces));
+        assert!(serde_json::from_str::<Request>(r#"{"command": "focus_window"}"#).is_err());
+    }
+
+    #[test]
+    fn replies_are_json_lines() {
+        assert_eq!(
+            Reply::Ok(serde_json::Value::Null).into_line(),
+            b"{\"ok\":null}\n"
+        );
+        assert_eq!(
+            Reply::Error("no keyboard".to_string()).into_line(),
+            b"{\"error\":\"no keyboard\"}\n"
+        );
+    }
+}
@@ -0,0 +1,167 @@
+use std::{collections::BTreeMap, os::unix::fs::PermissionsExt, path::PathBuf};
+
+use smithay::input::keyboard::Keysym;
+
+use crate::utils::{
+    action::Action,
+    overlay::{Overlay, OverlayResponse},
+};
+
+/// Number of results shown below the prompt.
+pub const MAX_RESULTS: usize = 8;
+
+pub struct LauncherEntry {
+    pub name: String,
+    pub command: String,
+}
+
+/// Built-in launcher overlay, filters `$PATH` executables and desktop entries.
+pub struct Launcher {
+    pub query: String,
+    entries: Vec<LauncherEntry>,
+    selected: usize,
+}
+
+impl Launcher {
+    pub fn new() -> Self {
+        let mut entries = BTreeMap::new();
+        for dir in std::env::var("PATH").unwrap_or_default().split(':') {
+            let Ok(read_dir) = std::fs::read_dir(dir) else {
+                continue;
+            };
+            for entry in read_dir.flatten() {
+                let executable = entry
+                    .metadata()
+                    .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+                    .unwrap_or(false);
+                if executable {
+                    let name = entry.file_name().to_string_lossy().into_owned();
+                    entries.insert(name.clone(), name);
+                }
+            }
+        }
+        for dir in application_dirs() {
+            let Ok(read_dir) = std::fs::read_dir(dir) else {
+                continue;
+            };
+            for entry in read_dir.flatten() {
+                if entry.path().extension().is_some_and(|ext| ext == "desktop") {
+                    if let Some((name, command)) = parse_desktop_entry(&entry.path()) {
+                        entries.insert(name, command);
+                    }
+                }
+            }
+        }
+
+        Self {
+            query: String::new(),
+            entries: entries
+                .into_iter()
+                .map(|(name, command)| LauncherEntry { name, command })
+                .collect(),
+            selected: 0,
+        }
+    }
+
+    /// Entries matching the query, prefix matches first.
+    pub fn matches(&self) -> Vec<&LauncherEntry> {
+        let query = self.query.to_lowercase();
+        let mut matches: Vec<&LauncherEntry> = self
+            .entries
+            .iter()
+            .filter(|e| e.name.to_lowercase().contains(&query))
+            .collect();
+        matches.sort_by_key(|e| !e.name.to_lowercase().starts_with(&query));
+        matches.truncate(MAX_RESULTS);
+        matches
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected.min(self.matches().len().saturating_sub(1))
+    }
+}
+
+fn application_dirs() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    if let Ok(home) = std::env::var("HOME") {
+        dirs.push(PathBuf::from(home).join(".local/share/applications"));
+    }
+    let data_dirs =
+        std::env::var("XDG_DATA_DIRS").unwrap_or("/usr/local/share:/usr/share".to_string());
+    for dir in data_dirs.split(':') {
+        dirs.push(PathBuf::from(dir).join("applications"));
+    }
+    dirs
+}
+
+fn parse_desktop_entry(path: &PathBuf) -> Option<(String, String)> {
+    let data = std::fs::read_to_string(path).ok()?;
+    let mut name = None;
+    let mut exec = None;
+    let mut in_entry = false;
+    for line in data.lines() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            in_entry = line == "[Desktop Entry]";
+            continue;
+        }
+        if !in_entry {
+            continue;
+        }
+        if line == "NoDisplay=true" || line == "Hidden=true" {
+            return None;
+        }
+        if let Some(value) = line.strip_prefix("Name=") {
+            name.get_or_insert(value.to_string());
+        } else if let Some(value) = line.strip_prefix("Exec=") {
+            // Drop field codes like %f and %U, we never pass files
+            let command: Vec<&str> = value
+                .split_whitespace()
+                .filter(|arg| !arg.starts_with('%'))
+                .collect();
+            exec.get_or_insert(command.join(" "));
+        }
+    }
+    Some((name?, exec?))
+}
+
+impl Overlay for Launcher {
+    fn key(&mut self, sym: Keysym) -> OverlayResponse {
+        match sym {
+            Keysym::Return | Keysym::KP_Enter => {
+                return match self.matches().get(self.selected()) {
+                    Some(entry) => OverlayResponse::Run(Action::Exec {
+                        command: entry.command.clone(),
+                    }),
+                    None => OverlayResponse::Close,
+                };
+            }
+            Keysym::BackSpace => {
+                self.query.pop();
+                self.selected = 0;
+            }
+            Keysym::Up => self.selected = self.selected().saturating_sub(1),
+            Keysym::Down | Keysym::Tab => self.selected = self.selected() + 1,
+            _ => {
+                if let Some(c) = sym.key_char().filter(|c| !c.is_control()) {
+                    self.query.push(c);
+                    self.selected = 0;
+                }
+            }
+        }
+        OverlayResponse::Keep
+    }
+
+    /// The prompt, then the results with the selected one highlighted.
+    fn lines(&self) -> Vec<(String, bool)> {
+        let selected = self.selected();
+        std::iter::once((format!("> {}", self.query), false))
+            .chain(
+                self.matches()
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, entry)| (entry.name.clone(), index == selected)),
+            )
+            .collect()
+    }
+}
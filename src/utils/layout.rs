@@ -1,11 +1,38 @@
 use smithay::{
     desktop::{space::SpaceElement, Window},
-    utils::{Logical, Rectangle},
+    utils::{Logical, Point, Rectangle},
 };
 
+use crate::utils::render::text::LINE_HEIGHT;
+
 pub enum LayoutState {
     Floating,
     MasterStack(MasterStack),
+    Tabbed(Tabbed),
+    /// Every window takes the whole area, the focused one on top.
+    Monocle,
+    /// Rows of `ceil(sqrt(n))` columns.
+    Grid,
+    /// Side by side, equal widths.
+    Columns,
+}
+
+impl LayoutState {
+    /// Whether only the topmost tiled window is visible.
+    pub fn is_stacked(&self) -> bool {
+        matches!(self, LayoutState::Tabbed(_) | LayoutState::Monocle)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            LayoutState::Floating => "floating",
+            LayoutState::MasterStack(_) => "masterstack",
+            LayoutState::Tabbed(_) => "tabbed",
+            LayoutState::Monocle => "monocle",
+            LayoutState::Grid => "grid",
+            LayoutState::Columns => "columns",
+        }
+    }
 }
 
 impl Default for LayoutState {
@@ -35,21 +62,97 @@ impl LayoutBehavior for LayoutState {
                 vec![]
             }
             LayoutState::MasterStack(layout) => layout.placement(windows, area),
+            LayoutState::Tabbed(layout) => layout.placement(windows, area),
+            LayoutState::Monocle => windows
+                .map(|window| Placement {
+                    window,
+                    geometry: area,
+                })
+                .collect(),
+            LayoutState::Grid => grid_placement(windows, area),
+            LayoutState::Columns => columns_placement(windows, area),
         }
     }
 }
 
+fn grid_placement<'a, I>(windows: I, area: Rectangle<i32, Logical>) -> Vec<Placement<'a>>
+where
+    I: Iterator<Item = &'a Window> + ExactSizeIterator,
+{
+    let count = windows.len() as i32;
+    if count == 0 {
+        return vec![];
+    }
+    let columns = (count as f64).sqrt().ceil() as i32;
+    let rows = (count + columns - 1) / columns;
+    let height = area.size.h / rows;
+
+    windows
+        .enumerate()
+        .map(|(i, window)| {
+            let (row, column) = (i as i32 / columns, i as i32 % columns);
+            // The last row may be short, its windows share the full width
+            let in_row = columns.min(count - row * columns);
+            let width = area.size.w / in_row;
+            let geometry = Rectangle::new(
+                (area.loc.x + width * column, area.loc.y + height * row).into(),
+                (width, height).into(),
+            );
+            Placement { window, geometry }
+        })
+        .collect()
+}
+
+fn columns_placement<'a, I>(windows: I, area: Rectangle<i32, Logical>) -> Vec<Placement<'a>>
+where
+    I: Iterator<Item = &'a Window> + ExactSizeIterator,
+{
+    let width = area.size.w / windows.len().max(1) as i32;
+    windows
+        .enumerate()
+        .map(|(i, window)| {
+            let geometry = Rectangle::new(
+                (area.loc.x + width * i as i32, area.loc.y).into(),
+                (width, area.size.h).into(),
+            );
+            Placement { window, geometry }
+        })
+        .collect()
+}
+
+/// Bounds of the master area, as a fraction of the layout width.
+const MASTER_SIZE_RANGE: (f32, f32) = (0.1, 0.9);
+
 pub struct MasterStack {
     master_size: f32,
     windows: Vec<Window>,
 }
-impl Default for MasterStack {
-    fn default() -> Self {
+
+impl MasterStack {
+    pub fn new(master_size: f32) -> Self {
         Self {
-            master_size: 0.5,
+            master_size: clamp_master_size(master_size),
             windows: vec![],
         }
     }
+
+    pub fn master_size(&self) -> f32 {
+        self.master_size
+    }
+
+    /// Grows (or shrinks, for a negative `delta`) the master area.
+    pub fn resize_master(&mut self, delta: f32) {
+        self.master_size = clamp_master_size(self.master_size + delta);
+    }
+}
+impl Default for MasterStack {
+    fn default() -> Self {
+        Self::new(0.5)
+    }
+}
+
+pub fn clamp_master_size(size: f32) -> f32 {
+    size.clamp(MASTER_SIZE_RANGE.0, MASTER_SIZE_RANGE.1)
 }
 impl LayoutBehavior for MasterStack {
     fn placement<'a, I>(&mut self, windows: I, area: Rectangle<i32, Logical>) -> Vec<Placement<'a>>
@@ -59,7 +162,8 @@ impl LayoutBehavior for MasterStack {
         let mut result = Vec::new();
         let count = windows.len() as i32;
 
-        let half_width = area.size.w / 2;
+        let master_width = (area.size.w as f32 * self.master_size) as i32;
+        let stack_width = area.size.w - master_width;
         let stack_height = if count > 1 {
             area.size.h / (count - 1)
         } else {
@@ -73,14 +177,14 @@ impl LayoutBehavior for MasterStack {
             let mut height = area.size.h;
 
             if count > 1 {
-                width = half_width;
+                width = master_width;
             }
 
             // stack windows
             if i > 0 {
-                x = area.loc.x + half_width;
+                x = area.loc.x + master_width;
                 y = area.loc.y + stack_height * (i as i32 - 1);
-                width = half_width;
+                width = stack_width;
                 height = stack_height;
             }
 
@@ -92,3 +196,59 @@ impl LayoutBehavior for MasterStack {
         result
     }
 }
+
+pub const TAB_BAR_HEIGHT: i32 = LINE_HEIGHT + 4;
+
+/// All windows share one rectangle below a tab bar; only the topmost one is visible.
+#[derive(Default)]
+pub struct Tabbed {
+    /// Windows in tab order (order of insertion).
+    pub windows: Vec<Window>,
+    pub bar: Rectangle<i32, Logical>,
+}
+
+impl Tabbed {
+    pub fn tabs(&self) -> impl Iterator<Item = (&Window, Rectangle<i32, Logical>)> {
+        let count = self.windows.len().max(1) as i32;
+        let width = self.bar.size.w / count;
+        self.windows.iter().enumerate().map(move |(i, window)| {
+            let loc = (self.bar.loc.x + width * i as i32, self.bar.loc.y);
+            (
+                window,
+                Rectangle::new(loc.into(), (width, self.bar.size.h).into()),
+            )
+        })
+    }
+
+    pub fn tab_at(&self, point: Point<f64, Logical>) -> Option<&Window> {
+        self.tabs()
+            .find(|(_, geo)| geo.to_f64().contains(point))
+            .map(|(window, _)| window)
+    }
+}
+
+impl LayoutBehavior for Tabbed {
+    fn placement<'a, I>(&mut self, windows: I, area: Rectangle<i32, Logical>) -> Vec<Placement<'a>>
+    where
+        I: Iterator<Item = &'a Window> + ExactSizeIterator,
+    {
+        let windows: Vec<&Window> = windows.collect();
+        self.windows.retain(|w| windows.contains(&w));
+        for window in &windows {
+            if !self.windows.contains(window) {
+                self.windows.push((*window).clone());
+            }
+        }
+
+        self.bar = Rectangle::new(area.loc, (area.size.w, TAB_BAR_HEIGHT).into());
+        let geometry = Rectangle::new(
+            (area.loc.x, area.loc.y + TAB_BAR_HEIGHT).into(),
+            (area.size.w, area.size.h - TAB_BAR_HEIGHT).into(),
+        );
+
+        windows
+            .into_iter()
+            .map(|window| Placement { window, geometry })
+            .collect()
+    }
+}
@@ -0,0 +1,173 @@
+use smithay::{
+    delegate_session_lock,
+    output::Output,
+    reexports::wayland_server::{
+        protocol::{wl_output::WlOutput, wl_surface::WlSurface},
+        Resource,
+    },
+    utils::{Logical, Point, Size},
+    wayland::session_lock::{
+        LockSurface, SessionLockHandler, SessionLockManagerState, SessionLocker,
+    },
+};
+
+use crate::state::{root_surface, State};
+
+/// A locked session. It stays locked, with blank outputs, until the client
+/// unlocks it, even when that client dies.
+pub struct SessionLock {
+    /// Confirmation held back until every output showed a locked frame.
+    locker: Option<SessionLocker>,
+    /// Outputs that showed a locked frame since the lock.
+    shown: Vec<Output>,
+    surfaces: Vec<(Output, LockSurface)>,
+}
+
+impl SessionLock {
+    /// The lock surface covering `output`, if its client made one.
+    pub fn surface(&self, output: &Output) -> Option<&LockSurface> {
+        self.surfaces
+            .iter()
+            .find(|(o, surface)| o == output && surface.wl_surface().is_alive())
+            .map(|(_, surface)| surface)
+    }
+
+    fn owns(&self, surface: &WlSurface) -> bool {
+        let root = root_surface(surface);
+        self.surfaces
+            .iter()
+            .any(|(_, lock_surface)| *lock_surface.wl_surface() == root)
+    }
+}
+
+impl State {
+    pub fn is_locked(&self) -> bool {
+        self.session_lock.is_some()
+    }
+
+    /// The lock surface under the pointer, relative to its output.
+    pub fn lock_surface_under(&self) -> Option<(WlSurface, Point<f64, Logical>)> {
+        let lock = self.session_lock.as_ref()?;
+        let output = self.pointer_output()?;
+        let loc = self
+            .workspaces
+            .get_current()
+            .space
+            .output_geometry(&output)?
+            .loc;
+        let surface = lock.surface(&output)?;
+        Some((surface.wl_surface().clone(), loc.to_f64()))
+    }
+
+    /// The lock surface that should hold the keyboard, `requested` when it is one.
+    pub fn lock_focus(&self, requested: Option<WlSurface>) -> Option<WlSurface> {
+        let lock = self.session_lock.as_ref()?;
+        if let Some(requested) = requested.filter(|surface| lock.owns(surface)) {
+            return Some(requested);
+        }
+        self.pointer_output()
+            .and_then(|output| lock.surface(&output))
+            .or_else(|| {
+                lock.surfaces
+                    .iter()
+                    .map(|(_, surface)| surface)
+                    .find(|surface| surface.wl_surface().is_alive())
+            })
+            .map(|surface| surface.wl_surface().clone())
+    }
+
+    /// Notes that `output` showed a locked frame. The client learns that the
+    /// session is locked once all powered outputs did.
+    pub fn lock_frame_shown(&mut self, output: &Output) {
+        let Some(lock) = self.session_lock.as_mut() else {
+            return;
+        };
+        if lock.locker.is_none() {
+            return;
+        }
+        if !lock.shown.contains(output) {
+            lock.shown.push(output.clone());
+        }
+        let pending = self
+            .backend_data
+            .devices
+            .values()
+            .flat_map(|device| device.surfaces.values())
+            .any(|surface| surface.powered && !lock.shown.contains(&surface.output));
+        if !pending {
+            if let Some(locker) = lock.locker.take() {
+                locker.lock();
+            }
+        }
+    }
+}
+
+impl SessionLockHandler for State {
+    fn lock_state(&mut self) -> &mut SessionLockManagerState {
+        &mut self.session_lock_state
+    }
+
+    fn lock(&mut self, confirmation: SessionLocker) {
+        tracing::info!("Locking the session");
+        // A new lock replaces the one of a client that died, its surfaces are gone
+        self.session_lock = Some(SessionLock {
+            locker: Some(confirmation),
+            shown: Vec::new(),
+            surfaces: Vec::new(),
+        });
+        self.overlay = None;
+        self.window_cycle = None;
+        self.set_keyboard_focus(None);
+
+        // Powered-off outputs show nothing already
+        let powered = self
+            .backend_data
+            .devices
+            .values()
+            .flat_map(|device| device.surfaces.values())
+            .any(|surface| surface.powered);
+        if powered {
+            self.redraw_all();
+        } else if let Some(locker) = self
+            .session_lock
+            .as_mut()
+            .and_then(|lock| lock.locker.take())
+        {
+            locker.lock();
+        }
+    }
+
+    fn unlock(&mut self) {
+        tracing::info!("Unlocking the session");
+        self.session_lock = None;
+        self.set_keyboard_focus_auto();
+        self.redraw_all();
+    }
+
+    fn new_surface(&mut self, surface: LockSurface, output: WlOutput) {
+        let Some(output) = Output::from_resource(&output) else {
+            return;
+        };
+        let Some(lock) = self.session_lock.as_mut() else {
+            return;
+        };
+        let size = self
+            .workspaces
+            .get_current()
+            .space
+            .output_geometry(&output)
+            .map(|geo| geo.size)
+            .unwrap_or_default();
+        surface.with_pending_state(|state| {
+            state.size = Some(Size::from((size.w as u32, size.h as u32)));
+        });
+        surface.send_configure();
+        lock.surfaces.retain(|(o, _)| *o != output);
+        lock.surfaces.push((output, surface));
+
+        let focus = self.lock_focus(None);
+        self.set_keyboard_focus(focus);
+    }
+}
+
+delegate_session_lock!(State);
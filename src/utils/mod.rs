@@ -1,8 +1,23 @@
 pub mod action;
+pub mod animation;
+pub mod arrangement;
+pub mod autohide;
 pub mod config;
 pub mod cursor;
 pub mod grab;
+pub mod idle;
+pub mod ipc;
+pub mod launcher;
 pub mod layout;
+pub mod lock;
 pub mod logs;
+pub mod overlay;
 pub mod render;
+pub mod rules;
+pub mod selection;
+pub mod stats;
+pub mod swallow;
+#[cfg(test)]
+pub mod test_utils;
+pub mod titlebar;
 pub mod workspaces;
@@ -0,0 +1,40 @@
+use smithay::input::keyboard::Keysym;
+
+use crate::{state::State, utils::action::Action};
+
+/// What an overlay wants done after a key press.
+pub enum OverlayResponse {
+    Keep,
+    Close,
+    /// Close the overlay, then run the action.
+    Run(Action),
+}
+
+/// Compositor-drawn UI (launcher, menus) that takes every key while it is open.
+/// Escape always closes it, before the overlay sees the key.
+pub trait Overlay {
+    fn key(&mut self, sym: Keysym) -> OverlayResponse;
+
+    /// Lines to draw top to bottom, with whether each one is highlighted.
+    fn lines(&self) -> Vec<(String, bool)>;
+}
+
+impl State {
+    pub fn overlay_key(&mut self, sym: Keysym) {
+        let Some(overlay) = self.overlay.as_mut() else {
+            return;
+        };
+        let response = match sym {
+            Keysym::Escape => OverlayResponse::Close,
+            sym => overlay.key(sym),
+        };
+        match response {
+            OverlayResponse::Keep => {}
+            OverlayResponse::Close => self.overlay = None,
+            OverlayResponse::Run(action) => {
+                self.overlay = None;
+                action.execute(self);
+            }
+        }
+    }
+}
@@ -0,0 +1,49 @@
+#version 100
+
+//_DEFINES_
+
+#if defined(EXTERNAL)
+#extension GL_OES_EGL_image_external : require
+#endif
+
+precision mediump float;
+#if defined(EXTERNAL)
+uniform samplerExternalOES tex;
+#else
+uniform sampler2D tex;
+#endif
+
+uniform float alpha;
+varying vec2 v_coords;
+
+#if defined(DEBUG_FLAGS)
+uniform float tint;
+#endif
+
+// Size of one texel in texture coordinates.
+uniform vec2 texel;
+// Distance between samples in texels.
+uniform float radius;
+
+void main() {
+    // 9x9 gaussian kernel
+    vec4 color = vec4(0.0);
+    float total = 0.0;
+    for (int x = -4; x <= 4; x++) {
+        for (int y = -4; y <= 4; y++) {
+            float weight = exp(-float(x * x + y * y) / 8.0);
+            vec2 offset = vec2(float(x), float(y)) * texel * radius;
+            color += texture2D(tex, v_coords + offset) * weight;
+            total += weight;
+        }
+    }
+    color /= total;
+
+    // The blurred backdrop is always opaque
+    gl_FragColor = vec4(color.rgb, 1.0) * alpha;
+
+#if defined(DEBUG_FLAGS)
+    if (tint == 1.0)
+        gl_FragColor = vec4(0.0, 0.2, 0.0, 0.2) + gl_FragColor * 0.8;
+#endif
+}
@@ -0,0 +1,209 @@
+use super::{framebuffer_rect, framebuffer_transform};
+use smithay::{
+    backend::{
+        allocator::Fourcc,
+        renderer::{
+            element::{Element, Id, Kind, RenderElement},
+            gles::{
+                ffi, GlesError, GlesFrame, GlesRenderer, GlesTexProgram, GlesTexture, Uniform,
+                UniformName, UniformType,
+            },
+            utils::{CommitCounter, DamageSet, OpaqueRegions},
+            Offscreen, Texture,
+        },
+    },
+    utils::{Buffer, Physical, Rectangle, Scale, Size, Transform},
+};
+
+const BLUR_SHADER: &str = include_str!("blur.frag");
+
+pub struct BlurShader(pub GlesTexProgram);
+
+/// What a blurred backdrop keeps between frames.
+pub struct BlurCache {
+    id: Id,
+    texture: GlesTexture,
+    scene: u64,
+    commit: CommitCounter,
+}
+
+/// Copies what was drawn below it into a texture and draws it back blurred.
+/// Placed right below a window, the window is composited over a blurred backdrop.
+pub struct BlurElement {
+    id: Id,
+    geometry: Rectangle<i32, Physical>,
+    output_size: Size<i32, Physical>,
+    output_transform: Transform,
+    texture: GlesTexture,
+    program: GlesTexProgram,
+    radius: f32,
+    scene: u64,
+    commit: CommitCounter,
+}
+
+impl BlurElement {
+    /// `geometry` is clamped to the output. The cached texture is reused when
+    /// it already has the right size, otherwise a new one is allocated.
+    pub fn new(
+        renderer: &mut GlesRenderer,
+        cache: Option<BlurCache>,
+        geometry: Rectangle<i32, Physical>,
+        output_size: Size<i32, Physical>,
+        output_transform: Transform,
+        radius: f32,
+    ) -> Option<Self> {
+        let geometry = geometry.intersection(Rectangle::from_size(output_size))?;
+        // The copy has the framebuffer's orientation
+        let fb_size = output_transform.invert().transform_size(geometry.size);
+        let size: Size<i32, Buffer> = (fb_size.w, fb_size.h).into();
+        let (id, texture, scene, mut commit) = match cache {
+            Some(cache) => (cache.id, Some(cache.texture), cache.scene, cache.commit),
+            None => (Id::new(), None, 0, CommitCounter::default()),
+        };
+        let texture = match texture.filter(|t| t.size() == size) {
+            Some(texture) => texture,
+            None => {
+                commit.increment();
+                renderer.create_buffer(Fourcc::Abgr8888, size).ok()?
+            }
+        };
+        let program = renderer
+            .egl_context()
+            .user_data()
+            .get::<BlurShader>()?
+            .0
+            .clone();
+
+        Some(Self {
+            id,
+            geometry,
+            output_size,
+            output_transform,
+            texture,
+            program,
+            radius,
+            scene,
+            commit,
+        })
+    }
+
+    /// `scene` is a fingerprint of everything drawn over the backdrop's
+    /// geometry, the backdrop is only redrawn when it changes.
+    pub fn set_scene(&mut self, scene: u64) {
+        if self.scene != scene {
+            self.scene = scene;
+            self.commit.increment();
+        }
+    }
+
+    pub fn cache(&self) -> BlurCache {
+        BlurCache {
+            id: self.id.clone(),
+            texture: self.texture.clone(),
+            scene: self.scene,
+            commit: self.commit,
+        }
+    }
+}
+
+impl Element for BlurElement {
+    fn id(&self) -> &Id {
+        &self.id
+    }
+
+    fn current_commit(&self) -> CommitCounter {
+        self.commit
+    }
+
+    fn src(&self) -> Rectangle<f64, Buffer> {
+        Rectangle::from_size(self.texture.size()).to_f64()
+    }
+
+    fn geometry(&self, _scale: Scale<f64>) -> Rectangle<i32, Physical> {
+        self.geometry
+    }
+
+    // The blur reads back the whole region, partial redraws would blur what
+    // was composited over it last frame
+    fn damage_since(
+        &self,
+        _scale: Scale<f64>,
+        commit: Option<CommitCounter>,
+    ) -> DamageSet<i32, Physical> {
+        if commit == Some(self.commit) {
+            DamageSet::default()
+        } else {
+            DamageSet::from_slice(&[Rectangle::from_size(self.geometry.size)])
+        }
+    }
+
+    fn opaque_regions(&self, _scale: Scale<f64>) -> OpaqueRegions<i32, Physical> {
+        OpaqueRegions::default()
+    }
+
+    fn kind(&self) -> Kind {
+        Kind::Unspecified
+    }
+}
+
+impl RenderElement<GlesRenderer> for BlurElement {
+    fn draw(
+        &self,
+        frame: &mut GlesFrame<'_, '_>,
+        src: Rectangle<f64, Buffer>,
+        dst: Rectangle<i32, Physical>,
+        damage: &[Rectangle<i32, Physical>],
+        _opaque_regions: &[Rectangle<i32, Physical>],
+    ) -> Result<(), GlesError> {
+        let fb_rect = framebuffer_rect(dst, self.output_size, self.output_transform);
+        let tex_id = self.texture.tex_id();
+        frame.with_context(|gl| unsafe {
+            gl.BindTexture(ffi::TEXTURE_2D, tex_id);
+            gl.CopyTexSubImage2D(
+                ffi::TEXTURE_2D,
+                0,
+                0,
+                0,
+                fb_rect.loc.x,
+                fb_rect.loc.y,
+                fb_rect.size.w,
+                fb_rect.size.h,
+            );
+            gl.BindTexture(ffi::TEXTURE_2D, 0);
+        })?;
+
+        let size = self.texture.size();
+        frame.render_texture_from_to(
+            &self.texture,
+            src,
+            dst,
+            damage,
+            &[],
+            // Undo the bottom-up, rotated copy
+            framebuffer_transform(self.output_transform),
+            1.0,
+            Some(&self.program),
+            &[
+                Uniform::new("texel", [1.0 / size.w as f32, 1.0 / size.h as f32]),
+                Uniform::new("radius", self.radius),
+            ],
+        )
+    }
+}
+
+pub fn compile_blur_shader(renderer: &mut GlesRenderer) {
+    let blur_shader = renderer
+        .compile_custom_texture_shader(
+            BLUR_SHADER,
+            &[
+                UniformName::new("texel", UniformType::_2f),
+                UniformName::new("radius", UniformType::_1f),
+            ],
+        )
+        .unwrap();
+
+    renderer
+        .egl_context()
+        .user_data()
+        .insert_if_missing(|| BlurShader(blur_shader));
+}
@@ -34,20 +34,38 @@ uniform vec3 startColor;
 uniform vec3 endColor;
 uniform float thickness;
 uniform float halfThickness;
+// Two-tone borders: band colors replace the gradient where bandMix is 1.0 (x inner, y outer)
+uniform vec3 innerColor;
+uniform vec3 outerColor;
+uniform vec2 bandMix;
+uniform float outerWidth;
+// Radius of the window corners the border wraps, 0.0 for square ones
+uniform float cornerRadius;
 
 void main() {
     vec2 center = size / 2.0 - vec2(0.5);
     vec2 location = v_coords * size;
     vec4 mix_color;
 
-    float distance = max(abs(location.x - center.x) - (size.x / 2.0 - halfThickness), abs(location.y - center.y) - (size.y / 2.0 - halfThickness));
+    // Distance to the middle of the border, rounded around rounded windows
+    float midRadius = cornerRadius > 0.0 ? cornerRadius + halfThickness : 0.0;
+    vec2 q = abs(location - center) - (size / 2.0 - halfThickness) + vec2(midRadius);
+    float distance = midRadius > 0.0
+        ? length(max(q, 0.0)) + min(max(q.x, q.y), 0.0) - midRadius
+        : max(q.x, q.y);
     float smoothedAlpha = 1.0 - smoothstep(0.0, 1.0, abs(distance) - (halfThickness));
 
     float dotProduct = dot(v_coords, gradientDirection);
 
     vec3 gradientColor = mix(startColor, endColor, smoothstep(0.0, 1.0, dotProduct));
 
-    mix_color = mix(vec4(0.0, 0.0, 0.0, 0.0), vec4(gradientColor, smoothedAlpha), smoothedAlpha);
+    // Depth from the outer edge picks the band
+    float depth = halfThickness - distance;
+    vec3 inner = mix(gradientColor, innerColor, bandMix.x);
+    vec3 outer = mix(gradientColor, outerColor, bandMix.y);
+    vec3 color = mix(inner, outer, 1.0 - step(outerWidth, depth));
+
+    mix_color = mix(vec4(0.0, 0.0, 0.0, 0.0), vec4(color, smoothedAlpha), smoothedAlpha);
 
     gl_FragColor = mix_color;
 }
@@ -15,6 +15,15 @@ const BORDER_SHADER: &str = include_str!("border.frag");
 
 pub struct BorderShader(pub GlesPixelProgram);
 
+/// Two-tone border: the outer `outer_width` pixels and the rest of the border
+/// each get a fixed color, or keep the gradient when it is `None`.
+#[derive(Default)]
+pub struct Bands<'a> {
+    pub inner: Option<&'a str>,
+    pub outer: Option<&'a str>,
+    pub outer_width: f32,
+}
+
 impl BorderShader {
     pub fn element(
         renderer: &mut GlesRenderer,
@@ -24,6 +33,8 @@ impl BorderShader {
         active: &str,
         end_acitve: &str,
         border_thickness: f32,
+        corner_radius: f32,
+        bands: &Bands,
     ) -> PixelShaderElement {
         let program = renderer
             .egl_context()
@@ -35,6 +46,12 @@ impl BorderShader {
 
         let angle = angle * std::f32::consts::PI;
         let gradient_direction = [angle.cos(), angle.sin()];
+        let inner = bands.inner.and_then(|color| hex_to_rgb(color).ok());
+        let outer = bands.outer.and_then(|color| hex_to_rgb(color).ok());
+        let band_mix = [
+            if inner.is_some() { 1.0 } else { 0.0 },
+            if outer.is_some() { 1.0 } else { 0.0 },
+        ];
         PixelShaderElement::new(
             program,
             geo,
@@ -46,6 +63,11 @@ impl BorderShader {
                 Uniform::new("thickness", border_thickness),
                 Uniform::new("halfThickness", border_thickness * 0.5),
                 Uniform::new("gradientDirection", gradient_direction),
+                Uniform::new("innerColor", inner.unwrap_or_default()),
+                Uniform::new("outerColor", outer.unwrap_or_default()),
+                Uniform::new("bandMix", band_mix),
+                Uniform::new("outerWidth", bands.outer_width.clamp(0.0, border_thickness)),
+                Uniform::new("cornerRadius", corner_radius.max(0.0)),
             ],
             Kind::Unspecified,
         )
@@ -63,6 +85,11 @@ pub fn compile_shaders(renderer: &mut GlesRenderer) {
                 UniformName::new("thickness", UniformType::_1f),
                 UniformName::new("halfThickness", UniformType::_1f),
                 UniformName::new("gradientDirection", UniformType::_2f),
+                UniformName::new("innerColor", UniformType::_3f),
+                UniformName::new("outerColor", UniformType::_3f),
+                UniformName::new("bandMix", UniformType::_2f),
+                UniformName::new("outerWidth", UniformType::_1f),
+                UniformName::new("cornerRadius", UniformType::_1f),
             ],
         )
         .unwrap();
@@ -74,7 +101,7 @@ pub fn compile_shaders(renderer: &mut GlesRenderer) {
         .insert_if_missing(|| BorderShader(border_shader));
 }
 
-fn hex_to_rgb(hex: &str) -> Result<[f32; 3], &'static str> {
+pub fn hex_to_rgb(hex: &str) -> Result<[f32; 3], &'static str> {
     let hex = hex.trim_start_matches('#');
 
     if hex.len() != 6 {
@@ -0,0 +1,7 @@
+precision mediump float;
+uniform float alpha;
+varying vec2 v_coords;
+
+void main() {
+    gl_FragColor = vec4(0.0, 0.0, 0.0, alpha);
+}
@@ -0,0 +1,47 @@
+use smithay::{
+    backend::renderer::{
+        element::Kind,
+        gles::{element::PixelShaderElement, GlesPixelProgram, GlesRenderer},
+    },
+    utils::{Logical, Rectangle},
+};
+
+const DIM_SHADER: &str = include_str!("dim.frag");
+
+pub struct DimShader(pub GlesPixelProgram);
+
+impl DimShader {
+    pub fn element(
+        renderer: &mut GlesRenderer,
+        geo: Rectangle<i32, Logical>,
+        strength: f32,
+    ) -> PixelShaderElement {
+        let program = renderer
+            .egl_context()
+            .user_data()
+            .get::<DimShader>()
+            .unwrap()
+            .0
+            .clone();
+
+        PixelShaderElement::new(
+            program,
+            geo,
+            None,
+            strength.clamp(0.0, 1.0),
+            vec![],
+            Kind::Unspecified,
+        )
+    }
+}
+
+pub fn compile_dim_shader(renderer: &mut GlesRenderer) {
+    let dim_shader = renderer
+        .compile_custom_pixel_shader(DIM_SHADER, &[])
+        .unwrap();
+
+    renderer
+        .egl_context()
+        .user_data()
+        .insert_if_missing(|| DimShader(dim_shader));
+}
@@ -1,5 +1,11 @@
+pub mod blur;
 pub mod border;
+pub mod dim;
+pub mod rounded;
+pub mod text;
 
+use blur::BlurElement;
+use rounded::RoundedElement;
 use smithay::{
     backend::{
         drm::DrmDeviceFd,
@@ -15,7 +21,7 @@ use smithay::{
             ImportAll, ImportMem, Renderer, RendererSuper, Texture,
         },
     },
-    utils::{Buffer, Physical, Rectangle, Scale},
+    utils::{Buffer, Physical, Rectangle, Scale, Size, Transform},
 };
 
 pub type GlMultiRenderer<'a> = MultiRenderer<
@@ -37,6 +43,37 @@ pub type MultiError<'a> = multigpu::Error<
     GbmGlesBackend<GlesRenderer, DrmDeviceFd>,
     GbmGlesBackend<GlesRenderer, DrmDeviceFd>,
 >;
+
+/// `rect` on an output with `transform`, whose transformed size is
+/// `output_size`, in its framebuffer: untransformed and bottom-up like GL.
+pub fn framebuffer_rect(
+    rect: Rectangle<i32, Physical>,
+    output_size: Size<i32, Physical>,
+    transform: Transform,
+) -> Rectangle<i32, Physical> {
+    let rect = transform.invert().transform_rect_in(rect, &output_size);
+    let buffer_size = transform.invert().transform_size(output_size);
+    Rectangle::new(
+        (rect.loc.x, buffer_size.h - rect.loc.y - rect.size.h).into(),
+        rect.size,
+    )
+}
+
+/// Buffer transform of pixels read back from a framebuffer of an output
+/// with `transform`: GL rows are bottom-up on top of the output rotation.
+pub fn framebuffer_transform(transform: Transform) -> Transform {
+    match transform {
+        Transform::Normal => Transform::Flipped180,
+        Transform::_90 => Transform::Flipped270,
+        Transform::_180 => Transform::Flipped,
+        Transform::_270 => Transform::Flipped90,
+        Transform::Flipped => Transform::_180,
+        Transform::Flipped90 => Transform::_270,
+        Transform::Flipped180 => Transform::Normal,
+        Transform::Flipped270 => Transform::_90,
+    }
+}
+
 pub enum CustomRenderElements<R>
 where
     R: Renderer,
@@ -44,6 +81,8 @@ where
     Texture(TextureRenderElement<GlesTexture>),
     Window(WaylandSurfaceRenderElement<R>),
     Shader(PixelShaderElement),
+    Blur(BlurElement),
+    Rounded(RoundedElement<R>),
 }
 
 impl<R> Element for CustomRenderElements<R>
@@ -57,6 +96,8 @@ where
             CustomRenderElements::Texture(elem) => elem.id(),
             CustomRenderElements::Window(elem) => elem.id(),
             CustomRenderElements::Shader(elem) => elem.id(),
+            CustomRenderElements::Blur(elem) => elem.id(),
+            CustomRenderElements::Rounded(elem) => elem.id(),
         }
     }
     fn src(&self) -> Rectangle<f64, Buffer> {
@@ -64,6 +105,8 @@ where
             CustomRenderElements::Texture(elem) => elem.src(),
             CustomRenderElements::Window(elem) => elem.src(),
             CustomRenderElements::Shader(elem) => elem.src(),
+            CustomRenderElements::Blur(elem) => elem.src(),
+            CustomRenderElements::Rounded(elem) => elem.src(),
         }
     }
     fn geometry(&self, scale: Scale<f64>) -> Rectangle<i32, Physical> {
@@ -71,6 +114,8 @@ where
             CustomRenderElements::Texture(elem) => elem.geometry(scale),
             CustomRenderElements::Window(elem) => elem.geometry(scale),
             CustomRenderElements::Shader(elem) => elem.geometry(scale),
+            CustomRenderElements::Blur(elem) => elem.geometry(scale),
+            CustomRenderElements::Rounded(elem) => elem.geometry(scale),
         }
     }
     fn current_commit(&self) -> CommitCounter {
@@ -78,6 +123,8 @@ where
             CustomRenderElements::Texture(elem) => elem.current_commit(),
             CustomRenderElements::Window(elem) => elem.current_commit(),
             CustomRenderElements::Shader(elem) => elem.current_commit(),
+            CustomRenderElements::Blur(elem) => elem.current_commit(),
+            CustomRenderElements::Rounded(elem) => elem.current_commit(),
         }
     }
     fn opaque_regions(
@@ -88,6 +135,8 @@ where
             CustomRenderElements::Texture(elem) => elem.opaque_regions(scale),
             CustomRenderElements::Window(elem) => elem.opaque_regions(scale),
             CustomRenderElements::Shader(elem) => elem.opaque_regions(scale),
+            CustomRenderElements::Blur(elem) => elem.opaque_regions(scale),
+            CustomRenderElements::Rounded(elem) => elem.opaque_regions(scale),
         }
     }
     fn kind(&self) -> smithay::backend::renderer::element::Kind {
@@ -95,6 +144,8 @@ where
             CustomRenderElements::Texture(elem) => elem.kind(),
             CustomRenderElements::Window(elem) => elem.kind(),
             CustomRenderElements::Shader(elem) => elem.kind(),
+            CustomRenderElements::Blur(elem) => elem.kind(),
+            CustomRenderElements::Rounded(elem) => elem.kind(),
         }
     }
     fn alpha(&self) -> f32 {
@@ -102,6 +153,8 @@ where
             CustomRenderElements::Texture(elem) => elem.alpha(),
             CustomRenderElements::Window(elem) => elem.alpha(),
             CustomRenderElements::Shader(elem) => elem.alpha(),
+            CustomRenderElements::Blur(elem) => elem.alpha(),
+            CustomRenderElements::Rounded(elem) => elem.alpha(),
         }
     }
     fn location(&self, scale: Scale<f64>) -> smithay::utils::Point<i32, Physical> {
@@ -109,6 +162,8 @@ where
             CustomRenderElements::Texture(elem) => elem.location(scale),
             CustomRenderElements::Window(elem) => elem.location(scale),
             CustomRenderElements::Shader(elem) => elem.location(scale),
+            CustomRenderElements::Blur(elem) => elem.location(scale),
+            CustomRenderElements::Rounded(elem) => elem.location(scale),
         }
     }
     fn transform(&self) -> smithay::utils::Transform {
@@ -116,6 +171,8 @@ where
             CustomRenderElements::Texture(elem) => elem.transform(),
             CustomRenderElements::Window(elem) => elem.transform(),
             CustomRenderElements::Shader(elem) => elem.transform(),
+            CustomRenderElements::Blur(elem) => elem.transform(),
+            CustomRenderElements::Rounded(elem) => elem.transform(),
         }
     }
     fn damage_since(
@@ -127,6 +184,8 @@ where
             CustomRenderElements::Texture(elem) => elem.damage_since(scale, commit),
             CustomRenderElements::Window(elem) => elem.damage_since(scale, commit),
             CustomRenderElements::Shader(elem) => elem.damage_since(scale, commit),
+            CustomRenderElements::Blur(elem) => elem.damage_since(scale, commit),
+            CustomRenderElements::Rounded(elem) => elem.damage_since(scale, commit),
         }
     }
 }
@@ -162,6 +221,23 @@ impl<'a> RenderElement<GlMultiRenderer<'a>> for CustomRenderElements<GlMultiRend
                 opaque_regions,
             )
             .map_err(MultiError::Render),
+            CustomRenderElements::Blur(elem) => RenderElement::<GlesRenderer>::draw(
+                elem,
+                frame.as_mut(),
+                src,
+                dst,
+                damage,
+                opaque_regions,
+            )
+            .map_err(MultiError::Render),
+            CustomRenderElements::Rounded(elem) => {
+                frame
+                    .as_mut()
+                    .override_default_tex_program(elem.program().clone(), elem.uniforms());
+                let res = elem.inner().draw(frame, src, dst, damage, opaque_regions);
+                frame.as_mut().clear_tex_program_override();
+                res
+            }
         }
     }
 
@@ -173,6 +249,8 @@ impl<'a> RenderElement<GlMultiRenderer<'a>> for CustomRenderElements<GlMultiRend
             CustomRenderElements::Texture(elem) => elem.underlying_storage(renderer.as_mut()),
             CustomRenderElements::Window(elem) => elem.underlying_storage(renderer),
             CustomRenderElements::Shader(elem) => elem.underlying_storage(renderer.as_mut()),
+            // Scanout would skip the clipping
+            CustomRenderElements::Blur(_) | CustomRenderElements::Rounded(_) => None,
         }
     }
 }
@@ -0,0 +1,45 @@
+#version 100
+
+//_DEFINES_
+
+#if defined(EXTERNAL)
+#extension GL_OES_EGL_image_external : require
+#endif
+
+precision mediump float;
+#if defined(EXTERNAL)
+uniform samplerExternalOES tex;
+#else
+uniform sampler2D tex;
+#endif
+
+uniform float alpha;
+varying vec2 v_coords;
+
+#if defined(DEBUG_FLAGS)
+uniform float tint;
+#endif
+
+// Window geometry in framebuffer pixels: x, y of the bottom left corner, width, height.
+uniform vec4 clipRect;
+uniform float cornerRadius;
+
+void main() {
+    vec4 color = texture2D(tex, v_coords);
+#if defined(NO_ALPHA)
+    color = vec4(color.rgb, 1.0);
+#endif
+
+    // Signed distance to the rounded rectangle, negative inside
+    vec2 halfSize = clipRect.zw / 2.0;
+    vec2 q = abs(gl_FragCoord.xy - clipRect.xy - halfSize) - halfSize + vec2(cornerRadius);
+    float distance = length(max(q, 0.0)) + min(max(q.x, q.y), 0.0) - cornerRadius;
+    float coverage = 1.0 - smoothstep(-0.5, 0.5, distance);
+
+    gl_FragColor = color * alpha * coverage;
+
+#if defined(DEBUG_FLAGS)
+    if (tint == 1.0)
+        gl_FragColor = vec4(0.0, 0.2, 0.0, 0.2) + gl_FragColor * 0.8;
+#endif
+}
@@ -0,0 +1,151 @@
+use super::framebuffer_rect;
+use smithay::{
+    backend::renderer::{
+        element::{surface::WaylandSurfaceRenderElement, Element, Id, Kind},
+        gles::{GlesRenderer, GlesTexProgram, Uniform, UniformName, UniformType},
+        utils::{CommitCounter, DamageSet, OpaqueRegions},
+        ImportAll, ImportMem, Renderer, RendererSuper,
+    },
+    utils::{Buffer, Physical, Point, Rectangle, Scale, Size, Transform},
+};
+
+const ROUNDED_SHADER: &str = include_str!("rounded.frag");
+
+pub struct RoundedCornerShader(pub GlesTexProgram);
+
+/// A window surface clipped to the rounded rectangle of its window.
+/// It's drawn with the default texture program swapped for the clipping one.
+pub struct RoundedElement<R: Renderer> {
+    inner: WaylandSurfaceRenderElement<R>,
+    program: GlesTexProgram,
+    /// Window geometry on the output.
+    clip: Rectangle<i32, Physical>,
+    output_size: Size<i32, Physical>,
+    output_transform: Transform,
+    radius: f32,
+}
+
+impl<R: Renderer> RoundedElement<R> {
+    /// Hands `inner` back when the shader isn't compiled, to be drawn unclipped.
+    pub fn new(
+        renderer: &mut GlesRenderer,
+        inner: WaylandSurfaceRenderElement<R>,
+        clip: Rectangle<i32, Physical>,
+        output_size: Size<i32, Physical>,
+        output_transform: Transform,
+        radius: f32,
+    ) -> Result<Self, WaylandSurfaceRenderElement<R>> {
+        let Some(program) = renderer
+            .egl_context()
+            .user_data()
+            .get::<RoundedCornerShader>()
+            .map(|shader| shader.0.clone())
+        else {
+            return Err(inner);
+        };
+        // Corners can't be rounder than half the window
+        let radius = radius.min(clip.size.w.min(clip.size.h) as f32 / 2.0);
+        Ok(Self {
+            inner,
+            program,
+            clip,
+            output_size,
+            output_transform,
+            radius,
+        })
+    }
+
+    pub fn inner(&self) -> &WaylandSurfaceRenderElement<R> {
+        &self.inner
+    }
+
+    pub fn program(&self) -> &GlesTexProgram {
+        &self.program
+    }
+
+    pub fn uniforms(&self) -> Vec<Uniform<'static>> {
+        // The shader compares against gl_FragCoord
+        let rect = framebuffer_rect(self.clip, self.output_size, self.output_transform);
+        vec![
+            Uniform::new(
+                "clipRect",
+                [
+                    rect.loc.x as f32,
+                    rect.loc.y as f32,
+                    rect.size.w as f32,
+                    rect.size.h as f32,
+                ],
+            ),
+            Uniform::new("cornerRadius", self.radius),
+        ]
+    }
+}
+
+impl<R> Element for RoundedElement<R>
+where
+    R: Renderer,
+    <R as RendererSuper>::TextureId: 'static,
+    R: ImportAll + ImportMem,
+{
+    fn id(&self) -> &Id {
+        self.inner.id()
+    }
+
+    fn current_commit(&self) -> CommitCounter {
+        self.inner.current_commit()
+    }
+
+    fn src(&self) -> Rectangle<f64, Buffer> {
+        self.inner.src()
+    }
+
+    fn geometry(&self, scale: Scale<f64>) -> Rectangle<i32, Physical> {
+        self.inner.geometry(scale)
+    }
+
+    fn location(&self, scale: Scale<f64>) -> Point<i32, Physical> {
+        self.inner.location(scale)
+    }
+
+    fn transform(&self) -> Transform {
+        self.inner.transform()
+    }
+
+    fn damage_since(
+        &self,
+        scale: Scale<f64>,
+        commit: Option<CommitCounter>,
+    ) -> DamageSet<i32, Physical> {
+        self.inner.damage_since(scale, commit)
+    }
+
+    // The cut off corners show what is below
+    fn opaque_regions(&self, _scale: Scale<f64>) -> OpaqueRegions<i32, Physical> {
+        OpaqueRegions::default()
+    }
+
+    fn alpha(&self) -> f32 {
+        self.inner.alpha()
+    }
+
+    fn kind(&self) -> Kind {
+        self.inner.kind()
+    }
+}
+
+pub fn compile_rounded_shader(renderer: &mut GlesRenderer) {
+    let rounded_shader = renderer
+        .compile_custom_texture_shader(
+            ROUNDED_SHADER,
+            &[
+                UniformName::new("clipRect", UniformType::_4f),
+                UniformName::new("cornerRadius", UniformType::_1f),
+            ],
+        )
+        .unwrap();
+
+    renderer
+        .egl_context()
+        .user_data()
+        .insert_if_missing(|| RoundedCornerShader(rounded_shader));
+}
@@ -0,0 +1,121 @@
+use smithay::{
+    backend::{
+        allocator::Fourcc,
+        renderer::{
+            element::texture::TextureBuffer,
+            gles::{GlesRenderer, GlesTexture},
+        },
+    },
+    utils::Transform,
+};
+
+const GLYPH_W: usize = 3;
+const GLYPH_H: usize = 5;
+const PIXEL: usize = 2;
+
+/// Height of a single line of text including padding.
+pub const LINE_HEIGHT: i32 = ((GLYPH_H + 2) * PIXEL) as i32;
+
+// 3x5 bitmap glyphs, one row per byte using the lowest 3 bits.
+fn glyph(c: char) -> [u8; GLYPH_H] {
+    match c.to_ascii_uppercase() {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b010, 0b010, 0b010],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b111, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        ',' => [0b000, 0b000, 0b000, 0b010, 0b100],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '+' => [0b000, 0b010, 0b111, 0b010, 0b000],
+        '_' => [0b000, 0b000, 0b000, 0b000, 0b111],
+        '/' => [0b001, 0b001, 0b010, 0b100, 0b100],
+        _ => [0; GLYPH_H],
+    }
+}
+
+/// Width in pixels `text` needs when rendered with [`text_texture`].
+pub fn text_width(text: &str) -> i32 {
+    ((text.chars().count() * (GLYPH_W + 1) + 1) * PIXEL) as i32
+}
+
+/// Rasterizes `text` with the built-in bitmap font into an ABGR8888 texture of
+/// `size` logical pixels, drawn at `scale` to stay sharp on scaled outputs.
+/// Characters that don't fit are cut off.
+pub fn text_texture(
+    renderer: &mut GlesRenderer,
+    text: &str,
+    size: (i32, i32),
+    scale: f64,
+    background: [u8; 4],
+    foreground: [u8; 4],
+) -> TextureBuffer<GlesTexture> {
+    let physical = |len: i32| (len as f64 * scale).round().max(1.0) as usize;
+    let (width, height) = (physical(size.0), physical(size.1));
+    let pixel = physical(PIXEL as i32);
+    let stride = width * 4;
+    let top = height.saturating_sub(GLYPH_H * pixel) / 2;
+
+    let mut data = background.repeat(width * height);
+    for (index, c) in text.chars().enumerate() {
+        for (row, bits) in glyph(c).iter().enumerate() {
+            for col in 0..GLYPH_W {
+                if bits & (1 << (GLYPH_W - 1 - col)) == 0 {
+                    continue;
+                }
+                let x0 = (1 + index * (GLYPH_W + 1) + col) * pixel;
+                let y0 = top + row * pixel;
+                for y in y0..(y0 + pixel).min(height) {
+                    for x in x0..(x0 + pixel).min(width) {
+                        let offset = y * stride + x * 4;
+                        data[offset..offset + 4].copy_from_slice(&foreground);
+                    }
+                }
+            }
+        }
+    }
+
+    TextureBuffer::from_memory(
+        renderer,
+        &data,
+        Fourcc::Abgr8888,
+        (width as i32, height as i32),
+        false,
+        1,
+        Transform::Normal,
+        None,
+    )
+    .unwrap()
+}
@@ -0,0 +1,91 @@
+use std::cell::RefCell;
+
+use smithay::desktop::Window;
+
+use crate::{
+    state::State,
+    utils::{
+        layout::LayoutState,
+        workspaces::{place_fixed, place_on_center, window_app_id, window_title, WindowMode},
+    },
+};
+
+/// Marks windows the rules were applied to, so a remap keeps what the user changed since.
+struct RulesApplied;
+
+impl State {
+    /// Applies the window rules matching `window`, which has to be on the
+    /// current workspace. For each setting the first rule that has it wins.
+    /// Returns the workspace the window ended up on.
+    pub fn apply_window_rules(&mut self, window: &Window) -> usize {
+        let source = self.workspaces.active_ws();
+        if window.user_data().get::<RulesApplied>().is_some() {
+            return source;
+        }
+        window.user_data().insert_if_missing(|| RulesApplied);
+
+        let app_id = window_app_id(window);
+        let title = window_title(window);
+        let rules: Vec<_> = self.config.window_rules(&app_id, &title).collect();
+        if rules.is_empty() {
+            return source;
+        }
+        let floating = rules.iter().find_map(|rule| rule.floating);
+        let workspace = rules.iter().find_map(|rule| rule.workspace);
+        let size = rules.iter().find_map(|rule| rule.size);
+        let position = rules.iter().find_map(|rule| rule.position);
+
+        // Index 0 wraps around and is rejected as out of range
+        let target = workspace
+            .map(|index| index.wrapping_sub(1))
+            .filter(|&index| {
+                self.workspaces.send_window_to_ws(
+                    window,
+                    index,
+                    &self.config.floating,
+                    self.config.titlebars,
+                )
+            })
+            .unwrap_or(source);
+
+        let ws = &mut self.workspaces.workspaces[target];
+        let Some(mode) = window.user_data().get::<RefCell<WindowMode>>() else {
+            return target;
+        };
+        match floating {
+            Some(true) => *mode.borrow_mut() = WindowMode::Floating,
+            Some(false) if !matches!(ws.layout, LayoutState::Floating) => {
+                *mode.borrow_mut() = WindowMode::Tiled
+            }
+            _ => {}
+        }
+        if matches!(*mode.borrow(), WindowMode::Floating) {
+            if floating == Some(true) {
+                place_on_center(
+                    &mut ws.space,
+                    window,
+                    self.config.border.gap_outer + self.config.border.thickness,
+                    &self.config.floating,
+                    self.config.titlebars,
+                );
+            }
+            if size.is_some() || position.is_some() {
+                place_fixed(
+                    &mut ws.space,
+                    window,
+                    size.map(Into::into),
+                    position.map(Into::into),
+                    self.config.titlebars,
+                );
+            }
+        }
+
+        self.refresh_layout_for(target);
+        if target != source {
+            // Close the gap the window left behind
+            self.refresh_layout();
+            self.set_keyboard_focus_auto();
+        }
+        target
+    }
+}
@@ -0,0 +1,170 @@
+use std::{io::Read, os::fd::OwnedFd, sync::Arc};
+
+use smithay::{
+    reexports::calloop::{generic::Generic, Interest, Mode, PostAction},
+    wayland::selection::primary_selection::{
+        request_primary_client_selection, set_primary_selection,
+    },
+};
+
+use crate::state::State;
+
+/// Text types a primary selection is kept in once its owner is gone.
+const TEXT_MIME_TYPES: &[&str] = &[
+    "text/plain;charset=utf-8",
+    "UTF8_STRING",
+    "text/plain",
+    "TEXT",
+    "STRING",
+];
+
+/// Larger selections aren't kept. Staying within the pipe buffer also means
+/// handing the copy out never blocks.
+const MAX_CACHED_SELECTION: usize = 64 * 1024;
+
+/// Data behind a selection the compositor offers in place of a client.
+#[derive(Clone)]
+pub enum SelectionData {
+    /// Served by the X11 window owning it.
+    Xwayland,
+    /// Copy of a primary selection whose client dropped it or went away.
+    Cached(Arc<Vec<u8>>),
+}
+
+/// Copy of the primary selection, offered once its owner drops it.
+#[derive(Default)]
+pub struct PrimaryCache {
+    /// Bumped by every new selection and restore, reads started before that
+    /// are out of date and dropped.
+    generation: u64,
+    cached: Option<(Vec<String>, Arc<Vec<u8>>)>,
+}
+
+impl PrimaryCache {
+    /// Forgets the current copy, returns the generation to read the new one with.
+    pub fn begin(&mut self) -> u64 {
+        self.generation += 1;
+        self.cached = None;
+        self.generation
+    }
+
+    /// Keeps the data of a read started with `generation`, unless a newer
+    /// selection or a restore came in the meantime.
+    pub fn finish(&mut self, generation: u64, mime_types: Vec<String>, data: Arc<Vec<u8>>) -> bool {
+        if generation != self.generation {
+            return false;
+        }
+        self.cached = Some((mime_types, data));
+        true
+    }
+
+    /// Takes the copy to offer, reads still running are dropped once done.
+    pub fn take(&mut self) -> Option<(Vec<String>, Arc<Vec<u8>>)> {
+        self.generation += 1;
+        self.cached.take()
+    }
+}
+
+impl State {
+    /// Reads a copy of the new client-owned primary selection, so middle-click
+    /// paste keeps working after the client is closed.
+    pub fn cache_primary_selection(&mut self, mime_types: Vec<String>) {
+        let generation = self.primary_cache.begin();
+        let offered: Vec<String> = TEXT_MIME_TYPES
+            .iter()
+            .filter(|mime| mime_types.iter().any(|m| m == *mime))
+            .map(|mime| mime.to_string())
+            .collect();
+        let Some(mime_type) = offered.first().cloned() else {
+            return;
+        };
+
+        // The seat only points at the new source once the request is handled
+        self.loop_handle.insert_idle(move |state| {
+            let (reader, writer) = match std::io::pipe() {
+                Ok(pipe) => pipe,
+                Err(err) => {
+                    tracing::warn!("Failed to create selection pipe: {}", err);
+                    return;
+                }
+            };
+            if let Err(err) =
+                request_primary_client_selection(&state.seat, mime_type, OwnedFd::from(writer))
+            {
+                tracing::debug!(?err, "Failed to read primary selection");
+                return;
+            }
+            let mut data = Vec::new();
+            let result = state.loop_handle.insert_source(
+                Generic::new(reader, Interest::READ, Mode::Level),
+                move |_, reader, state| {
+                    let mut chunk = [0u8; 4096];
+                    match (&**reader).read(&mut chunk) {
+                        Ok(0) => {
+                            let data = Arc::new(std::mem::take(&mut data));
+                            if !state
+                                .primary_cache
+                                .finish(generation, offered.clone(), data)
+                            {
+                                tracing::debug!("Dropping out of date primary selection copy");
+                            }
+                            Ok(PostAction::Remove)
+                        }
+                        Ok(n) if data.len() + n <= MAX_CACHED_SELECTION => {
+                            data.extend_from_slice(&chunk[..n]);
+                            Ok(PostAction::Continue)
+                        }
+                        Ok(_) => Ok(PostAction::Remove),
+                        Err(err) if err.kind() == std::io::ErrorKind::Interrupted => {
+                            Ok(PostAction::Continue)
+                        }
+                        Err(_) => Ok(PostAction::Remove),
+                    }
+                },
+            );
+            if let Err(err) = result {
+                tracing::warn!("Failed to watch selection pipe: {}", err);
+            }
+        });
+    }
+
+    /// Offers the cached copy once the client-owned primary selection is gone.
+    pub fn restore_primary_selection(&mut self) {
+        if let Some((mime_types, data)) = self.primary_cache.take() {
+            set_primary_selection(
+                &self.display_handle,
+                &self.seat,
+                mime_types,
+                SelectionData::Cached(data),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn owner_closing_during_read_drops_the_read() {
+        let mut cache = PrimaryCache::default();
+        let generation = cache.begin();
+        // The owner goes away before its data is read, nothing to restore yet
+        assert!(cache.take().is_none());
+        let data = Arc::new(b"old".to_vec());
+        assert!(!cache.finish(generation, vec!["text/plain".into()], data));
+        assert!(cache.take().is_none());
+    }
+
+    #[test]
+    fn newer_selection_wins_over_slower_read() {
+        let mut cache = PrimaryCache::default();
+        let first = cache.begin();
+        let second = cache.begin();
+        let mime = vec!["text/plain".to_string()];
+        assert!(cache.finish(second, mime.clone(), Arc::new(b"new".to_vec())));
+        assert!(!cache.finish(first, mime, Arc::new(b"old".to_vec())));
+        let (_, data) = cache.take().unwrap();
+        assert_eq!(data.as_slice(), b"new");
+    }
+}
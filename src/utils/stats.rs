@@ -0,0 +1,92 @@
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+/// Counters of the render path, kept since the last `stats` query.
+pub struct RenderStats {
+    since: Instant,
+    frames_rendered: u64,
+    /// Repaints that found no damage and queued nothing.
+    frames_skipped: u64,
+    /// Repaints deferred by an output's `max_fps`.
+    frames_capped: u64,
+    reschedules: u64,
+    errors: u64,
+    /// Client buffers copied from the primary gpu to the others.
+    gpu_copies: u64,
+    /// Time spent building and submitting the rendered frames.
+    build_time: Duration,
+}
+
+/// What the `stats` ipc command replies with.
+#[derive(Serialize)]
+pub struct StatsReport {
+    pub seconds: f64,
+    pub frames_rendered: u64,
+    pub frames_skipped: u64,
+    pub frames_capped: u64,
+    pub reschedules: u64,
+    pub errors: u64,
+    pub gpu_copies: u64,
+    pub avg_frame_ms: f64,
+}
+
+impl RenderStats {
+    pub fn new() -> Self {
+        Self {
+            since: Instant::now(),
+            frames_rendered: 0,
+            frames_skipped: 0,
+            frames_capped: 0,
+            reschedules: 0,
+            errors: 0,
+            gpu_copies: 0,
+            build_time: Duration::ZERO,
+        }
+    }
+
+    /// Counts a repaint that got as far as rendering, `rendered` is None when it failed.
+    pub fn frame(&mut self, rendered: Option<bool>, build_time: Duration) {
+        match rendered {
+            Some(true) => {
+                self.frames_rendered += 1;
+                self.build_time += build_time;
+            }
+            Some(false) => self.frames_skipped += 1,
+            None => self.errors += 1,
+        }
+    }
+
+    pub fn capped(&mut self) {
+        self.frames_capped += 1;
+    }
+
+    pub fn rescheduled(&mut self) {
+        self.reschedules += 1;
+    }
+
+    pub fn gpu_copies(&mut self, copies: u64) {
+        self.gpu_copies += copies;
+    }
+
+    /// The counters since the last report, they start over afterwards.
+    pub fn take_report(&mut self) -> StatsReport {
+        let avg_frame_ms = if self.frames_rendered > 0 {
+            self.build_time.as_secs_f64() * 1000.0 / self.frames_rendered as f64
+        } else {
+            0.0
+        };
+        let report = StatsReport {
+            seconds: self.since.elapsed().as_secs_f64(),
+            frames_rendered: self.frames_rendered,
+            frames_skipped: self.frames_skipped,
+            frames_capped: self.frames_capped,
+            reschedules: self.reschedules,
+            errors: self.errors,
+            gpu_copies: self.gpu_copies,
+            avg_frame_ms,
+        };
+        *self = Self::new();
+        report
+    }
+}
@@ -0,0 +1,103 @@
+use smithay::{
+    desktop::{Window, WindowSurface},
+    reexports::wayland_server::{DisplayHandle, Resource},
+    utils::IsAlive,
+    wayland::seat::WaylandFocus,
+};
+
+use crate::{state::State, utils::workspaces::window_app_id};
+
+/// Terminal hidden while the window it spawned is mapped.
+pub struct Swallowed {
+    pub terminal: Window,
+    /// Workspace the terminal was taken from.
+    pub workspace: usize,
+}
+
+pub fn window_pid(window: &Window, dh: &DisplayHandle) -> Option<i32> {
+    match window.underlying_surface() {
+        WindowSurface::Wayland(xdg) => {
+            let client = xdg.wl_surface().client()?;
+            Some(client.get_credentials(dh).ok()?.pid)
+        }
+        #[cfg(feature = "xwayland")]
+        WindowSurface::X11(x11) => x11.pid().map(|pid| pid as i32),
+    }
+}
+
+fn parent_pid(pid: i32) -> Option<i32> {
+    let stat = std::fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+    // The command name may contain spaces, skip past its closing paren
+    let rest = &stat[stat.rfind(')')? + 1..];
+    rest.split_whitespace().nth(1)?.parse().ok()
+}
+
+fn is_descendant(mut pid: i32, ancestor: i32) -> bool {
+    for _ in 0..64 {
+        match parent_pid(pid) {
+            Some(ppid) if ppid == ancestor => return true,
+            Some(ppid) if ppid > 1 => pid = ppid,
+            _ => return false,
+        }
+    }
+    false
+}
+
+impl State {
+    /// Hides the terminal `window` was launched from, if swallowing is enabled.
+    pub fn swallow(&mut self, window: &Window) {
+        if !self.config.swallow {
+            return;
+        }
+        let Some(pid) = window_pid(window, &self.display_handle) else {
+            return;
+        };
+        let workspace = self.workspaces.active_ws();
+        let ws = self.workspaces.get_current_mut();
+        let terminal = ws
+            .space
+            .elements()
+            .filter(|w| *w != window)
+            .filter(|w| self.config.swallow_terminals.contains(&window_app_id(w)))
+            .find(|w| {
+                window_pid(w, &self.display_handle)
+                    .map(|terminal_pid| terminal_pid != pid && is_descendant(pid, terminal_pid))
+                    .unwrap_or(false)
+            })
+            .cloned();
+        if let Some(terminal) = terminal {
+            ws.space.unmap_elem(&terminal);
+            window.user_data().insert_if_missing(|| Swallowed {
+                terminal,
+                workspace,
+            });
+        }
+    }
+
+    /// Maps back the terminal swallowed by `window` on the workspace it
+    /// was taken from.
+    pub fn unswallow(&mut self, window: &Window) {
+        let Some(swallowed) = window.user_data().get::<Swallowed>() else {
+            return;
+        };
+        if !swallowed.terminal.alive() {
+            return;
+        }
+        let terminal = swallowed.terminal.clone();
+        let workspace = swallowed
+            .workspace
+            .min(self.workspaces.workspaces.len() - 1);
+        self.workspaces.workspaces[workspace].insert_window(
+            terminal.clone(),
+            self.config.border.gap_outer + self.config.border.thickness,
+            self.config.new_window_position,
+            &self.config.floating,
+            self.config.titlebars,
+            None,
+        );
+        self.refresh_layout_for(workspace);
+        if workspace == self.workspaces.active_ws() {
+            self.set_keyboard_focus(terminal.wl_surface().map(|s| s.as_ref().clone()));
+        }
+    }
+}
@@ -0,0 +1,26 @@
+use smithay::output::{Mode, Output, PhysicalProperties, Scale, Subpixel};
+
+/// An output called `name` running `size` at 60 Hz with an integer `scale`,
+/// placed at `loc`.
+pub fn output(name: &str, size: (i32, i32), scale: i32, loc: (i32, i32)) -> Output {
+    let output = Output::new(
+        name.to_string(),
+        PhysicalProperties {
+            size: (0, 0).into(),
+            subpixel: Subpixel::Unknown,
+            make: "test".to_string(),
+            model: "test".to_string(),
+        },
+    );
+    let mode = Mode {
+        size: size.into(),
+        refresh: 60_000,
+    };
+    output.change_current_state(
+        Some(mode),
+        None,
+        Some(Scale::Integer(scale)),
+        Some(loc.into()),
+    );
+    output
+}
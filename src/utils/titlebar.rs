@@ -0,0 +1,191 @@
+use std::cell::RefCell;
+
+use smithay::{
+    desktop::{Window, WindowSurface},
+    reexports::wayland_protocols::xdg::{
+        decoration::zv1::server::zxdg_toplevel_decoration_v1::Mode as DecorationMode,
+        shell::server::xdg_toplevel,
+    },
+    utils::{Logical, Point, Rectangle, Serial},
+    wayland::seat::WaylandFocus,
+};
+
+use crate::{
+    state::State,
+    utils::{
+        layout::TAB_BAR_HEIGHT,
+        workspaces::{centering_area, WindowMode},
+    },
+};
+
+pub const TITLEBAR_HEIGHT: i32 = TAB_BAR_HEIGHT;
+
+/// Part of a titlebar under the pointer.
+#[derive(PartialEq, Clone, Copy)]
+pub enum TitlebarHit {
+    Bar,
+    Maximize,
+    Close,
+}
+
+/// Geometry a floating window had before it was maximized.
+struct Maximized(RefCell<Option<Rectangle<i32, Logical>>>);
+
+/// Floating windows that leave decorations to the compositor get a titlebar.
+pub fn has_titlebar(window: &Window) -> bool {
+    let floating = window
+        .user_data()
+        .get::<RefCell<WindowMode>>()
+        .is_some_and(|mode| *mode.borrow() == WindowMode::Floating);
+    floating
+        && match window.underlying_surface() {
+            WindowSurface::Wayland(toplevel) => {
+                toplevel.current_state().decoration_mode == Some(DecorationMode::ServerSide)
+            }
+            #[cfg(feature = "xwayland")]
+            WindowSurface::X11(x11) => !x11.is_decorated(),
+        }
+}
+
+/// Height of the titlebar drawn above `window`, 0 without one.
+pub fn titlebar_height(window: &Window, titlebars: bool) -> i32 {
+    if titlebars && has_titlebar(window) {
+        TITLEBAR_HEIGHT
+    } else {
+        0
+    }
+}
+
+/// The bar sits right above the window geometry `geo`.
+pub fn titlebar_geometry(geo: Rectangle<i32, Logical>) -> Rectangle<i32, Logical> {
+    Rectangle::new(
+        (geo.loc.x, geo.loc.y - TITLEBAR_HEIGHT).into(),
+        (geo.size.w, TITLEBAR_HEIGHT).into(),
+    )
+}
+
+/// Square buttons at the right end of the bar, close being the last one.
+pub fn button_geometry(
+    bar: Rectangle<i32, Logical>,
+    button: TitlebarHit,
+) -> Rectangle<i32, Logical> {
+    let index = match button {
+        TitlebarHit::Close => 1,
+        _ => 2,
+    };
+    Rectangle::new(
+        (bar.loc.x + bar.size.w - TITLEBAR_HEIGHT * index, bar.loc.y).into(),
+        (TITLEBAR_HEIGHT, TITLEBAR_HEIGHT).into(),
+    )
+}
+
+fn hit(bar: Rectangle<i32, Logical>, point: Point<f64, Logical>) -> Option<TitlebarHit> {
+    [TitlebarHit::Close, TitlebarHit::Maximize]
+        .into_iter()
+        .find(|button| button_geometry(bar, *button).to_f64().contains(point))
+        .or_else(|| bar.to_f64().contains(point).then_some(TitlebarHit::Bar))
+}
+
+impl State {
+    /// Topmost titlebar under the pointer.
+    pub fn titlebar_under(&self) -> Option<(Window, TitlebarHit)> {
+        if !self.config.titlebars {
+            return None;
+        }
+        let ws = self.workspaces.get_current();
+        ws.space
+            .elements()
+            .rev()
+            .filter(|window| has_titlebar(window))
+            .find_map(|window| {
+                let geo = ws.space.element_geometry(window)?;
+                hit(titlebar_geometry(geo), self.pointer_location).map(|hit| (window.clone(), hit))
+            })
+    }
+
+    /// Handles a left click on a titlebar: close, maximize or start moving the window.
+    pub fn titlebar_click(&mut self, button: u32, serial: Serial) -> bool {
+        let Some((window, hit)) = self.titlebar_under() else {
+            return false;
+        };
+        let ws = self.workspaces.get_current_mut();
+        ws.space.raise_element(&window, true);
+        ws.active_window = Some(window.clone());
+        self.set_keyboard_focus(window.wl_surface().map(|s| s.as_ref().clone()));
+        match hit {
+            TitlebarHit::Close => match window.underlying_surface() {
+                WindowSurface::Wayland(xdg) => xdg.send_close(),
+                #[cfg(feature = "xwayland")]
+                WindowSurface::X11(x11) => {
+                    x11.close().ok();
+                }
+            },
+            TitlebarHit::Maximize => {
+                self.toggle_maximize(&window);
+            }
+            TitlebarHit::Bar => self.init_window_move_grab(window, None, button, serial),
+        }
+        true
+    }
+
+    /// Maximizes a floating window to the usable area of its output, or puts it
+    /// back where it was. Returns false for windows that aren't floating.
+    pub fn toggle_maximize(&mut self, window: &Window) -> bool {
+        let offset = self.config.border.gap_outer + self.config.border.thickness;
+        let ws = self.workspaces.get_current_mut();
+        let floating = window
+            .user_data()
+            .get::<RefCell<WindowMode>>()
+            .is_some_and(|mode| *mode.borrow() == WindowMode::Floating);
+        if !floating {
+            return false;
+        }
+        window
+            .user_data()
+            .insert_if_missing(|| Maximized(RefCell::new(None)));
+        let mut saved = window
+            .user_data()
+            .get::<Maximized>()
+            .unwrap()
+            .0
+            .borrow_mut();
+
+        let (geometry, maximized) = match saved.take() {
+            Some(prev) => (prev, false),
+            None => {
+                let Some(area) = centering_area(
+                    &ws.space,
+                    window,
+                    offset,
+                    &self.config.floating,
+                    self.config.titlebars,
+                ) else {
+                    return false;
+                };
+                *saved = ws.space.element_geometry(window);
+                (area, true)
+            }
+        };
+
+        match window.underlying_surface() {
+            WindowSurface::Wayland(xdg) => {
+                xdg.with_pending_state(|state| {
+                    state.size = Some(geometry.size);
+                    if maximized {
+                        state.states.set(xdg_toplevel::State::Maximized);
+                    } else {
+                        state.states.unset(xdg_toplevel::State::Maximized);
+                    }
+                });
+                xdg.send_pending_configure();
+            }
+            #[cfg(feature = "xwayland")]
+            WindowSurface::X11(x11) => {
+                x11.set_maximized(maximized).ok();
+                x11.configure(geometry).ok();
+            }
+        }
+        ws.space.map_element(window.clone(), geometry.loc, true);
+        true
+    }
+}
@@ -1,4 +1,7 @@
-use std::cell::RefCell;
+use std::{
+    cell::{Cell, RefCell},
+    sync::atomic::{AtomicU64, Ordering},
+};
 
 use smithay::{
     backend::renderer::{
@@ -8,11 +11,21 @@ use smithay::{
     desktop::{layer_map_for_output, space::SpaceElement, Space, Window, WindowSurface},
     output::Output,
     reexports::wayland_protocols::xdg::shell::server::xdg_toplevel,
-    utils::{Logical, Point, Rectangle, Size},
-    wayland::{seat::WaylandFocus, shell::xdg::ToplevelSurface},
+    utils::{user_data::UserDataMap, Logical, Point, Rectangle, Size},
+    wayland::{
+        compositor::with_states,
+        seat::WaylandFocus,
+        shell::xdg::{ToplevelSurface, XdgToplevelSurfaceData},
+    },
 };
 
-use crate::utils::{action::Direction, layout::LayoutState};
+use crate::utils::{
+    action::Direction,
+    autohide::usable_zone,
+    config::{Floating, NewWindowPosition},
+    layout::{clamp_master_size, LayoutState, MasterStack},
+    titlebar::titlebar_height,
+};
 
 #[derive(PartialEq, Clone)]
 pub enum WindowMode {
@@ -25,33 +38,129 @@ pub enum WindowMode {
 pub struct Workspace {
     pub space: Space<Window>,
     pub layout: LayoutState,
+    /// Layout used before the current one, for `LastLayout`.
+    pub previous_layout: Option<LayoutState>,
     pub active_window: Option<Window>,
     pub prev_window: Option<Window>,
+    /// Windows by when they were last focused, most recent first.
+    pub mru: Vec<Window>,
+    pub all_floating: bool,
+    /// Master area fraction, kept while another layout is in use.
+    pub master_ratio: f32,
+}
+
+/// Name of the output a window is tiled on.
+pub struct WindowOutput(pub RefCell<String>);
+
+pub fn window_output_name(window: &Window) -> Option<String> {
+    window
+        .user_data()
+        .get::<WindowOutput>()
+        .map(|output| output.0.borrow().clone())
+}
+
+pub fn set_window_output(window: &Window, output: &Output) {
+    window
+        .user_data()
+        .insert_if_missing(|| WindowOutput(RefCell::new(String::new())));
+    *window
+        .user_data()
+        .get::<WindowOutput>()
+        .unwrap()
+        .0
+        .borrow_mut() = output.name();
+}
+
+/// A toplevel that attached a null buffer without being destroyed. It keeps its
+/// mode, workspace and location until it maps again.
+pub struct UnmappedWindow {
+    pub window: Window,
+    pub workspace: usize,
+    pub loc: Point<i32, Logical>,
+}
+
+/// Mode a window had before `ToggleWorkspaceFloating` made it float.
+pub struct SavedWindowMode(pub RefCell<Option<WindowMode>>);
+
+/// Where a window was last floating, it goes back there when floated again.
+pub struct FloatingGeometry(pub RefCell<Option<Rectangle<i32, Logical>>>);
+
+impl FloatingGeometry {
+    /// Remembers `geo` in the user data of a window.
+    pub fn store(data: &UserDataMap, geo: Rectangle<i32, Logical>) {
+        data.insert_if_missing(|| FloatingGeometry(RefCell::new(None)));
+        *data.get::<FloatingGeometry>().unwrap().0.borrow_mut() = Some(geo);
+    }
+
+    pub fn stored(data: &UserDataMap) -> Option<Rectangle<i32, Logical>> {
+        data.get::<FloatingGeometry>().and_then(|geo| *geo.0.borrow())
+    }
 }
 
 impl Workspace {
-    pub fn new() -> Self {
+    pub fn new(master_ratio: f32) -> Self {
+        let master_ratio = clamp_master_size(master_ratio);
         Self {
             space: Space::default(),
-            layout: LayoutState::default(),
+            layout: LayoutState::MasterStack(MasterStack::new(master_ratio)),
+            previous_layout: None,
             active_window: None,
             prev_window: None,
+            mru: Vec::new(),
+            all_floating: false,
+            master_ratio,
+        }
+    }
+
+    /// A master/stack layout with this workspace's ratio.
+    pub fn master_stack(&self) -> LayoutState {
+        LayoutState::MasterStack(MasterStack::new(self.master_ratio))
+    }
+
+    /// The next tiling layout after the current one, master/stack after the
+    /// floating and tabbed layouts.
+    pub fn next_tiling_layout(&self) -> LayoutState {
+        match self.layout {
+            LayoutState::MasterStack(_) => LayoutState::Monocle,
+            LayoutState::Monocle => LayoutState::Grid,
+            LayoutState::Grid => LayoutState::Columns,
+            LayoutState::Columns | LayoutState::Floating | LayoutState::Tabbed(_) => {
+                self.master_stack()
+            }
         }
     }
+
+    /// Grows (or shrinks, for a negative `delta`) the master area, false
+    /// when the workspace isn't tiled as master/stack.
+    pub fn resize_master(&mut self, delta: f32) -> bool {
+        let LayoutState::MasterStack(layout) = &mut self.layout else {
+            return false;
+        };
+        layout.resize_master(delta);
+        self.master_ratio = layout.master_size();
+        true
+    }
 }
 
 pub struct Workspaces {
     pub workspaces: Vec<Workspace>,
     pub active_workspace: usize,
     pub prev_workspace: usize,
+    /// Windows pinned to every workspace. Each one lives in the space of the
+    /// active workspace and moves along on every switch.
+    pub sticky: Vec<Window>,
 }
 
 impl Workspaces {
-    pub fn new(w: u8) -> Self {
+    pub fn new(w: u8, master_ratio: f32) -> Self {
+        // Everything assumes a current workspace exists
         Self {
-            workspaces: (0..w).map(|_| Workspace::new()).collect(),
+            workspaces: (0..w.max(1))
+                .map(|_| Workspace::new(master_ratio))
+                .collect(),
             active_workspace: 0,
             prev_workspace: 0,
+            sticky: Vec::new(),
         }
     }
 
@@ -67,20 +176,97 @@ impl Workspaces {
         return self.active_workspace;
     }
 
+    /// Takes `window` out of whichever workspace holds it, returning that
+    /// workspace and the location the window had there.
+    pub fn take_window(&mut self, window: &Window) -> Option<(usize, Point<i32, Logical>)> {
+        let (index, loc) = take_element(
+            self.workspaces.iter_mut().map(|ws| &mut ws.space),
+            window,
+        )?;
+        let ws = &mut self.workspaces[index];
+        if ws.active_window.as_ref() == Some(window) {
+            ws.active_window = None;
+        }
+        Some((index, loc))
+    }
+
     pub fn is_ws_empty(&self, workspace: usize) -> bool {
-        return self.workspaces[workspace].space.elements().len() == 0;
+        self.workspaces
+            .get(workspace)
+            .is_none_or(|ws| ws.space.elements().len() == 0)
+    }
+
+    /// Whether `workspace` exists, warns about binds pointing past the configured count.
+    fn exists(&self, workspace: usize) -> bool {
+        let exists = workspace < self.workspaces.len();
+        if !exists {
+            tracing::warn!(
+                "No workspace {}, only {} are configured",
+                workspace.wrapping_add(1),
+                self.workspaces.len()
+            );
+        }
+        exists
     }
 
     pub fn set_active_workspace(&mut self, workspace: usize) {
-        if workspace >= self.workspaces.len() {
+        if !self.exists(workspace) {
             return;
         }
+        self.carry_sticky(self.active_workspace, workspace);
         self.prev_workspace = self.active_workspace;
         self.active_workspace = workspace;
     }
 
-    pub fn move_window_to_ws(&mut self, ws_index: usize) {
-        if self.active_workspace == ws_index {
+    pub fn is_sticky(&self, window: &Window) -> bool {
+        self.sticky.contains(window)
+    }
+
+    /// Pins `window` to every workspace, or unpins it. Returns whether it is sticky now.
+    pub fn toggle_sticky(&mut self, window: &Window) -> bool {
+        if self.is_sticky(window) {
+            self.sticky.retain(|w| w != window);
+            false
+        } else {
+            self.sticky.push(window.clone());
+            true
+        }
+    }
+
+    /// Moves the sticky windows from workspace `from` to `to`, at the same
+    /// place and above its windows. A fullscreen window covers its own
+    /// workspace only, it stays behind until it leaves fullscreen.
+    fn carry_sticky(&mut self, from: usize, to: usize) {
+        let workspaces = &self.workspaces;
+        self.sticky.retain(|window| {
+            workspaces
+                .iter()
+                .any(|ws| ws.space.elements().any(|w| w == window))
+        });
+        if from == to {
+            return;
+        }
+        for window in &self.sticky {
+            let fullscreen = window
+                .user_data()
+                .get::<RefCell<WindowMode>>()
+                .is_some_and(|mode| matches!(*mode.borrow(), WindowMode::Fullscreen(_)));
+            let old = &mut self.workspaces[from];
+            let Some(loc) = old.space.element_location(window).filter(|_| !fullscreen) else {
+                continue;
+            };
+            old.space.unmap_elem(window);
+            if old.active_window.as_ref() == Some(window) {
+                old.active_window = None;
+            }
+            self.workspaces[to]
+                .space
+                .map_element(window.clone(), loc, false);
+        }
+    }
+
+    pub fn move_window_to_ws(&mut self, ws_index: usize, floating: &Floating, titlebars: bool) {
+        if self.active_workspace == ws_index || !self.exists(ws_index) {
             return;
         }
         let ws = self.get_current_mut();
@@ -90,15 +276,52 @@ impl Workspaces {
         };
 
         let loc = ws.space.element_location(&active);
-        ws.insert_window(active.clone(), 0);
-        ws.space.unmap_elem(&active);
+        ws.remove_window(&active);
+        // A window sent to a workspace stays there
+        self.sticky.retain(|w| *w != active);
         self.set_active_workspace(ws_index);
-        if let Some(loc) = loc {
-            self.get_current_mut()
-                .space
-                .map_element(active.clone(), loc, false);
+        let ws = self.get_current_mut();
+        match loc {
+            // It keeps its place, which for tiled windows is its order in the layout
+            Some(loc) => {
+                ws.space.map_element(active.clone(), loc, false);
+                ws.set_active_window(Some(active));
+            }
+            None => ws.insert_window(
+                active,
+                0,
+                NewWindowPosition::default(),
+                floating,
+                titlebars,
+                None,
+            ),
         }
     }
+
+    /// Moves `window` from the current workspace to `ws_index` without
+    /// switching to it, false when it stays where it is.
+    pub fn send_window_to_ws(
+        &mut self,
+        window: &Window,
+        ws_index: usize,
+        floating: &Floating,
+        titlebars: bool,
+    ) -> bool {
+        if self.active_workspace == ws_index || !self.exists(ws_index) {
+            return false;
+        }
+        self.sticky.retain(|w| w != window);
+        self.get_current_mut().remove_window(window);
+        self.workspaces[ws_index].insert_window(
+            window.clone(),
+            0,
+            NewWindowPosition::default(),
+            floating,
+            titlebars,
+            None,
+        );
+        true
+    }
 }
 impl Workspace {
     pub fn set_active_window(&mut self, window: Option<Window>) {
@@ -108,34 +331,93 @@ impl Workspace {
     pub fn get_active_window(&self) -> Option<Window> {
         self.active_window.clone()
     }
+
+    /// Moves `window` to the front of the most-recently-used order.
+    pub fn touch_mru(&mut self, window: &Window) {
+        if !self.space.elements().any(|w| w == window) {
+            return;
+        }
+        self.mru.retain(|w| w != window);
+        self.mru.insert(0, window.clone());
+    }
+
+    /// The windows in most-recently-used order. Closed windows are dropped,
+    /// windows that were never focused come last.
+    pub fn mru_windows(&mut self) -> &[Window] {
+        let space = &self.space;
+        self.mru.retain(|w| space.elements().any(|e| e == w));
+        for window in space.elements() {
+            if !self.mru.contains(window) {
+                self.mru.push(window.clone());
+            }
+        }
+        &self.mru
+    }
     pub fn remove_window(&mut self, window: &Window) {
         self.space.unmap_elem(window);
         self.active_window = None;
     }
 
-    pub fn insert_window(&mut self, window: Window, offset: i32) {
+    pub fn insert_window(
+        &mut self,
+        window: Window,
+        offset: i32,
+        position: NewWindowPosition,
+        floating: &Floating,
+        titlebars: bool,
+        output: Option<&Output>,
+    ) {
+        if let Some(output) = output {
+            set_window_output(&window, output);
+        }
         match self.layout {
             crate::utils::layout::LayoutState::Floating => {
                 window
                     .user_data()
                     .insert_if_missing(|| RefCell::new(WindowMode::Floating));
-                place_on_center(&mut self.space, &window, offset);
+                place_on_center(&mut self.space, &window, offset, floating, titlebars);
             }
             _ => {
                 window
                     .user_data()
                     .insert_if_missing(|| RefCell::new(WindowMode::Tiled));
-                self.space.map_element(window.clone(), (0, 0), true);
+                let loc = self.new_window_location(position);
+                self.space.map_element(window.clone(), loc, true);
             }
         }
         self.active_window = Some(window.clone());
     }
 
-    pub fn change_focus(&mut self, direction: &Direction, loc: &mut Point<f64, Logical>) {
+    /// Tiled windows are ordered by location (top to bottom, left to right),
+    /// so the initial location decides where a new window ends up in the layout.
+    fn new_window_location(&self, position: NewWindowPosition) -> Point<i32, Logical> {
+        let master = self
+            .space
+            .elements()
+            .filter(|w| {
+                w.user_data()
+                    .get::<RefCell<WindowMode>>()
+                    .map(|d| *d.borrow() == WindowMode::Tiled)
+                    .unwrap_or(false)
+            })
+            .filter_map(|w| self.space.element_location(w))
+            .min_by(|a, b| a.y.cmp(&b.y).then(a.x.cmp(&b.x)));
+
+        match (position, master) {
+            (NewWindowPosition::StackTop, Some(master)) => master + Point::from((1, 0)),
+            (NewWindowPosition::StackBottom, _) => Point::from((i32::MAX / 2, i32::MAX / 2)),
+            _ => Point::from((i32::MIN / 2, i32::MIN / 2)),
+        }
+    }
+
+    /// Moves `loc` onto the nearest window in `direction`, returns false if there is none.
+    pub fn change_focus(&mut self, direction: &Direction, loc: &mut Point<f64, Logical>) -> bool {
         let focused = self.get_active_window();
         if let Some((window, _)) = best_window(direction, &self.space, focused) {
             *loc = window_center(&self.space, &window).unwrap();
+            return true;
         }
+        false
     }
 
     pub fn move_window(&mut self, direction: &Direction, loc: &mut Point<f64, Logical>) {
@@ -166,9 +448,183 @@ impl Workspace {
         self.space.map_element(best, focused_pos, false);
     }
 
+    /// Switches to `layout`, keeping the current one as the previous layout.
+    /// The floating layout floats every window, the others tile them again.
+    pub fn set_layout(&mut self, layout: LayoutState) {
+        let retile = match layout {
+            LayoutState::Floating => false,
+            LayoutState::MasterStack(_)
+            | LayoutState::Monocle
+            | LayoutState::Grid
+            | LayoutState::Columns => matches!(self.layout, LayoutState::Floating),
+            LayoutState::Tabbed(_) => true,
+        };
+        for item in self.space.elements() {
+            if let Some(data) = item.user_data().get::<RefCell<WindowMode>>() {
+                if matches!(layout, LayoutState::Floating) {
+                    *data.borrow_mut() = WindowMode::Floating;
+                } else if retile && *data.borrow() == WindowMode::Floating {
+                    save_floating_geometry(&self.space, item);
+                    *data.borrow_mut() = WindowMode::Tiled;
+                }
+            }
+        }
+        self.previous_layout = Some(std::mem::replace(&mut self.layout, layout));
+    }
+
+    /// Floats every window on the workspace, or restores the modes they had before.
+    pub fn toggle_all_floating(&mut self) {
+        self.all_floating = !self.all_floating;
+        for window in self.space.elements() {
+            let Some(mode) = window.user_data().get::<RefCell<WindowMode>>() else {
+                continue;
+            };
+            if !matches!(*mode.borrow(), WindowMode::Tiled | WindowMode::Floating) {
+                continue;
+            }
+            window
+                .user_data()
+                .insert_if_missing(|| SavedWindowMode(RefCell::new(None)));
+            let saved = &window.user_data().get::<SavedWindowMode>().unwrap().0;
+
+            if self.all_floating {
+                *saved.borrow_mut() = Some(mode.borrow().clone());
+                *mode.borrow_mut() = WindowMode::Floating;
+            } else {
+                save_floating_geometry(&self.space, window);
+                *mode.borrow_mut() = saved.borrow_mut().take().unwrap_or(WindowMode::Tiled);
+            }
+        }
+    }
+
+    /// In the tabbed and monocle layouts, the topmost tiled window is the visible tab.
+    pub fn visible_tab(&self) -> Option<&Window> {
+        if !self.layout.is_stacked() {
+            return None;
+        }
+        self.space.elements().rev().find(|w| {
+            w.user_data()
+                .get::<RefCell<WindowMode>>()
+                .map(|d| *d.borrow() == WindowMode::Tiled)
+                .unwrap_or(false)
+        })
+    }
+
+    /// Windows shown over a pinned fullscreen window, bottom to top: floating
+    /// windows and the focused window.
+    pub fn above_fullscreen(&self, fullscreen: &Window) -> Vec<Window> {
+        self.space
+            .elements()
+            .filter(|w| *w != fullscreen)
+            .filter(|w| {
+                Some(*w) == self.active_window.as_ref()
+                    || w.user_data()
+                        .get::<RefCell<WindowMode>>()
+                        .map(|d| *d.borrow() == WindowMode::Floating)
+                        .unwrap_or(false)
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// The only tiled window on screen, if there is exactly one (or the visible tab).
+    pub fn single_visible_tile(&self) -> Option<&Window> {
+        if self.layout.is_stacked() {
+            return self.visible_tab();
+        }
+        let mut tiled = self.space.elements().filter(|w| {
+            w.user_data()
+                .get::<RefCell<WindowMode>>()
+                .map(|d| *d.borrow() == WindowMode::Tiled)
+                .unwrap_or(false)
+        });
+        match (tiled.next(), tiled.next()) {
+            (Some(window), None) => Some(window),
+            _ => None,
+        }
+    }
+
     fn render_elements(&self) {}
 }
 
+/// Unmaps `element` from the first of `spaces` holding it, returning the
+/// index of that space and the element's location in it.
+pub fn take_element<'a, E>(
+    spaces: impl IntoIterator<Item = &'a mut Space<E>>,
+    element: &E,
+) -> Option<(usize, Point<i32, Logical>)>
+where
+    E: SpaceElement + PartialEq + 'a,
+{
+    spaces.into_iter().enumerate().find_map(|(index, space)| {
+        let loc = space.element_location(element)?;
+        space.unmap_elem(element);
+        Some((index, loc))
+    })
+}
+
+/// Stable identifier of a window, assigned on first use.
+pub struct WindowId(pub u64);
+
+pub fn window_id(window: &Window) -> u64 {
+    static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+    window
+        .user_data()
+        .insert_if_missing(|| WindowId(NEXT_ID.fetch_add(1, Ordering::Relaxed)));
+    window.user_data().get::<WindowId>().unwrap().0
+}
+
+/// Set on windows whose client didn't answer the last `xdg_wm_base` ping.
+pub struct Unresponsive(pub Cell<bool>);
+
+pub fn is_unresponsive(window: &Window) -> bool {
+    window
+        .user_data()
+        .get::<Unresponsive>()
+        .map(|u| u.0.get())
+        .unwrap_or(false)
+}
+
+pub fn set_unresponsive(window: &Window, unresponsive: bool) {
+    window
+        .user_data()
+        .insert_if_missing(|| Unresponsive(Cell::new(false)));
+    window
+        .user_data()
+        .get::<Unresponsive>()
+        .unwrap()
+        .0
+        .set(unresponsive);
+}
+
+pub fn window_app_id(window: &Window) -> String {
+    match window.underlying_surface() {
+        WindowSurface::Wayland(xdg) => with_states(xdg.wl_surface(), |states| {
+            states
+                .data_map
+                .get::<XdgToplevelSurfaceData>()
+                .and_then(|data| data.lock().unwrap().app_id.clone())
+        })
+        .unwrap_or_default(),
+        #[cfg(feature = "xwayland")]
+        WindowSurface::X11(x11) => x11.class(),
+    }
+}
+
+pub fn window_title(window: &Window) -> String {
+    match window.underlying_surface() {
+        WindowSurface::Wayland(xdg) => with_states(xdg.wl_surface(), |states| {
+            states
+                .data_map
+                .get::<XdgToplevelSurfaceData>()
+                .and_then(|data| data.lock().unwrap().title.clone())
+        })
+        .unwrap_or_default(),
+        #[cfg(feature = "xwayland")]
+        WindowSurface::X11(x11) => x11.title(),
+    }
+}
+
 pub fn is_fullscreen<'a, I>(elements: I) -> Option<&'a Window>
 where
     I: Iterator<Item = &'a Window>,
@@ -261,41 +717,382 @@ pub fn best_window(
     best
 }
 
-pub fn place_on_center(space: &mut Space<Window>, window: &Window, offset: i32) {
-    let output = match space.outputs().next().cloned() {
-        Some(o) => o,
-        None => return,
+/// Geometry of the closest output next to the one containing `loc` in `direction`.
+pub fn output_in_direction(
+    space: &Space<Window>,
+    loc: Point<f64, Logical>,
+    direction: &Direction,
+) -> Option<Rectangle<i32, Logical>> {
+    let current = space
+        .outputs()
+        .filter_map(|o| space.output_geometry(o))
+        .find(|geo| geo.to_f64().contains(loc))?;
+
+    space
+        .outputs()
+        .filter_map(|o| space.output_geometry(o))
+        .filter(|geo| match direction {
+            Direction::Left => geo.loc.x + geo.size.w <= current.loc.x,
+            Direction::Right => geo.loc.x >= current.loc.x + current.size.w,
+            Direction::Top => geo.loc.y + geo.size.h <= current.loc.y,
+            Direction::Down => geo.loc.y >= current.loc.y + current.size.h,
+        })
+        .min_by_key(|geo| (geo.loc.x - current.loc.x).abs() + (geo.loc.y - current.loc.y).abs())
+}
+
+/// Moves `loc` so at least `strip` pixels of a window of `size` stay on an output,
+/// including its top edge where the titlebar is drawn, `titlebar` pixels above
+/// `loc` for server-side ones.
+pub fn constrain_to_outputs(
+    space: &Space<Window>,
+    loc: Point<i32, Logical>,
+    size: Size<i32, Logical>,
+    strip: i32,
+    titlebar: i32,
+) -> Point<i32, Logical> {
+    let strip_w = strip.min(size.w);
+    let strip_h = strip.min(size.h);
+    let candidates: Vec<Point<i32, Logical>> = space
+        .outputs()
+        .filter_map(|output| space.output_geometry(output))
+        .map(|geo| {
+            let min_x = geo.loc.x - size.w + strip_w;
+            let max_x = geo.loc.x + geo.size.w - strip_w;
+            let min_y = geo.loc.y + titlebar;
+            let max_y = geo.loc.y + geo.size.h - strip_h;
+            Point::from((
+                loc.x.clamp(min_x, max_x.max(min_x)),
+                loc.y.clamp(min_y, max_y.max(min_y)),
+            ))
+        })
+        .collect();
+    if candidates.contains(&loc) {
+        return loc;
+    }
+    candidates
+        .into_iter()
+        .min_by_key(|p| (p.x - loc.x).pow(2) + (p.y - loc.y).pow(2))
+        .unwrap_or(loc)
+}
+
+/// The output showing the center of `window`, or the first one for unmapped windows.
+pub fn window_output(space: &Space<Window>, window: &Window) -> Option<Output> {
+    space
+        .element_geometry(window)
+        .and_then(|geo| {
+            let center = geo.loc + geo.size.downscale(2).to_point();
+            space
+                .outputs()
+                .find(|o| {
+                    space
+                        .output_geometry(o)
+                        .is_some_and(|output_geo| output_geo.contains(center))
+                })
+                .or_else(|| space.outputs_for_element(window).first())
+                .cloned()
+        })
+        .or_else(|| {
+            let name = window_output_name(window)?;
+            space.outputs().find(|o| o.name() == name).cloned()
+        })
+        .or_else(|| space.outputs().next().cloned())
+}
+
+/// Usable area of the output of `window` that floating windows are centered in.
+/// The window geometry goes in it, so its top leaves room for the titlebar.
+pub fn centering_area(
+    space: &Space<Window>,
+    window: &Window,
+    offset: i32,
+    floating: &Floating,
+    titlebars: bool,
+) -> Option<Rectangle<i32, Logical>> {
+    let output = window_output(space, window)?;
+    let output_geo = space.output_geometry(&output)?;
+
+    let zone = usable_zone(&layer_map_for_output(&output));
+    let margin = offset + floating.margin;
+    let titlebar = titlebar_height(window, titlebars);
+    Some(Rectangle::new(
+        output_geo.loc + zone.loc + Point::from((margin, margin + titlebar)),
+        (
+            (zone.size.w - margin * 2).max(1),
+            (zone.size.h - margin * 2 - titlebar).max(1),
+        )
+            .into(),
+    ))
+}
+
+/// Moves `window` to the center of its output, keeping its size.
+pub fn center_window(
+    space: &mut Space<Window>,
+    window: &Window,
+    offset: i32,
+    floating: &Floating,
+    titlebars: bool,
+) {
+    let Some(area) = centering_area(space, window, offset, floating, titlebars) else {
+        return;
     };
+    let Some(geo) = space.element_geometry(window) else {
+        return;
+    };
+    let location = Point::from((
+        area.loc.x + (area.size.w - geo.size.w) / 2,
+        area.loc.y + (area.size.h - geo.size.h) / 2,
+    ));
+    space.map_element(window.clone(), location, true);
+}
 
-    let output_geo = match space.output_geometry(&output) {
-        Some(g) => g,
-        None => return,
+pub fn place_on_center(
+    space: &mut Space<Window>,
+    window: &Window,
+    offset: i32,
+    floating: &Floating,
+    titlebars: bool,
+) {
+    let Some(area) = centering_area(space, window, offset, floating, titlebars) else {
+        return;
     };
 
-    let layer_map = layer_map_for_output(&output);
-    let zone = layer_map.non_exclusive_zone();
-    let area = Rectangle::new(output_geo.loc + zone.loc, zone.size);
+    let requested = space
+        .element_geometry(window)
+        .map(|geo| geo.size)
+        .unwrap_or_else(|| window.geometry().size);
+    // Nothing committed yet, the client picks its size and gets centered
+    // once it did
+    let unsized = requested.is_empty() && floating.default_size.is_none();
+    set_pending_center(window, unsized);
+    let size = if unsized {
+        Size::default()
+    } else {
+        floating.window_size(area.size, requested)
+    };
+    let location = Point::from((
+        area.loc.x + (area.size.w - size.w) / 2,
+        area.loc.y + (area.size.h - size.h) / 2,
+    ));
 
-    // Set bounds so the client knows the maximum size
-    if let Some(toplevel) = window.toplevel() {
-        toplevel.with_pending_state(|state| {
-            state.bounds = Some(area.size);
-        });
+    match window.underlying_surface() {
+        WindowSurface::Wayland(toplevel) => {
+            // Set bounds so the client knows the maximum size
+            toplevel.with_pending_state(|state| {
+                state.bounds = Some(area.size);
+                if !unsized {
+                    state.size = Some(size);
+                }
+            });
+            if toplevel.is_initial_configure_sent() {
+                toplevel.send_pending_configure();
+            }
+        }
+        #[cfg(feature = "xwayland")]
+        WindowSurface::X11(_) if unsized => {}
+        #[cfg(feature = "xwayland")]
+        WindowSurface::X11(x11) => {
+            if let Err(err) = x11.configure(Rectangle::new(location, size)) {
+                tracing::warn!("Failed to configure floating window: {}", err);
+            }
+        }
     }
+    space.map_element(window.clone(), location, true);
+}
 
-    let window_geo = space.element_geometry(&window);
-    match window_geo {
+/// Set on floating windows placed before their first commit, they are
+/// centered again once their size is known.
+pub struct PendingCenter(pub Cell<bool>);
+
+pub fn is_pending_center(window: &Window) -> bool {
+    window
+        .user_data()
+        .get::<PendingCenter>()
+        .map(|p| p.0.get())
+        .unwrap_or(false)
+}
+
+fn set_pending_center(window: &Window, pending: bool) {
+    window
+        .user_data()
+        .insert_if_missing(|| PendingCenter(Cell::new(false)));
+    window
+        .user_data()
+        .get::<PendingCenter>()
+        .unwrap()
+        .0
+        .set(pending);
+}
+
+/// Gives a floating window a fixed `size` and a `position` relative to its
+/// output, keeping its current value for whichever is None. The position is
+/// that of the titlebar when the window has one.
+pub fn place_fixed(
+    space: &mut Space<Window>,
+    window: &Window,
+    size: Option<Size<i32, Logical>>,
+    position: Option<Point<i32, Logical>>,
+    titlebars: bool,
+) {
+    let Some(geo) = space.element_geometry(window) else {
+        return;
+    };
+    let size = size.unwrap_or(geo.size);
+    let titlebar = titlebar_height(window, titlebars);
+    let location = position
+        .and_then(|position| {
+            let output = window_output(space, window)?;
+            Some(space.output_geometry(&output)?.loc + position + Point::from((0, titlebar)))
+        })
+        .unwrap_or(geo.loc);
+
+    match window.underlying_surface() {
+        WindowSurface::Wayland(toplevel) => {
+            toplevel.with_pending_state(|state| state.size = Some(size));
+            if toplevel.is_initial_configure_sent() {
+                toplevel.send_pending_configure();
+            }
+        }
+        #[cfg(feature = "xwayland")]
+        WindowSurface::X11(x11) => {
+            if let Err(err) = x11.configure(Rectangle::new(location, size)) {
+                tracing::warn!("Failed to configure floating window: {}", err);
+            }
+        }
+    }
+    space.map_element(window.clone(), location, true);
+}
+
+/// Remembers the geometry of the floating `window` for `restore_floating_geometry`.
+pub fn save_floating_geometry(space: &Space<Window>, window: &Window) {
+    let Some(geo) = space.element_geometry(window) else {
+        return;
+    };
+    FloatingGeometry::store(window.user_data(), geo);
+}
+
+/// Floats `window` where it was floating last time, or centers it like
+/// `place_on_center` when it never floated before.
+pub fn restore_floating_geometry(
+    space: &mut Space<Window>,
+    window: &Window,
+    offset: i32,
+    floating: &Floating,
+    titlebars: bool,
+) {
+    match FloatingGeometry::stored(window.user_data()) {
         Some(geo) => {
-            let x = area.loc.x + (area.size.w - geo.size.w) / 2;
-            let y = area.loc.y + (area.size.h - geo.size.h) / 2;
+            space.map_element(window.clone(), geo.loc, false);
+            place_fixed(space, window, Some(geo.size), None, titlebars);
+        }
+        None => place_on_center(space, window, offset, floating, titlebars),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use smithay::utils::IsAlive;
+
+    use super::*;
 
-            let location = Point::from((x, y));
+    #[derive(Clone, PartialEq)]
+    struct Tile(u32);
 
-            space.map_element(window.clone(), location, true);
+    impl IsAlive for Tile {
+        fn alive(&self) -> bool {
+            true
         }
-        None => {
-            let location = Point::from((area.loc.x + offset, area.loc.y + offset));
-            space.map_element(window.clone(), location, true);
+    }
+
+    impl SpaceElement for Tile {
+        fn bbox(&self) -> Rectangle<i32, Logical> {
+            Rectangle::from_size((100, 100).into())
+        }
+        fn is_in_input_region(&self, point: &Point<f64, Logical>) -> bool {
+            self.bbox().to_f64().contains(*point)
+        }
+        fn set_activate(&self, _activated: bool) {}
+        fn output_enter(&self, _output: &Output, _overlap: Rectangle<i32, Logical>) {}
+        fn output_leave(&self, _output: &Output) {}
+    }
+
+    #[test]
+    fn unmap_and_remap_on_hidden_workspace() {
+        let mut spaces: Vec<Space<Tile>> = (0..3).map(|_| Space::default()).collect();
+        spaces[0].map_element(Tile(1), (0, 0), false);
+        spaces[2].map_element(Tile(2), (40, 30), false);
+
+        // Workspace 0 is shown, the window unmaps on workspace 2
+        let (workspace, loc) = take_element(spaces.iter_mut(), &Tile(2)).unwrap();
+        assert_eq!(workspace, 2);
+        assert_eq!(loc, Point::from((40, 30)));
+        assert_eq!(spaces[2].elements().count(), 0);
+        assert_eq!(spaces[0].elements().count(), 1);
+
+        // Remapped where it was, as remap_toplevel restores it
+        spaces[workspace].map_element(Tile(2), loc, false);
+        assert_eq!(spaces[2].element_location(&Tile(2)), Some(loc));
+        assert!(take_element(spaces.iter_mut(), &Tile(3)).is_none());
+    }
+
+    #[test]
+    fn floating_location_survives_tiling() {
+        let mut space = Space::<Tile>::default();
+        let data = UserDataMap::new();
+        space.map_element(Tile(1), (250, 120), false);
+
+        // Floating to tiled: remembered, then moved by the layout
+        FloatingGeometry::store(&data, space.element_geometry(&Tile(1)).unwrap());
+        space.map_element(Tile(1), (0, 0), false);
+
+        // Tiled to floating: back where it was
+        let saved = FloatingGeometry::stored(&data).unwrap();
+        space.map_element(Tile(1), saved.loc, false);
+        assert_eq!(
+            space.element_location(&Tile(1)),
+            Some(Point::from((250, 120)))
+        );
+        assert_eq!(saved.size, Size::from((100, 100)));
+    }
+
+    #[test]
+    fn switching_workspaces_remembers_the_previous_one() {
+        let mut workspaces = Workspaces::new(3, 0.5);
+        assert_eq!(workspaces.active_ws(), 0);
+        assert!(workspaces.is_ws_empty(2));
+
+        workspaces.set_active_workspace(2);
+        assert_eq!(workspaces.active_ws(), 2);
+        assert_eq!(workspaces.prev_workspace, 0);
+
+        // A bind past the configured count changes nothing
+        workspaces.set_active_workspace(3);
+        workspaces.set_active_workspace(0usize.wrapping_sub(1));
+        assert_eq!(workspaces.active_ws(), 2);
+        assert_eq!(workspaces.prev_workspace, 0);
+
+        workspaces.set_active_workspace(workspaces.prev_workspace);
+        assert_eq!(workspaces.active_ws(), 0);
+        assert_eq!(workspaces.prev_workspace, 2);
+    }
+
+    #[test]
+    fn master_ratio_outlives_other_layouts() {
+        let mut ws = Workspace::new(0.5);
+        assert!(ws.resize_master(0.2));
+        assert!((ws.master_ratio - 0.7).abs() < 1e-6);
+
+        // Clamped, and kept through every other tiling layout
+        assert!(ws.resize_master(1.0));
+        let mut names = Vec::new();
+        loop {
+            ws.layout = ws.next_tiling_layout();
+            names.push(ws.layout.name());
+            if let LayoutState::MasterStack(layout) = &ws.layout {
+                assert_eq!(layout.master_size(), 0.9);
+                break;
+            }
+            assert!(!ws.resize_master(-0.1));
         }
+        assert_eq!(names, ["monocle", "grid", "columns", "masterstack"]);
+        assert_eq!(ws.master_ratio, 0.9);
     }
 }